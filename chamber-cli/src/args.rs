@@ -1,9 +1,20 @@
+use chamber_shared::policy::Capability;
+use chamber_shared::secrets::SecretSort;
+use chrono::{DateTime, Utc};
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
+    /// The namespace to log in to, or for root key commands to act on. Defaults to the
+    /// default namespace.
+    #[arg(long, global = true)]
+    pub namespace: Option<String>,
+    /// The environment, such as staging or prod, that secrets commands read from and write to.
+    /// Defaults to the default environment.
+    #[arg(long = "env", global = true)]
+    pub environment: Option<String>,
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -20,21 +31,111 @@ pub enum Commands {
         #[command(subcommand)]
         cmd: UserCommands,
     },
+    /// Commands related to access policies. Note that your root key is required for this.
+    Policies {
+        #[command(subcommand)]
+        cmd: PolicyCommands,
+    },
+    /// Commands related to role management. Note that your root key is required for this.
+    Roles {
+        #[command(subcommand)]
+        cmd: RoleCommands,
+    },
+    /// Commands related to user groups. Note that your root key is required for this.
+    Groups {
+        #[command(subcommand)]
+        cmd: GroupCommands,
+    },
+    /// Commands related to namespaces. Note that your root key is required for this.
+    Namespaces {
+        #[command(subcommand)]
+        cmd: NamespaceCommands,
+    },
+    /// Explain who can access what. Note that your root key is required for this.
+    Access {
+        #[command(subcommand)]
+        cmd: AccessCommands,
+    },
+    /// Review promotions between environments. Note that your root key is required for this.
+    Audit {
+        /// How many of the most recent entries to show
+        #[arg(long, short = 'l', default_value_t = 50)]
+        limit: i64,
+    },
+    /// Review failed login and unseal attempts. Note that your root key is required for this.
+    Attempts {
+        /// How many of the most recent attempts to show
+        #[arg(long, short = 'l', default_value_t = 50)]
+        limit: i64,
+    },
     /// Commands related to setting/getting the URL for your Chamber instance.
     Website {
         #[command(subcommand)]
         cmd: WebsiteCommands,
     },
+    /// Unseal a fresh Chamber instance and create its first admin user.
+    Init(InitArgs),
     /// Log in to your Chamber instance.
-    Login(LoginArgs),
+    Login {
+        #[command(flatten)]
+        credentials: LoginArgs,
+        /// A code from your authenticator app, or one of your recovery codes.
+        /// You'll be prompted for one if it's needed and left out.
+        #[arg(long, short = 'c')]
+        code: Option<String>,
+        /// Log in by signing a challenge with this ed25519 private key instead of using a password.
+        #[arg(long, short = 'k')]
+        ssh_key: Option<PathBuf>,
+        /// Sign the challenge with your ssh-agent instead.
+        /// Uses the key given with --ssh-key if there is one, otherwise the agent's first ed25519 key.
+        #[arg(long, short = 'a')]
+        ssh_agent: bool,
+        /// Log in with this PEM client certificate instead of a password, as the user named in
+        /// its subject. Only works when the server terminates TLS with a client CA.
+        #[arg(long, requires = "cert_key")]
+        cert: Option<PathBuf>,
+        /// The PKCS#8 PEM private key for --cert.
+        #[arg(long, requires = "cert")]
+        cert_key: Option<PathBuf>,
+        /// Use a token from an external identity provider, such as a CI workload identity token,
+        /// instead of logging in. Only the secrets commands accept these.
+        #[arg(long, conflicts_with_all = ["ssh_key", "ssh_agent", "cert"])]
+        token: Option<String>,
+    },
+    /// Show the user you're logged in as, along with your roles and access level.
+    Whoami,
+    /// Change the password of the user you're logged in as.
+    Passwd,
+    /// Commands related to two-factor authentication for the user you're logged in as.
+    #[command(name = "2fa")]
+    TwoFactor {
+        #[command(subcommand)]
+        cmd: TwoFactorCommands,
+    },
     /// Commands related to generating keys for your Chamber instance.
     Keygen(KeygenArgs),
-    /// Unseal your Chamber instance.
+    /// Unseal your Chamber instance, either with a registered unseal key or with the root key
+    /// if no unseal keys have been registered yet.
     Unseal {
-        chamber_key: String,
+        chamber_key: Option<String>,
+        /// Sign the unseal challenge with this ed25519 private key.
+        #[arg(long, short = 'k')]
+        ssh_key: Option<PathBuf>,
+        /// Sign the unseal challenge with your ssh-agent instead.
+        #[arg(long, short = 'a')]
+        ssh_agent: bool,
+    },
+    /// Manage the public keys that can unseal your Chamber instance. Note that your root key is required for this.
+    UnsealKeys {
+        #[command(subcommand)]
+        cmd: UnsealKeyCommands,
     },
     Upload(UploadArgs),
-    Ssh,
+    /// Manage the SSH keys that you can log in with.
+    Ssh {
+        #[command(subcommand)]
+        cmd: SshCommands,
+    },
 }
 
 #[derive(Parser, Clone)]
@@ -45,6 +146,24 @@ pub struct LoginArgs {
     pub password: Option<String>,
 }
 
+#[derive(Parser, Clone)]
+pub struct CreateUserArgs {
+    #[command(flatten)]
+    pub credentials: LoginArgs,
+    /// Let the user manage the users, roles, groups and policies of their namespace.
+    #[arg(long)]
+    pub namespace_admin: bool,
+}
+
+#[derive(Parser, Clone)]
+pub struct InitArgs {
+    #[arg(long, short = 'u', default_value = "root")]
+    pub username: String,
+    /// Generate a password for the admin instead of prompting for one.
+    #[arg(long, short = 'g')]
+    pub generate_password: bool,
+}
+
 #[derive(Parser, Clone)]
 pub struct UserArgs {
     #[arg(long, short = 'u')]
@@ -52,8 +171,30 @@ pub struct UserArgs {
 }
 #[derive(Parser, Clone)]
 pub struct ListArgs {
+    /// A comma-separated list of tags
     #[arg(long, short = 't')]
     pub tags: Option<String>,
+    /// List secrets with any of the tags, rather than all of them
+    #[arg(long)]
+    pub any: bool,
+    /// A key prefix, like payments/, or a glob, like 'payments/*/stripe'
+    #[arg(long, short = 'k')]
+    pub key: Option<String>,
+    /// Either key or created
+    #[arg(long, short = 's', default_value = "key")]
+    pub sort: SecretSort,
+    /// Sort in descending order
+    #[arg(long)]
+    pub desc: bool,
+    /// How many secrets to list at once
+    #[arg(long, short = 'l')]
+    pub limit: Option<i64>,
+    /// Carry on from a previous page
+    #[arg(long, short = 'c')]
+    pub cursor: Option<String>,
+    /// Print the secrets as a tree of their paths
+    #[arg(long)]
+    pub tree: bool,
 }
 
 #[derive(Parser, Clone)]
@@ -78,16 +219,94 @@ pub struct UpdateUserArgs {
     pub access_level: Option<i32>,
     #[arg(short, long, value_parser, num_args = 1.., value_delimiter = ' ')]
     pub roles: Option<Vec<String>>,
+    /// Whether the user can manage their namespace
+    #[arg(long)]
+    pub namespace_admin: Option<bool>,
 }
 
 #[derive(Subcommand)]
 pub enum UserCommands {
     /// Create a new user
-    Create(LoginArgs),
+    Create(CreateUserArgs),
     /// Create a new user
     Update(UpdateUserArgs),
     /// Create a new user
     Delete(UserArgs),
+    /// List all users
+    List,
+    /// Disable a user so that they can no longer log in
+    Disable { username: String },
+    /// Re-enable a disabled user
+    Enable { username: String },
+    /// Set or clear the date that a user expires at
+    Expire(ExpireUserArgs),
+    /// Remove a user's two-factor authentication, for when they've lost their device and codes
+    #[command(name = "reset-2fa")]
+    ResetTwoFactor { username: String },
+    /// Require two-factor authentication for users above an access level
+    #[command(name = "require-2fa")]
+    RequireTwoFactor {
+        /// Leave this out to make two-factor authentication optional for everyone.
+        #[arg(long, short = 'a')]
+        above: Option<i32>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SshCommands {
+    /// Register an ed25519 public key, like ~/.ssh/id_ed25519.pub
+    Add { path: PathBuf },
+    /// List your registered keys
+    List,
+    /// Remove a key by its fingerprint
+    Remove { fingerprint: String },
+}
+
+/// The unseal key that approves a change to the unseal keys.
+#[derive(Parser, Clone)]
+pub struct ApprovalArgs {
+    /// Sign the approval with this registered ed25519 unseal key.
+    #[arg(long, short = 'k')]
+    pub ssh_key: Option<PathBuf>,
+    /// Sign the approval with your ssh-agent instead.
+    #[arg(long, short = 'a')]
+    pub ssh_agent: bool,
+}
+
+#[derive(Subcommand)]
+pub enum UnsealKeyCommands {
+    /// Register an operator's ed25519 public key. The root key can't unseal once one is registered,
+    /// and adding more keys needs the approval of one that is.
+    Add {
+        name: String,
+        path: PathBuf,
+        #[command(flatten)]
+        approval: ApprovalArgs,
+    },
+    /// List the registered unseal keys
+    List,
+    /// Remove an unseal key by its fingerprint, with the approval of a registered one
+    Remove {
+        fingerprint: String,
+        #[command(flatten)]
+        approval: ApprovalArgs,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TwoFactorCommands {
+    /// Set up an authenticator app and get your recovery codes
+    Enroll,
+    /// Turn off two-factor authentication
+    Disable,
+}
+
+#[derive(Parser, Clone)]
+pub struct ExpireUserArgs {
+    pub username: String,
+    /// An RFC 3339 timestamp, like 2024-12-31T23:59:59Z. Leave this out to remove the expiry.
+    #[arg(long, short = 'a')]
+    pub at: Option<DateTime<Utc>>,
 }
 
 #[derive(Subcommand)]
@@ -104,10 +323,23 @@ pub enum SecretsCommands {
     },
     /// List the names of all secrets currently stored (that you have access to)
     List(ListArgs),
+    /// List the folders and secrets directly inside a folder
+    Ls { prefix: Option<String> },
+    /// Move or rename a secret, or a folder along with everything in it. Only the secrets in the
+    /// --env environment are moved, and the same keys in other environments are left where they are
+    Mv { from: String, to: String },
     /// List decrypted secrets of all secrets by tag
     ListByTag(ListByTagArgs),
     /// Delete a secret
     Rm(KeyArgs),
+    /// Copy a secret's value from one environment to another, such as from staging to prod
+    Promote {
+        key: String,
+        #[arg(long)]
+        from: String,
+        #[arg(long)]
+        to: String,
+    },
 }
 
 #[derive(Parser, Clone)]
@@ -115,6 +347,146 @@ pub struct ListByTagArgs {
     pub key: String
 }
 
+#[derive(Subcommand)]
+pub enum PolicyCommands {
+    /// Create a policy from a JSON file
+    Create(PolicyFileArgs),
+    /// Replace an existing policy with the contents of a JSON file
+    Update(PolicyFileArgs),
+    /// List all policies
+    List,
+    /// View a policy
+    Get { name: String },
+    /// Delete a policy
+    Delete { name: String },
+    /// Attach a policy to users and/or roles
+    Attach(AttachPolicyArgs),
+    /// Detach a policy from users and/or roles
+    Detach(AttachPolicyArgs),
+}
+
+#[derive(Parser, Clone)]
+pub struct PolicyFileArgs {
+    /// Path to a JSON file containing the policy.
+    pub file: PathBuf,
+}
+
+#[derive(Parser, Clone)]
+pub struct AttachPolicyArgs {
+    pub name: String,
+    #[arg(short, long, value_parser, num_args = 1.., value_delimiter = ' ')]
+    pub users: Option<Vec<String>>,
+    #[arg(short, long, value_parser, num_args = 1.., value_delimiter = ' ')]
+    pub roles: Option<Vec<String>>,
+}
+
+#[derive(Subcommand)]
+pub enum RoleCommands {
+    /// Create a new role
+    Create(RoleArgs),
+    /// Update the description of a role
+    Update(RoleArgs),
+    /// List all roles along with the users and secrets that reference them
+    List,
+    /// View a role along with the users and secrets that reference it
+    Get { name: String },
+    /// Delete a role that is no longer referenced by any users or secrets
+    Delete { name: String },
+}
+
+#[derive(Parser, Clone)]
+pub struct RoleArgs {
+    pub name: String,
+    #[arg(long, short = 'd')]
+    pub description: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum GroupCommands {
+    /// Create a new group
+    Create(CreateGroupArgs),
+    /// Update the description, roles or access level of a group
+    Update(UpdateGroupArgs),
+    /// List all groups
+    List,
+    /// View a group
+    Get { name: String },
+    /// Delete a group
+    Delete { name: String },
+    /// Add users and/or nested groups to a group
+    AddMembers(GroupMembersArgs),
+    /// Remove users and/or nested groups from a group
+    RemoveMembers(GroupMembersArgs),
+}
+
+#[derive(Parser, Clone)]
+pub struct CreateGroupArgs {
+    pub name: String,
+    #[arg(long, short = 'd')]
+    pub description: Option<String>,
+    #[arg(long, short = 'a')]
+    pub access_level: Option<i32>,
+    #[arg(short, long, value_parser, num_args = 1.., value_delimiter = ' ')]
+    pub roles: Option<Vec<String>>,
+    #[arg(short, long, value_parser, num_args = 1.., value_delimiter = ' ')]
+    pub members: Option<Vec<String>>,
+    #[arg(short, long, value_parser, num_args = 1.., value_delimiter = ' ')]
+    pub subgroups: Option<Vec<String>>,
+}
+
+#[derive(Parser, Clone)]
+pub struct UpdateGroupArgs {
+    pub name: String,
+    #[arg(long, short = 'd')]
+    pub description: Option<String>,
+    #[arg(long, short = 'a')]
+    pub access_level: Option<i32>,
+    #[arg(short, long, value_parser, num_args = 1.., value_delimiter = ' ')]
+    pub roles: Option<Vec<String>>,
+}
+
+#[derive(Parser, Clone)]
+pub struct GroupMembersArgs {
+    pub name: String,
+    #[arg(short, long, value_parser, num_args = 1.., value_delimiter = ' ')]
+    pub users: Option<Vec<String>>,
+    #[arg(short, long, value_parser, num_args = 1.., value_delimiter = ' ')]
+    pub groups: Option<Vec<String>>,
+}
+
+#[derive(Subcommand)]
+pub enum NamespaceCommands {
+    /// Create a new namespace
+    Create {
+        name: String,
+        #[arg(long, short = 'd')]
+        description: Option<String>,
+    },
+    /// List all namespaces
+    List,
+    /// Delete a namespace along with all of its secrets, users, roles, groups and policies
+    Delete { name: String },
+}
+
+#[derive(Subcommand)]
+pub enum AccessCommands {
+    /// List every user and role that can read a secret, and why
+    Secret { key: String },
+    /// List every secret that a user can reach
+    User { username: String },
+    /// Check whether a user would be allowed a capability on a secret
+    Simulate(SimulateArgs),
+}
+
+#[derive(Parser, Clone)]
+pub struct SimulateArgs {
+    pub username: String,
+    pub key: String,
+    /// One of read, list, create, update or delete
+    #[arg(long, short = 'c', default_value = "read")]
+    pub capability: Capability,
+}
+
 #[derive(Subcommand)]
 pub enum WebsiteCommands {
     /// Get the current URL
@@ -1,18 +1,105 @@
 use chamber_shared::AuthBody;
 use comfy_table::Table;
-use inquire::Text;
+use inquire::{Password, Text};
+use reqwest::header::{HeaderMap, HeaderValue};
 use reqwest::StatusCode;
+use std::collections::BTreeMap;
+use std::path::Path;
 
-use crate::errors::CliError;
+use crate::errors::{describe_error, CliError};
 
-use crate::args::{Cli, Commands, SecretsCommands, UserCommands, WebsiteCommands};
+use crate::args::{
+    AccessCommands, Cli, Commands, GroupCommands, NamespaceCommands, PolicyCommands, RoleCommands,
+    SecretsCommands, SshCommands, TwoFactorCommands, UnsealKeyCommands, UserCommands,
+    WebsiteCommands,
+};
 
 
 use crate::config::AppConfig;
-use chamber_shared::SecretPublic;
-use chamber_crypto::secrets::{KeyFile, SecretInfo};
+use crate::ssh;
+use chamber_shared::attempts::{FailedAttempt, FailedAttemptsParams};
+use chamber_shared::audit::{AuditEntry, AuditLogQuery};
+use chamber_shared::environments::{
+    check_environment_name, PromoteSecretParams, ENVIRONMENT_HEADER,
+};
+use chamber_shared::errors::{ErrorBody, ErrorCode};
+use chamber_shared::groups::{Group, GroupMembersParams, GroupParams, UpdateGroupParams};
+use chamber_shared::namespaces::{check_namespace_name, Namespace, NAMESPACE_HEADER};
+use chamber_shared::policy::{
+    AccessGrant, AttachPolicyParams, Decision, Policy, PolicyParams, ReachableSecret, SecretAccess,
+    SecretAccessParams, SimulateParams, UserAccess, UserAccessParams,
+};
+use chamber_shared::roles::{Role, RoleParams, RoleUsage};
+use chamber_shared::secrets::{
+    CreateSecretParams, FolderListing, FolderQuery, ListSecretsQuery, MoveSecretsParams,
+    MovedSecret, SecretInfo, SecretKey, SecretPublic, SortOrder, TagMatch, UpdateSecret,
+    NEXT_CURSOR_HEADER,
+};
+use chamber_shared::ssh::{
+    AddSshKeyParams, AddUnsealKeyParams, RemoveSshKeyParams, RemoveUnsealKeyParams, SshChallenge,
+    SshChallengeParams, SshKey, SshLoginParams, UnsealKey, UnsealParams,
+};
+use chamber_shared::users::{
+    CertLoginParams, ChangePasswordParams, CreateUserParams, DisableTwoFactorParams, InitParams,
+    InitResponse, RecoveryCodes, TwoFactorCodeParams, TwoFactorEnrollment, TwoFactorPasswordParams,
+    TwoFactorSettings, UpdateUserParams, UserExpiryParams, UserLoginParams, UserParams,
+    UserProfile, UserSummary,
+};
+use chamber_crypto::secrets::KeyFile;
+
+/// Asks for an unseal challenge and signs it with an unseal key.
+fn sign_unseal_challenge(
+    ctx: &reqwest::blocking::Client,
+    website: &str,
+    ssh_key: Option<&Path>,
+    ssh_agent: bool,
+) -> Result<UnsealParams, CliError> {
+    let challenge = ctx
+        .post(format!("{website}/unseal/challenge"))
+        .send()?
+        .error_for_status()?
+        .json::<SshChallenge>()?;
+
+    let signature = ssh::sign_challenge(ssh_key, ssh_agent, &challenge)?;
+
+    Ok(UnsealParams {
+        nonce: challenge.nonce,
+        signature,
+    })
+}
+
+/// A client that sends `--namespace` and `--env`, if they were given, with every request.
+fn client(
+    namespace: Option<&str>,
+    environment: Option<&str>,
+) -> Result<reqwest::blocking::Client, CliError> {
+    let mut headers = HeaderMap::new();
+
+    if let Some(namespace) = namespace {
+        check_namespace_name(namespace).map_err(CliError::InvalidNamespace)?;
+        headers.insert(
+            NAMESPACE_HEADER,
+            HeaderValue::from_str(namespace).expect("Namespace names are valid header values"),
+        );
+    }
+
+    if let Some(environment) = environment {
+        check_environment_name(environment).map_err(CliError::InvalidEnvironment)?;
+        headers.insert(
+            ENVIRONMENT_HEADER,
+            HeaderValue::from_str(environment).expect("Environment names are valid header values"),
+        );
+    }
+
+    Ok(reqwest::blocking::Client::builder()
+        .default_headers(headers)
+        .build()?)
+}
 
 pub fn parse_cli(cli: Cli, cfg: AppConfig) -> Result<(), CliError> {
+    let namespace = cli.namespace;
+    let environment = cli.environment;
+
     match cli.command {
         Commands::Secrets { cmd } => match cmd {
             SecretsCommands::Get(args) => {
@@ -30,18 +117,19 @@ pub fn parse_cli(cli: Cli, cfg: AppConfig) -> Result<(), CliError> {
                     None => Text::new("Please enter the key you want to retrieve:").prompt()?,
                 };
 
-                let ctx = reqwest::blocking::Client::new();
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
 
                 let res = ctx
                     .post(website)
                     .header("Content-Type", "application/json")
                     .header("Authorization", jwt)
-                    .json(&serde_json::json!({"key":key}))
+                    .json(&SecretKey { key })
                     .send()?;
 
-                let body = res.text()?;
-
-                println!("{body}");
+                match res.status() {
+                    StatusCode::OK => println!("{}", res.text()?),
+                    _ => println!("Error: {}", describe_error(res)?),
+                }
             }
 
             SecretsCommands::Set { key, value } => {
@@ -54,13 +142,19 @@ pub fn parse_cli(cli: Cli, cfg: AppConfig) -> Result<(), CliError> {
                     None => panic!("You didn't set a URL for a Chamber instance to log into!"),
                 };
 
-                let ctx = reqwest::blocking::Client::new();
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
 
                 let res = ctx
                     .post(website)
                     .header("Content-Type", "application/json")
                     .header("Authorization", jwt)
-                    .json(&serde_json::json!({"key":key,"value":value}))
+                    .json(&CreateSecretParams {
+                        key,
+                        value,
+                        tags: None,
+                        access_level: None,
+                        role_whitelist: None,
+                    })
                     .send()?;
 
                 match res.status() {
@@ -80,15 +174,15 @@ pub fn parse_cli(cli: Cli, cfg: AppConfig) -> Result<(), CliError> {
                     None => panic!("You didn't set a URL for a Chamber instance to log into!"),
                 };
 
-                let ctx = reqwest::blocking::Client::new();
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
 
                 let res = ctx
                     .put(website)
                     .header("Authorization", jwt)
-                    .json(&serde_json::json!({
-                        "key": key,
-                        "update_data": tags
-                    }))
+                    .json(&UpdateSecret {
+                        key,
+                        update_data: tags,
+                    })
                     .send()?;
 
                 match res.status() {
@@ -106,14 +200,12 @@ pub fn parse_cli(cli: Cli, cfg: AppConfig) -> Result<(), CliError> {
                     None => panic!("You didn't set a URL for a Chamber instance to log into!"),
                 };
 
-                let ctx = reqwest::blocking::Client::new();
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
 
                 let res = ctx
                     .post(website)
                     .header("Authorization", jwt)
-                    .json(&serde_json::json!({
-                        "key": args.key
-                    }))
+                    .json(&SecretKey { key: args.key })
                     .send()?;
 
                 let json = res.json::<Vec<SecretPublic>>().unwrap();
@@ -128,25 +220,116 @@ pub fn parse_cli(cli: Cli, cfg: AppConfig) -> Result<(), CliError> {
                 };
 
                 let website = match cfg.website() {
-                    Some(res) => format!("{res}/secrets"),
+                    Some(res) => format!("{res}/v1/secrets"),
+                    None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+                };
+
+                let tag_match = match args.any {
+                    true => TagMatch::Any,
+                    false => TagMatch::All,
+                };
+
+                let order = match args.desc {
+                    true => SortOrder::Desc,
+                    false => SortOrder::Asc,
+                };
+
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+                let res = ctx
+                    .get(website)
+                    .header("Authorization", jwt)
+                    .query(&ListSecretsQuery {
+                        tags: args.tags,
+                        tag_match: Some(tag_match),
+                        key: args.key,
+                        sort: Some(args.sort),
+                        order: Some(order),
+                        cursor: args.cursor,
+                        limit: args.limit,
+                        ..Default::default()
+                    })
+                    .send()?;
+
+                if res.status() != StatusCode::OK {
+                    println!("Error: {}", describe_error(res)?);
+                    return Ok(());
+                }
+
+                let next_cursor = res
+                    .headers()
+                    .get(NEXT_CURSOR_HEADER)
+                    .and_then(|x| x.to_str().ok())
+                    .map(String::from);
+
+                let json = res.json::<Vec<SecretInfo>>()?;
+
+                match args.tree {
+                    true => println!("{}", secrets_tree(json)),
+                    false => println!("{}", secrets_table(json)),
+                }
+
+                if let Some(cursor) = next_cursor {
+                    println!("To see the next page, use --cursor {cursor}");
+                }
+            }
+            SecretsCommands::Ls { prefix } => {
+                let Some(jwt) = cfg.clone().jwt_key() else {
+                    panic!("You need to log in before you can do that!");
+                };
+
+                let website = match cfg.website() {
+                    Some(res) => format!("{res}/v1/folders"),
+                    None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+                };
+
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+                let res = ctx
+                    .get(website)
+                    .header("Authorization", jwt)
+                    .query(&FolderQuery { prefix })
+                    .send()?;
+
+                match res.status() {
+                    StatusCode::OK => {
+                        let json = res.json::<FolderListing>()?;
+
+                        println!("{}", folder_table(json));
+                    }
+                    _ => println!("Error: {}", describe_error(res)?),
+                }
+            }
+            SecretsCommands::Mv { from, to } => {
+                let Some(jwt) = cfg.clone().jwt_key() else {
+                    panic!("You need to log in before you can do that!");
+                };
+
+                let website = match cfg.website() {
+                    Some(res) => format!("{res}/v1/folders/move"),
                     None => panic!("You didn't set a URL for a Chamber instance to log into!"),
                 };
 
-                let ctx = reqwest::blocking::Client::new();
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
 
                 let res = ctx
                     .post(website)
                     .header("Authorization", jwt)
-                    .json(&serde_json::json!({
-                        "tag_filter": args.tags
-                    }))
+                    .json(&MoveSecretsParams { from, to })
                     .send()?;
 
-                let json = res.json::<Vec<SecretInfo>>().unwrap();
+                match res.status() {
+                    StatusCode::OK => {
+                        let json = res.json::<Vec<MovedSecret>>()?;
 
-                let table = secrets_table(json);
+                        for moved in &json {
+                            println!("{} -> {}", moved.from, moved.to);
+                        }
 
-                println!("{table}");
+                        println!("Moved {} secrets.", json.len());
+                    }
+                    _ => println!("Error: {}", describe_error(res)?),
+                }
             }
             SecretsCommands::Rm(args) => {
                 let Some(jwt) = cfg.clone().jwt_key() else {
@@ -163,17 +346,44 @@ pub fn parse_cli(cli: Cli, cfg: AppConfig) -> Result<(), CliError> {
                     None => Text::new("Please enter the key you want to retrieve:").prompt()?,
                 };
 
-                let ctx = reqwest::blocking::Client::new();
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
 
                 let res = ctx
                     .delete(website)
                     .header("Authorization", jwt)
-                    .json(&serde_json::json!({"key":key}))
+                    .json(&SecretKey { key })
                     .send()?;
 
                 match res.status() {
                     StatusCode::OK => println!("Key successfully deleted."),
-                    _ => println!("Error while deleting key: {}", res.text().unwrap()),
+                    _ => println!("Error while deleting key: {}", describe_error(res)?),
+                }
+            }
+            SecretsCommands::Promote { key, from, to } => {
+                let Some(jwt) = cfg.clone().jwt_key() else {
+                    panic!("You need to log in before you can do that!");
+                };
+
+                let website = match cfg.website() {
+                    Some(res) => format!("{res}/v1/environments/promote"),
+                    None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+                };
+
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+                let res = ctx
+                    .post(website)
+                    .header("Authorization", jwt)
+                    .json(&PromoteSecretParams {
+                        key: key.clone(),
+                        from: from.clone(),
+                        to: to.clone(),
+                    })
+                    .send()?;
+
+                match res.status() {
+                    StatusCode::OK => println!("Promoted {key} from {from} to {to}."),
+                    _ => println!("Error while promoting key: {}", describe_error(res)?),
                 }
             }
         },
@@ -203,21 +413,27 @@ pub fn parse_cli(cli: Cli, cfg: AppConfig) -> Result<(), CliError> {
 
                 let key = Text::new("Please enter your root key:").prompt()?;
 
-                let username = match args.username {
+                let username = match args.credentials.username {
                     Some(res) => res,
                     None => Text::new("Please enter your desired username:").prompt()?,
                 };
-                let password = match args.password {
+                let password = match args.credentials.password {
                     Some(res) => res,
                     None => Text::new("Please enter your desired password:").prompt()?,
                 };
-                let ctx = reqwest::blocking::Client::new();
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
 
                 let res = ctx
                     .post(website)
                     .header("Content-Type", "application/json")
                     .header("x-chamber-key", key)
-                    .json(&serde_json::json!({"username": username, "password": password}))
+                    .json(&CreateUserParams {
+                        username,
+                        password,
+                        access_level: None,
+                        roles: None,
+                        namespace_admin: Some(args.namespace_admin),
+                    })
                     .send()?;
 
                 match res.status() {
@@ -227,33 +443,37 @@ pub fn parse_cli(cli: Cli, cfg: AppConfig) -> Result<(), CliError> {
                         );
                     }
                     _ => {
-                        println!("Error: {}", res.text()?)
+                        println!("Error: {}", describe_error(res)?)
                     }
                 }
             }
             UserCommands::Update(args) => {
                 let website = match cfg.website() {
-                    Some(res) => format!("{res}/users/create"),
+                    Some(res) => format!("{res}/users/update"),
                     None => panic!("You didn't set a URL for a Chamber instance to log into!"),
                 };
 
-                if args.access_level.is_none() & args.roles.is_none() {
+                if args.access_level.is_none()
+                    & args.roles.is_none()
+                    & args.namespace_admin.is_none()
+                {
                     return Err(CliError::AtLeastOneArgError);
                 }
 
                 let key = Text::new("Please enter your root key:").prompt()?;
 
-                let ctx = reqwest::blocking::Client::new();
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
 
                 let res = ctx
-                    .post(website)
+                    .put(website)
                     .header("Content-Type", "application/json")
                     .header("x-chamber-key", key)
-                    .json(&serde_json::json!({
-                    "username": args.username,
-                    "access_level": args.access_level,
-                    "roles": args.roles
-                    }))
+                    .json(&UpdateUserParams {
+                        username: args.username,
+                        access_level: args.access_level,
+                        roles: args.roles,
+                        namespace_admin: args.namespace_admin,
+                    })
                     .send()?;
 
                 match res.status() {
@@ -261,7 +481,7 @@ pub fn parse_cli(cli: Cli, cfg: AppConfig) -> Result<(), CliError> {
                         println!("User has been updated.");
                     }
                     _ => {
-                        println!("Error: {}", res.text()?)
+                        println!("Error: {}", describe_error(res)?)
                     }
                 }
             }
@@ -279,15 +499,13 @@ pub fn parse_cli(cli: Cli, cfg: AppConfig) -> Result<(), CliError> {
                     None => Text::new("Name of the user to be deleted:").prompt()?,
                 };
 
-                let ctx = reqwest::blocking::Client::new();
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
 
                 let res = ctx
-                    .post(website)
+                    .delete(website)
                     .header("Content-Type", "application/json")
                     .header("x-chamber-key", key)
-                    .json(&serde_json::json!({
-                        "username": username
-                    }))
+                    .json(&UserParams { name: username })
                     .send()?;
 
                 match res.status() {
@@ -295,185 +513,1577 @@ pub fn parse_cli(cli: Cli, cfg: AppConfig) -> Result<(), CliError> {
                         println!("User has been deleted.");
                     }
                     _ => {
-                        println!("Error: {}", res.text()?)
+                        println!("Error: {}", describe_error(res)?)
                     }
                 }
             }
-        },
-        Commands::Website { cmd } => match cmd {
-            WebsiteCommands::Get => match cfg.website() {
-                Some(res) => println!("{res}"),
-                None => println!("No website has been set!"),
-            },
-            WebsiteCommands::Set(args) => {
-                let value = match args.value {
-                    Some(res) => res,
-                    None => Text::new("Enter the website URL:").prompt()?,
-                };
-                cfg.set_website(&value)?;
-            }
-        },
 
-        Commands::Login(args) => {
-            let username = match args.username {
-                Some(res) => res,
-                None => Text::new("Please enter your username:").prompt()?,
-            };
-            let password = match args.password {
-                Some(res) => res,
-                None => Text::new("Please enter your password:").prompt()?,
-            };
+            UserCommands::List => {
+                let website = match cfg.website() {
+                    Some(res) => format!("{res}/users/list"),
+                    None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+                };
 
-            let ctx = reqwest::blocking::Client::new();
+                let key = Text::new("Please enter your root key:").prompt()?;
 
-            let website = match cfg.to_owned().website() {
-                Some(res) => format!("{res}/login"),
-                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
-            };
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
 
-            let res = ctx
-                .post(website)
-                .header("Content-Type", "application/json")
-                .json(&serde_json::json!({
-                    "username": username,
-                    "password": password
-                }))
-                .send()?;
-            match res.status() {
-                StatusCode::OK => {
-                    let res = res.json::<AuthBody>()?;
+                let res = ctx.post(website).header("x-chamber-key", key).send()?;
 
-                    let token = format!("{} {}", res.token_type, res.access_token);
-                    cfg.set_token(&token)?;
+                match res.status() {
+                    StatusCode::OK => {
+                        let json = res.json::<Vec<UserSummary>>()?;
 
-                    println!("You've logged in successfully!");
-                }
-                _ => {
-                    println!("Something went wrong: {}", res.text()?);
+                        println!("{}", users_table(json));
+                    }
+                    _ => {
+                        println!("Error: {}", describe_error(res)?)
+                    }
                 }
             }
-        }
 
-        Commands::Unseal { chamber_key } => {
-            let ctx = reqwest::blocking::Client::new();
+            UserCommands::Disable { username } => {
+                let website = match cfg.website() {
+                    Some(res) => format!("{res}/users/disable"),
+                    None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+                };
 
-            let website = match cfg.to_owned().website() {
-                Some(res) => format!("{res}/unseal"),
-                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
-            };
+                let key = Text::new("Please enter your root key:").prompt()?;
 
-            let res = ctx
-                .post(website)
-                .header("Content-Type", "application/json")
-                .header("x-chamber-key", chamber_key)
-                .send()?;
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
 
-            match res.status() {
-                StatusCode::OK => println!("The instance has been unsealed and is ready to use!"),
-                _ => {
-                    println!("{}", res.text()?);
+                let res = ctx
+                    .post(website)
+                    .header("x-chamber-key", key)
+                    .json(&UserParams { name: username })
+                    .send()?;
+
+                match res.status() {
+                    StatusCode::OK => {
+                        println!("User has been disabled.");
+                    }
+                    _ => {
+                        println!("Error: {}", describe_error(res)?)
+                    }
                 }
             }
-        }
-        Commands::Upload(args) => {
-            let key = match args.key {
-                Some(res) => res,
-                None => Text::new("Please enter your root key:").prompt()?,
-            };
-            let ctx = reqwest::blocking::Client::new();
 
-            let website = match cfg.to_owned().website() {
-                Some(res) => format!("{res}/binfile"),
-                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
-            };
-
-            let file = std::fs::read("chamber.bin")?;
+            UserCommands::Enable { username } => {
+                let website = match cfg.website() {
+                    Some(res) => format!("{res}/users/enable"),
+                    None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+                };
 
-            let form = reqwest::blocking::multipart::Form::new();
-            let file_as_bytes = reqwest::blocking::multipart::Part::bytes(file);
+                let key = Text::new("Please enter your root key:").prompt()?;
 
-            let form = form.part("file", file_as_bytes);
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
 
-            let res = ctx
-                .post(website)
-                .header("x-chamber-key", key)
-                .multipart(form)
-                .send()?;
+                let res = ctx
+                    .post(website)
+                    .header("x-chamber-key", key)
+                    .json(&UserParams { name: username })
+                    .send()?;
 
-            match res.status() {
-                StatusCode::OK => {
-                    println!("The new crypto key and root key have been uploaded! All of your secrets have been re-keyed.");
+                match res.status() {
+                    StatusCode::OK => {
+                        println!("User has been enabled.");
+                    }
+                    _ => {
+                        println!("Error: {}", describe_error(res)?)
+                    }
                 }
-                _ => {
-                    println!("{}", res.text()?);
+            }
+
+            UserCommands::Expire(args) => {
+                let website = match cfg.website() {
+                    Some(res) => format!("{res}/users/expiry"),
+                    None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+                };
+
+                let key = Text::new("Please enter your root key:").prompt()?;
+
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+                let res = ctx
+                    .put(website)
+                    .header("x-chamber-key", key)
+                    .json(&UserExpiryParams {
+                        name: args.username,
+                        expires_at: args.at,
+                    })
+                    .send()?;
+
+                match res.status() {
+                    StatusCode::OK => match args.at {
+                        Some(at) => println!("User will expire at {at}."),
+                        None => println!("User no longer expires."),
+                    },
+                    _ => {
+                        println!("Error: {}", describe_error(res)?)
+                    }
                 }
             }
-        }
-        Commands::Ssh => {
-//            let string =
-//                std::fs::read_to_string("/home/joshuamo/.config/chamber/chamber.key").unwrap();
-//
-//            let mut key_params = composed::key::SecretKeyParamsBuilder::default();
-//
-//            let password = "Password".to_string();
-//
-//            key_params
-//                // change to 4096 later
-//                .key_type(composed::KeyType::Rsa(2048))
-//                .can_sign(true)
-//                .can_encrypt(true)
-//                .passphrase(Some(password.clone()))
-//                .primary_user_id("Me <joshua.mo.876@gmail.com>".into())
-//                .preferred_symmetric_algorithms(smallvec![
-//                    crypto::sym::SymmetricKeyAlgorithm::AES256
-//                ]);
-//
-//            let secret_key_params = key_params
-//                .build()
-//                .expect("Must be able to create secret key params");
-//
-//            let secret_key = secret_key_params
-//                .generate()
-//                .expect("Failed to generate a plain key.");
-//
-//            let passwd_fn = || password.clone();
-//
-//            let signed_secret_key = secret_key
-//                .sign(passwd_fn)
-//                .expect("Secret Key must be able to sign its own metadata");
-//
-//            let public_key = signed_secret_key.public_key();
-//            let signed_public_key = public_key
-//                .sign(&signed_secret_key, passwd_fn)
-//                .expect("Public key must be able to sign its own metadata");
-//
-//            let key_pair = KeyPair {
-//                secret_key: signed_secret_key,
-//                public_key: signed_public_key,
-//            };
-        }
-    }
 
-    Ok(())
-}
+            UserCommands::ResetTwoFactor { username } => {
+                let website = match cfg.website() {
+                    Some(res) => format!("{res}/users/reset_2fa"),
+                    None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+                };
 
-pub fn secrets_table(secrets: Vec<SecretInfo>) -> Table {
-    let mut table = Table::new();
-    table.set_header(vec!["Secret Key", "Tags"]);
+                let key = Text::new("Please enter your root key:").prompt()?;
 
-    secrets.into_iter().for_each(|x| {
-        table.add_row(vec![x.key, x.tags.join(", ")]);
-    });
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
 
-    table
-}
+                let res = ctx
+                    .post(website)
+                    .header("x-chamber-key", key)
+                    .json(&UserParams { name: username })
+                    .send()?;
 
-pub fn secrets_table_decrypted(secrets: Vec<SecretPublic>) -> Table {
-    let mut table = Table::new();
-    table.set_header(vec!["Key", "Value"]);
+                match res.status() {
+                    StatusCode::OK => {
+                        println!("Two-factor authentication has been removed from the user.");
+                    }
+                    _ => {
+                        println!("Error: {}", describe_error(res)?)
+                    }
+                }
+            }
 
-    secrets.into_iter().for_each(|x| {
-        table.add_row(vec![x.key, x.value]);
+            UserCommands::RequireTwoFactor { above } => {
+                let website = match cfg.website() {
+                    Some(res) => format!("{res}/settings/two_factor"),
+                    None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+                };
+
+                let key = Text::new("Please enter your root key:").prompt()?;
+
+                let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+                let res = ctx
+                    .put(website)
+                    .header("x-chamber-key", key)
+                    .json(&TwoFactorSettings {
+                        required_above: above,
+                    })
+                    .send()?;
+
+                match res.status() {
+                    StatusCode::OK => match above {
+                        Some(level) => println!(
+                            "Users above access level {level} now need two-factor authentication."
+                        ),
+                        None => println!("Two-factor authentication is now optional."),
+                    },
+                    _ => {
+                        println!("Error: {}", describe_error(res)?)
+                    }
+                }
+            }
+        },
+        Commands::Policies { cmd } => {
+            let website = match cfg.website() {
+                Some(res) => format!("{res}/policies"),
+                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+            };
+
+            let key = Text::new("Please enter your root key:").prompt()?;
+
+            let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+            match cmd {
+                PolicyCommands::Create(args) => {
+                    let file = std::fs::read_to_string(args.file)?;
+                    let policy: Policy = serde_json::from_str(&file)?;
+
+                    let res = ctx
+                        .post(format!("{website}/create"))
+                        .header("x-chamber-key", key)
+                        .json(&policy)
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::CREATED => println!("Policy {} has been created.", policy.name),
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                PolicyCommands::Update(args) => {
+                    let file = std::fs::read_to_string(args.file)?;
+                    let policy: Policy = serde_json::from_str(&file)?;
+
+                    let res = ctx
+                        .put(format!("{website}/update"))
+                        .header("x-chamber-key", key)
+                        .json(&policy)
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => println!("Policy {} has been updated.", policy.name),
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                PolicyCommands::List => {
+                    let res = ctx
+                        .post(format!("{website}/list"))
+                        .header("x-chamber-key", key)
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => {
+                            let json = res.json::<Vec<Policy>>()?;
+
+                            println!("{}", policies_table(json));
+                        }
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                PolicyCommands::Get { name } => {
+                    let res = ctx
+                        .post(format!("{website}/get"))
+                        .header("x-chamber-key", key)
+                        .json(&PolicyParams { name })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => {
+                            let json = res.json::<Policy>()?;
+
+                            println!("{}", serde_json::to_string_pretty(&json)?);
+                        }
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                PolicyCommands::Delete { name } => {
+                    let res = ctx
+                        .delete(format!("{website}/delete"))
+                        .header("x-chamber-key", key)
+                        .json(&PolicyParams { name })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => println!("Policy has been deleted."),
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                PolicyCommands::Attach(args) | PolicyCommands::Detach(args)
+                    if args.users.is_none() & args.roles.is_none() =>
+                {
+                    return Err(CliError::AtLeastOneArgError);
+                }
+                PolicyCommands::Attach(args) => {
+                    let res = ctx
+                        .post(format!("{website}/attach"))
+                        .header("x-chamber-key", key)
+                        .json(&AttachPolicyParams {
+                            name: args.name,
+                            users: args.users,
+                            roles: args.roles,
+                        })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => println!("Policy has been attached."),
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                PolicyCommands::Detach(args) => {
+                    let res = ctx
+                        .post(format!("{website}/detach"))
+                        .header("x-chamber-key", key)
+                        .json(&AttachPolicyParams {
+                            name: args.name,
+                            users: args.users,
+                            roles: args.roles,
+                        })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => println!("Policy has been detached."),
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+            }
+        }
+        Commands::Roles { cmd } => {
+            let website = match cfg.website() {
+                Some(res) => format!("{res}/roles"),
+                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+            };
+
+            let key = Text::new("Please enter your root key:").prompt()?;
+
+            let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+            match cmd {
+                RoleCommands::Create(args) => {
+                    let res = ctx
+                        .post(format!("{website}/create"))
+                        .header("x-chamber-key", key)
+                        .json(&Role {
+                            name: args.name.clone(),
+                            description: args.description.unwrap_or_default(),
+                        })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::CREATED => println!("Role {} has been created.", args.name),
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                RoleCommands::Update(args) => {
+                    let res = ctx
+                        .put(format!("{website}/update"))
+                        .header("x-chamber-key", key)
+                        .json(&Role {
+                            name: args.name.clone(),
+                            description: args.description.unwrap_or_default(),
+                        })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => println!("Role {} has been updated.", args.name),
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                RoleCommands::List => {
+                    let res = ctx
+                        .post(format!("{website}/list"))
+                        .header("x-chamber-key", key)
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => {
+                            let json = res.json::<Vec<RoleUsage>>()?;
+
+                            println!("{}", roles_table(json));
+                        }
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                RoleCommands::Get { name } => {
+                    let res = ctx
+                        .post(format!("{website}/get"))
+                        .header("x-chamber-key", key)
+                        .json(&RoleParams { name })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => {
+                            let json = res.json::<RoleUsage>()?;
+
+                            println!("{}", roles_table(vec![json]));
+                        }
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                RoleCommands::Delete { name } => {
+                    let res = ctx
+                        .delete(format!("{website}/delete"))
+                        .header("x-chamber-key", key)
+                        .json(&RoleParams { name })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => println!("Role has been deleted."),
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+            }
+        }
+        Commands::Groups { cmd } => {
+            let website = match cfg.website() {
+                Some(res) => format!("{res}/groups"),
+                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+            };
+
+            let key = Text::new("Please enter your root key:").prompt()?;
+
+            let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+            match cmd {
+                GroupCommands::Create(args) => {
+                    let group = Group {
+                        name: args.name,
+                        description: args.description.unwrap_or_default(),
+                        members: args.members.unwrap_or_default(),
+                        subgroups: args.subgroups.unwrap_or_default(),
+                        roles: args.roles.unwrap_or_default(),
+                        access_level: args.access_level.unwrap_or_default(),
+                    };
+
+                    let res = ctx
+                        .post(format!("{website}/create"))
+                        .header("x-chamber-key", key)
+                        .json(&group)
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::CREATED => println!("Group {} has been created.", group.name),
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                GroupCommands::Update(args) => {
+                    if args.description.is_none()
+                        & args.access_level.is_none()
+                        & args.roles.is_none()
+                    {
+                        return Err(CliError::AtLeastOneArgError);
+                    }
+
+                    let res = ctx
+                        .put(format!("{website}/update"))
+                        .header("x-chamber-key", key)
+                        .json(&UpdateGroupParams {
+                            name: args.name,
+                            description: args.description,
+                            roles: args.roles,
+                            access_level: args.access_level,
+                        })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => println!("Group has been updated."),
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                GroupCommands::List => {
+                    let res = ctx
+                        .post(format!("{website}/list"))
+                        .header("x-chamber-key", key)
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => {
+                            let json = res.json::<Vec<Group>>()?;
+
+                            println!("{}", groups_table(json));
+                        }
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                GroupCommands::Get { name } => {
+                    let res = ctx
+                        .post(format!("{website}/get"))
+                        .header("x-chamber-key", key)
+                        .json(&GroupParams { name })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => {
+                            let json = res.json::<Group>()?;
+
+                            println!("{}", groups_table(vec![json]));
+                        }
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                GroupCommands::Delete { name } => {
+                    let res = ctx
+                        .delete(format!("{website}/delete"))
+                        .header("x-chamber-key", key)
+                        .json(&GroupParams { name })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => println!("Group has been deleted."),
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                GroupCommands::AddMembers(args) | GroupCommands::RemoveMembers(args)
+                    if args.users.is_none() & args.groups.is_none() =>
+                {
+                    return Err(CliError::AtLeastOneArgError);
+                }
+                GroupCommands::AddMembers(args) => {
+                    let res = ctx
+                        .post(format!("{website}/add_members"))
+                        .header("x-chamber-key", key)
+                        .json(&GroupMembersParams {
+                            name: args.name,
+                            users: args.users,
+                            groups: args.groups,
+                        })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => println!("Members have been added."),
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                GroupCommands::RemoveMembers(args) => {
+                    let res = ctx
+                        .post(format!("{website}/remove_members"))
+                        .header("x-chamber-key", key)
+                        .json(&GroupMembersParams {
+                            name: args.name,
+                            users: args.users,
+                            groups: args.groups,
+                        })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => println!("Members have been removed."),
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+            }
+        }
+        Commands::Namespaces { cmd } => {
+            let website = match cfg.website() {
+                Some(res) => format!("{res}/v1/namespaces"),
+                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+            };
+
+            let key = Text::new("Please enter your root key:").prompt()?;
+
+            let ctx = reqwest::blocking::Client::new();
+
+            match cmd {
+                NamespaceCommands::Create { name, description } => {
+                    let res = ctx
+                        .post(website)
+                        .header("x-chamber-key", key)
+                        .json(&Namespace {
+                            name: name.clone(),
+                            description: description.unwrap_or_default(),
+                        })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::CREATED => println!("Namespace {name} has been created."),
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                NamespaceCommands::List => {
+                    let res = ctx.get(website).header("x-chamber-key", key).send()?;
+
+                    match res.status() {
+                        StatusCode::OK => {
+                            let json = res.json::<Vec<Namespace>>()?;
+
+                            println!("{}", namespaces_table(json));
+                        }
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                NamespaceCommands::Delete { name } => {
+                    let res = ctx
+                        .delete(format!("{website}/{name}"))
+                        .header("x-chamber-key", key)
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => println!("Namespace {name} has been deleted."),
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+            }
+        }
+        Commands::Access { cmd } => {
+            let website = match cfg.website() {
+                Some(res) => format!("{res}/access"),
+                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+            };
+
+            let key = Text::new("Please enter your root key:").prompt()?;
+
+            let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+            match cmd {
+                AccessCommands::Secret { key: secret_key } => {
+                    let res = ctx
+                        .post(format!("{website}/secret"))
+                        .header("x-chamber-key", key)
+                        .json(&SecretAccessParams { key: secret_key })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => {
+                            let json = res.json::<SecretAccess>()?;
+
+                            println!("{}", access_grants_table(json.users));
+                            println!("Roles that grant access: {}", json.roles.join(", "));
+                        }
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                AccessCommands::User { username } => {
+                    let res = ctx
+                        .post(format!("{website}/user"))
+                        .header("x-chamber-key", key)
+                        .json(&UserAccessParams { username })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => {
+                            let json = res.json::<UserAccess>()?;
+
+                            if !json.groups.is_empty() {
+                                println!("Inherits from groups: {}", json.groups.join(", "));
+                            }
+                            println!("{}", reachable_secrets_table(json.secrets));
+                        }
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                AccessCommands::Simulate(args) => {
+                    let res = ctx
+                        .post(format!("{website}/simulate"))
+                        .header("x-chamber-key", key)
+                        .json(&SimulateParams {
+                            username: args.username.clone(),
+                            key: args.key.clone(),
+                            capability: Some(args.capability),
+                        })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => {
+                            let json = res.json::<Decision>()?;
+
+                            let outcome = if json.allowed { "ALLOWED" } else { "DENIED" };
+                            println!(
+                                "{outcome}: {} {} {}",
+                                args.username, args.capability, args.key
+                            );
+                            match json.policy {
+                                Some(policy) => println!("Decided by policy: {policy}"),
+                                None => {
+                                    println!("No rule matched, so access is denied by default.")
+                                }
+                            }
+                            if let Some(rule) = json.rule {
+                                println!("{}", serde_json::to_string_pretty(&rule)?);
+                            }
+                        }
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+            }
+        }
+        Commands::Audit { limit } => {
+            let website = match cfg.website() {
+                Some(res) => format!("{res}/v1/audit"),
+                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+            };
+
+            let key = Text::new("Please enter your root key:").prompt()?;
+
+            let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+            let res = ctx
+                .get(website)
+                .header("x-chamber-key", key)
+                .query(&AuditLogQuery { limit: Some(limit) })
+                .send()?;
+
+            match res.status() {
+                StatusCode::OK => {
+                    let json = res.json::<Vec<AuditEntry>>()?;
+
+                    println!("{}", audit_table(json));
+                }
+                _ => println!("Error: {}", describe_error(res)?),
+            }
+        }
+        Commands::Attempts { limit } => {
+            let website = match cfg.website() {
+                Some(res) => format!("{res}/failed_attempts"),
+                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+            };
+
+            let key = Text::new("Please enter your root key:").prompt()?;
+
+            let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+            let res = ctx
+                .post(website)
+                .header("x-chamber-key", key)
+                .json(&FailedAttemptsParams { limit: Some(limit) })
+                .send()?;
+
+            match res.status() {
+                StatusCode::OK => {
+                    let json = res.json::<Vec<FailedAttempt>>()?;
+
+                    println!("{}", attempts_table(json));
+                }
+                _ => println!("Error: {}", describe_error(res)?),
+            }
+        }
+        Commands::Website { cmd } => match cmd {
+            WebsiteCommands::Get => match cfg.website() {
+                Some(res) => println!("{res}"),
+                None => println!("No website has been set!"),
+            },
+            WebsiteCommands::Set(args) => {
+                let value = match args.value {
+                    Some(res) => res,
+                    None => Text::new("Enter the website URL:").prompt()?,
+                };
+                cfg.set_website(&value)?;
+            }
+        },
+
+        Commands::Init(args) => {
+            let website = match cfg.website() {
+                Some(res) => res,
+                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+            };
+
+            let key = Text::new("Please enter your root key:").prompt()?;
+
+            let password = if args.generate_password {
+                None
+            } else {
+                Some(Password::new("Please enter a password for the admin user:").prompt()?)
+            };
+
+            let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+            let res = ctx
+                .post(format!("{website}/unseal"))
+                .header("x-chamber-key", &key)
+                .send()?;
+
+            if res.status() != StatusCode::OK {
+                println!("Couldn't unseal your Chamber instance: {}", describe_error(res)?);
+                return Ok(());
+            }
+
+            let res = ctx
+                .post(format!("{website}/init"))
+                .header("x-chamber-key", key)
+                .json(&InitParams {
+                    username: Some(args.username),
+                    password,
+                })
+                .send()?;
+
+            match res.status() {
+                StatusCode::CREATED => {
+                    let json = res.json::<InitResponse>()?;
+
+                    println!("Admin user {} has been created.", json.username);
+                    if let Some(password) = json.password {
+                        println!("Their password is: {password}");
+                        println!(
+                            "Make sure you keep it somewhere safe, as it won't be shown again!"
+                        );
+                    }
+                }
+                StatusCode::CONFLICT => {
+                    println!("Your Chamber instance has already been initialised.")
+                }
+                _ => println!("Error: {}", describe_error(res)?),
+            }
+        }
+
+        Commands::Login {
+            credentials,
+            code,
+            ssh_key,
+            ssh_agent,
+            cert,
+            cert_key,
+            token,
+        } => {
+            // External tokens are checked by the server on every request
+            if let Some(token) = token {
+                cfg.set_token(&format!("Bearer {token}"))?;
+
+                println!("Your token has been saved.");
+                return Ok(());
+            }
+
+            let use_ssh = ssh_key.is_some() || ssh_agent;
+            let use_cert = cert.is_some();
+
+            // Certificates log in as the user named in their subject
+            let username = match credentials.username {
+                Some(res) => res,
+                None if use_cert => String::new(),
+                None => Text::new("Please enter your username:").prompt()?,
+            };
+            let password = match credentials.password {
+                Some(res) => res,
+                None if use_ssh || use_cert => String::new(),
+                None => Text::new("Please enter your password:").prompt()?,
+            };
+
+            let ctx = match (cert, cert_key) {
+                (Some(cert), Some(key)) => {
+                    let identity = reqwest::Identity::from_pkcs8_pem(
+                        &std::fs::read(cert)?,
+                        &std::fs::read(key)?,
+                    )?;
+
+                    reqwest::blocking::Client::builder()
+                        .identity(identity)
+                        .build()?
+                }
+                _ => reqwest::blocking::Client::new(),
+            };
+
+            let website = match cfg.to_owned().website() {
+                Some(res) => res,
+                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+            };
+
+            let login = |code: Option<String>| -> Result<reqwest::blocking::Response, CliError> {
+                if use_cert {
+                    let res = ctx
+                        .post(format!("{website}/login/cert"))
+                        .json(&CertLoginParams {
+                            namespace: namespace.clone(),
+                            code,
+                        })
+                        .send()?;
+
+                    return Ok(res);
+                }
+
+                if !use_ssh {
+                    let res = ctx
+                        .post(format!("{website}/login"))
+                        .header("Content-Type", "application/json")
+                        .json(&UserLoginParams {
+                            namespace: namespace.clone(),
+                            username: username.clone(),
+                            password: password.clone(),
+                            code,
+                        })
+                        .send()?;
+
+                    return Ok(res);
+                }
+
+                // Every attempt needs a fresh challenge, as they can only be used once
+                let challenge = ctx
+                    .post(format!("{website}/login/ssh/challenge"))
+                    .json(&SshChallengeParams {
+                        namespace: namespace.clone(),
+                        username: username.clone(),
+                    })
+                    .send()?
+                    .error_for_status()?
+                    .json::<SshChallenge>()?;
+
+                let signature = ssh::sign_challenge(ssh_key.as_deref(), ssh_agent, &challenge)?;
+
+                let res = ctx
+                    .post(format!("{website}/login/ssh"))
+                    .json(&SshLoginParams {
+                        namespace: namespace.clone(),
+                        username: username.clone(),
+                        nonce: challenge.nonce,
+                        signature,
+                        code,
+                    })
+                    .send()?;
+
+                Ok(res)
+            };
+
+            let mut res = login(code.clone())?;
+
+            // The server only asks for a code once the password or signature is right
+            if res.status() == StatusCode::UNAUTHORIZED && code.is_none() {
+                let body = res.json::<ErrorBody>()?;
+
+                if body.code != ErrorCode::TwoFactorRequired {
+                    println!("Something went wrong: {body}");
+                    return Ok(());
+                }
+
+                let code = Text::new("Please enter your two-factor code:").prompt()?;
+                res = login(Some(code))?;
+            }
+
+            match res.status() {
+                StatusCode::OK => {
+                    let res = res.json::<AuthBody>()?;
+
+                    let token = format!("{} {}", res.token_type, res.access_token);
+                    cfg.set_token(&token)?;
+
+                    println!("You've logged in successfully!");
+                }
+                _ => {
+                    println!("Something went wrong: {}", describe_error(res)?);
+                }
+            }
+        }
+
+        Commands::Whoami => {
+            let Some(jwt) = cfg.clone().jwt_key() else {
+                panic!("You need to log in before you can do that!");
+            };
+
+            let website = match cfg.website() {
+                Some(res) => format!("{res}/me"),
+                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+            };
+
+            let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+            let res = ctx.get(website).header("Authorization", jwt).send()?;
+
+            match res.status() {
+                StatusCode::OK => {
+                    let json = res.json::<UserProfile>()?;
+
+                    println!("Username: {}", json.username);
+                    println!(
+                        "Namespace: {}{}",
+                        json.namespace,
+                        if json.namespace_admin { " (admin)" } else { "" }
+                    );
+                    println!(
+                        "Access level: {} (effective: {})",
+                        json.access_level, json.effective_access_level
+                    );
+                    println!("Roles: {}", json.roles.join(", "));
+                    println!("Effective roles: {}", json.effective_roles.join(", "));
+                    println!("Groups: {}", json.groups.join(", "));
+                    println!(
+                        "Two-factor authentication: {}",
+                        match (json.two_factor_enabled, json.two_factor_required) {
+                            (true, _) => "enabled",
+                            (false, true) => "required, run `chamber 2fa enroll`",
+                            (false, false) => "disabled",
+                        }
+                    );
+                }
+                _ => println!("Error: {}", describe_error(res)?),
+            }
+        }
+
+        Commands::Passwd => {
+            let Some(jwt) = cfg.clone().jwt_key() else {
+                panic!("You need to log in before you can do that!");
+            };
+
+            let website = match cfg.website() {
+                Some(res) => format!("{res}/me/password"),
+                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+            };
+
+            let old_password = Password::new("Please enter your current password:")
+                .without_confirmation()
+                .prompt()?;
+            let new_password = Password::new("Please enter your new password:").prompt()?;
+
+            let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+            let res = ctx
+                .put(website)
+                .header("Authorization", jwt)
+                .json(&ChangePasswordParams {
+                    old_password,
+                    new_password,
+                })
+                .send()?;
+
+            match res.status() {
+                StatusCode::OK => println!("Your password has been changed."),
+                StatusCode::UNAUTHORIZED => println!("Your current password was wrong."),
+                _ => println!("Error: {}", describe_error(res)?),
+            }
+        }
+
+        Commands::TwoFactor { cmd } => {
+            let Some(jwt) = cfg.clone().jwt_key() else {
+                panic!("You need to log in before you can do that!");
+            };
+
+            let website = match cfg.website() {
+                Some(res) => format!("{res}/me/2fa"),
+                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+            };
+
+            let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+            match cmd {
+                TwoFactorCommands::Enroll => {
+                    let password = Password::new("Please enter your password:")
+                        .without_confirmation()
+                        .prompt()?;
+
+                    let res = ctx
+                        .post(format!("{website}/enroll"))
+                        .header("Authorization", &jwt)
+                        .json(&TwoFactorPasswordParams { password })
+                        .send()?;
+
+                    if res.status() != StatusCode::OK {
+                        println!("Error: {}", describe_error(res)?);
+                        return Ok(());
+                    }
+
+                    let enrollment = res.json::<TwoFactorEnrollment>()?;
+
+                    println!("Add this to your authenticator app:");
+                    println!("{}", enrollment.otpauth_uri);
+                    println!("Or enter the secret manually: {}", enrollment.secret);
+
+                    let code =
+                        Text::new("Please enter the code from your authenticator app:").prompt()?;
+
+                    let res = ctx
+                        .post(format!("{website}/confirm"))
+                        .header("Authorization", &jwt)
+                        .json(&TwoFactorCodeParams { code })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => {
+                            let codes = res.json::<RecoveryCodes>()?;
+
+                            println!("Two-factor authentication is now enabled.");
+                            println!("Store these recovery codes somewhere safe, they won't be shown again:");
+                            codes.recovery_codes.iter().for_each(|x| println!("{x}"));
+                            println!("Log in again to use your new second factor.");
+                        }
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                TwoFactorCommands::Disable => {
+                    let password = Password::new("Please enter your password:")
+                        .without_confirmation()
+                        .prompt()?;
+                    let code = Text::new("Please enter a two-factor or recovery code:").prompt()?;
+
+                    let res = ctx
+                        .post(format!("{website}/disable"))
+                        .header("Authorization", jwt)
+                        .json(&DisableTwoFactorParams { password, code })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => println!("Two-factor authentication has been disabled."),
+                        StatusCode::UNAUTHORIZED => println!("Your password was wrong."),
+                        StatusCode::FORBIDDEN => {
+                            println!("Two-factor authentication is required for your access level.")
+                        }
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+            }
+        }
+
+        Commands::Unseal {
+            chamber_key,
+            ssh_key,
+            ssh_agent,
+        } => {
+            let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+            let website = match cfg.to_owned().website() {
+                Some(res) => res,
+                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+            };
+
+            let req = ctx
+                .post(format!("{website}/unseal"))
+                .header("Content-Type", "application/json");
+
+            let req = match (chamber_key, ssh_key.is_some() || ssh_agent) {
+                (_, true) => req.json(&sign_unseal_challenge(
+                    &ctx,
+                    &website,
+                    ssh_key.as_deref(),
+                    ssh_agent,
+                )?),
+                (Some(key), false) => req.header("x-chamber-key", key),
+                (None, false) => return Err(CliError::AtLeastOneArgError),
+            };
+
+            let res = req.send()?;
+
+            match res.status() {
+                StatusCode::OK => println!("The instance has been unsealed and is ready to use!"),
+                _ => {
+                    println!("{}", describe_error(res)?);
+                }
+            }
+        }
+        Commands::UnsealKeys { cmd } => {
+            let base = match cfg.website() {
+                Some(res) => res,
+                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+            };
+            let website = format!("{base}/unseal/keys");
+
+            let key = Text::new("Please enter your root key:").prompt()?;
+
+            let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+            match cmd {
+                UnsealKeyCommands::Add {
+                    name,
+                    path,
+                    approval,
+                } => {
+                    let public_key = ssh::read_public_key(&path)?.to_openssh()?;
+
+                    // Only needed once there are unseal keys, which the server checks
+                    let approval = match approval.ssh_key.is_some() || approval.ssh_agent {
+                        true => Some(sign_unseal_challenge(
+                            &ctx,
+                            &base,
+                            approval.ssh_key.as_deref(),
+                            approval.ssh_agent,
+                        )?),
+                        false => None,
+                    };
+
+                    let res = ctx
+                        .post(format!("{website}/create"))
+                        .header("x-chamber-key", key)
+                        .json(&AddUnsealKeyParams {
+                            name,
+                            public_key,
+                            approval,
+                        })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::CREATED => {
+                            let key = res.json::<UnsealKey>()?;
+                            println!("Added unseal key {} for {}.", key.key.fingerprint, key.name);
+                            println!("The root key can no longer be used to unseal the instance.");
+                        }
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                UnsealKeyCommands::List => {
+                    let res = ctx
+                        .post(format!("{website}/list"))
+                        .header("x-chamber-key", key)
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => {
+                            let json = res.json::<Vec<UnsealKey>>()?;
+
+                            println!("{}", unseal_keys_table(json));
+                        }
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                UnsealKeyCommands::Remove {
+                    fingerprint,
+                    approval,
+                } => {
+                    if approval.ssh_key.is_none() && !approval.ssh_agent {
+                        return Err(CliError::AtLeastOneArgError);
+                    }
+
+                    let approval = sign_unseal_challenge(
+                        &ctx,
+                        &base,
+                        approval.ssh_key.as_deref(),
+                        approval.ssh_agent,
+                    )?;
+
+                    let res = ctx
+                        .delete(format!("{website}/delete"))
+                        .header("x-chamber-key", key)
+                        .json(&RemoveUnsealKeyParams {
+                            fingerprint,
+                            approval,
+                        })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => println!("Unseal key has been removed."),
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+            }
+        }
+        Commands::Upload(args) => {
+            let key = match args.key {
+                Some(res) => res,
+                None => Text::new("Please enter your root key:").prompt()?,
+            };
+            let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+            let website = match cfg.to_owned().website() {
+                Some(res) => format!("{res}/binfile"),
+                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+            };
+
+            let file = std::fs::read("chamber.bin")?;
+
+            let form = reqwest::blocking::multipart::Form::new();
+            let file_as_bytes = reqwest::blocking::multipart::Part::bytes(file);
+
+            let form = form.part("file", file_as_bytes);
+
+            let res = ctx
+                .post(website)
+                .header("x-chamber-key", key)
+                .multipart(form)
+                .send()?;
+
+            match res.status() {
+                StatusCode::OK => {
+                    println!("The new crypto key and root key have been uploaded! All of your secrets have been re-keyed.");
+                }
+                _ => {
+                    println!("{}", describe_error(res)?);
+                }
+            }
+        }
+        Commands::Ssh { cmd } => {
+            let Some(jwt) = cfg.clone().jwt_key() else {
+                panic!("You need to log in before you can do that!");
+            };
+
+            let website = match cfg.website() {
+                Some(res) => format!("{res}/me/ssh_keys"),
+                None => panic!("You didn't set a URL for a Chamber instance to log into!"),
+            };
+
+            let ctx = client(namespace.as_deref(), environment.as_deref())?;
+
+            match cmd {
+                SshCommands::Add { path } => {
+                    let public_key = ssh::read_public_key(&path)?.to_openssh()?;
+
+                    let res = ctx
+                        .post(website)
+                        .header("Authorization", jwt)
+                        .json(&AddSshKeyParams { public_key })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::CREATED => {
+                            let key = res.json::<SshKey>()?;
+                            println!("Added SSH key {}.", key.fingerprint);
+                        }
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                SshCommands::List => {
+                    let res = ctx.get(website).header("Authorization", jwt).send()?;
+
+                    match res.status() {
+                        StatusCode::OK => {
+                            let json = res.json::<Vec<SshKey>>()?;
+
+                            println!("{}", ssh_keys_table(json));
+                        }
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+                SshCommands::Remove { fingerprint } => {
+                    let res = ctx
+                        .delete(website)
+                        .header("Authorization", jwt)
+                        .json(&RemoveSshKeyParams { fingerprint })
+                        .send()?;
+
+                    match res.status() {
+                        StatusCode::OK => println!("SSH key has been removed."),
+                        _ => println!("Error: {}", describe_error(res)?),
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn secrets_table(secrets: Vec<SecretInfo>) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec!["Secret Key", "Tags"]);
+
+    secrets.into_iter().for_each(|x| {
+        table.add_row(vec![x.key, x.tags.join(", ")]);
+    });
+
+    table
+}
+
+/// Prints secrets as a tree of their paths. A key that's also a folder, such as `db` next to
+/// `db/password`, gets an entry for each.
+pub fn secrets_tree(secrets: Vec<SecretInfo>) -> String {
+    #[derive(Default)]
+    struct Node {
+        secret: bool,
+        children: BTreeMap<String, Node>,
+    }
+
+    fn render(node: &Node, indent: &str, out: &mut String) {
+        let entries: Vec<(String, Option<&Node>)> = node
+            .children
+            .iter()
+            .flat_map(|(name, child)| {
+                let secret = child.secret.then(|| (name.clone(), None));
+                let folder =
+                    (!child.children.is_empty()).then(|| (format!("{name}/"), Some(child)));
+
+                secret.into_iter().chain(folder)
+            })
+            .collect();
+
+        for (i, (name, child)) in entries.iter().enumerate() {
+            let last = i + 1 == entries.len();
+            let (branch, nesting) = match last {
+                true => ("└── ", "    "),
+                false => ("├── ", "│   "),
+            };
+
+            out.push_str(&format!("{indent}{branch}{name}\n"));
+
+            if let Some(child) = child {
+                render(child, &format!("{indent}{nesting}"), out);
+            }
+        }
+    }
+
+    let mut root = Node::default();
+
+    for secret in secrets {
+        let node = secret.key.split('/').fold(&mut root, |node, segment| {
+            node.children.entry(segment.to_string()).or_default()
+        });
+
+        node.secret = true;
+    }
+
+    let mut out = String::from(".\n");
+    render(&root, "", &mut out);
+
+    out.trim_end().to_string()
+}
+
+pub fn folder_table(folder: FolderListing) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec!["Path", "Tags"]);
+
+    folder.folders.into_iter().for_each(|x| {
+        table.add_row(vec![format!("{x}/"), String::new()]);
+    });
+
+    folder.secrets.into_iter().for_each(|x| {
+        table.add_row(vec![x.key, x.tags.join(", ")]);
+    });
+
+    table
+}
+
+pub fn secrets_table_decrypted(secrets: Vec<SecretPublic>) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec!["Key", "Value"]);
+
+    secrets.into_iter().for_each(|x| {
+        table.add_row(vec![x.key, x.value]);
+    });
+
+    table
+}
+
+pub fn users_table(users: Vec<UserSummary>) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec![
+        "Username",
+        "Access Level",
+        "Roles",
+        "Status",
+        "2FA",
+        "Namespace Admin",
+        "Expires At",
+        "Created At",
+    ]);
+
+    users.into_iter().for_each(|x| {
+        let status = if x.disabled { "disabled" } else { "active" };
+        let two_factor = if x.two_factor_enabled { "enabled" } else { "" };
+        let namespace_admin = if x.namespace_admin { "yes" } else { "" };
+
+        table.add_row(vec![
+            x.username,
+            x.access_level.to_string(),
+            x.roles.join(", "),
+            status.to_string(),
+            two_factor.to_string(),
+            namespace_admin.to_string(),
+            x.expires_at.map(|x| x.to_rfc3339()).unwrap_or_default(),
+            x.created_at.to_rfc3339(),
+        ]);
+    });
+
+    table
+}
+
+pub fn ssh_keys_table(keys: Vec<SshKey>) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec!["Fingerprint", "Comment", "Added At"]);
+
+    keys.into_iter().for_each(|x| {
+        table.add_row(vec![x.fingerprint, x.comment, x.created_at.to_rfc3339()]);
+    });
+
+    table
+}
+
+pub fn unseal_keys_table(keys: Vec<UnsealKey>) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec!["Name", "Fingerprint", "Comment", "Added At"]);
+
+    keys.into_iter().for_each(|x| {
+        table.add_row(vec![
+            x.name,
+            x.key.fingerprint,
+            x.key.comment,
+            x.key.created_at.to_rfc3339(),
+        ]);
+    });
+
+    table
+}
+
+pub fn attempts_table(attempts: Vec<FailedAttempt>) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec!["Time", "Kind", "Username", "IP", "Reason"]);
+
+    attempts.into_iter().for_each(|x| {
+        table.add_row(vec![
+            x.created_at.to_rfc3339(),
+            x.kind,
+            x.username.unwrap_or_default(),
+            x.ip.unwrap_or_default(),
+            x.reason,
+        ]);
+    });
+
+    table
+}
+
+pub fn audit_table(entries: Vec<AuditEntry>) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec!["Time", "Action", "Username", "Key", "From", "To"]);
+
+    entries.into_iter().for_each(|x| {
+        table.add_row(vec![
+            x.created_at.to_rfc3339(),
+            x.action,
+            x.username,
+            x.key,
+            x.from_environment.unwrap_or_default(),
+            x.environment,
+        ]);
+    });
+
+    table
+}
+
+pub fn policies_table(policies: Vec<Policy>) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec!["Name", "Description", "Users", "Roles", "Rules"]);
+
+    policies.into_iter().for_each(|x| {
+        table.add_row(vec![
+            x.name,
+            x.description,
+            x.users.join(", "),
+            x.roles.join(", "),
+            x.rules.len().to_string(),
+        ]);
+    });
+
+    table
+}
+
+pub fn access_grants_table(grants: Vec<AccessGrant>) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec!["Username", "Policy", "Rule Path", "Via Roles", "Via Groups"]);
+
+    grants.into_iter().for_each(|x| {
+        table.add_row(vec![
+            x.username,
+            x.decision.policy.unwrap_or_default(),
+            x.decision.rule.map(|x| x.path).unwrap_or_default(),
+            x.roles.join(", "),
+            x.groups.join(", "),
+        ]);
+    });
+
+    table
+}
+
+pub fn reachable_secrets_table(secrets: Vec<ReachableSecret>) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec!["Key", "Capabilities", "Read Decided By"]);
+
+    secrets.into_iter().for_each(|x| {
+        table.add_row(vec![
+            x.key,
+            x.capabilities
+                .iter()
+                .map(|x| x.as_str())
+                .collect::<Vec<&str>>()
+                .join(", "),
+            x.decision.policy.unwrap_or_default(),
+        ]);
+    });
+
+    table
+}
+
+pub fn roles_table(roles: Vec<RoleUsage>) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec!["Name", "Description", "Users", "Groups", "Secrets"]);
+
+    roles.into_iter().for_each(|x| {
+        table.add_row(vec![
+            x.name,
+            x.description,
+            x.users.join(", "),
+            x.groups.join(", "),
+            x.secrets.join(", "),
+        ]);
+    });
+
+    table
+}
+
+pub fn groups_table(groups: Vec<Group>) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec![
+        "Name",
+        "Description",
+        "Access Level",
+        "Roles",
+        "Members",
+        "Subgroups",
+    ]);
+
+    groups.into_iter().for_each(|x| {
+        table.add_row(vec![
+            x.name,
+            x.description,
+            x.access_level.to_string(),
+            x.roles.join(", "),
+            x.members.join(", "),
+            x.subgroups.join(", "),
+        ]);
+    });
+
+    table
+}
+
+pub fn namespaces_table(namespaces: Vec<Namespace>) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec!["Name", "Description"]);
+
+    namespaces.into_iter().for_each(|x| {
+        table.add_row(vec![x.name, x.description]);
     });
 
     table
@@ -1,3 +1,4 @@
+use chamber_shared::errors::ErrorBody;
 use std::fmt;
 
 #[derive(Debug)]
@@ -6,6 +7,11 @@ pub enum CliError {
     IoError(std::io::Error),
     RequestError(reqwest::Error),
     PromptError(inquire::error::InquireError),
+    JsonError(serde_json::Error),
+    SshError(ssh_key::Error),
+    SshAgentError(String),
+    InvalidNamespace(String),
+    InvalidEnvironment(String),
     AtLeastOneArgError
 }
 
@@ -17,6 +23,11 @@ impl fmt::Display for CliError {
             Self::RequestError(err) => write!(f, "Error while using HTTP request: {err}"),
             Self::PromptError(err) => write!(f, "Error while attempting to use prompt: {err}"),
             Self::IoError(err) => write!(f, "Error during file I/O: {err}"),
+            Self::JsonError(err) => write!(f, "Error while parsing JSON: {err}"),
+            Self::SshError(err) => write!(f, "Error while using SSH key: {err}"),
+            Self::SshAgentError(err) => write!(f, "Error while using ssh-agent: {err}"),
+            Self::InvalidNamespace(err) => write!(f, "{err}"),
+            Self::InvalidEnvironment(err) => write!(f, "{err}"),
             Self::AtLeastOneArgError => write!(f, "You need at least one option filled."),
         }
     }
@@ -46,6 +57,18 @@ impl From<std::io::Error> for CliError {
     }
 }
 
+impl From<ssh_key::Error> for CliError {
+    fn from(err: ssh_key::Error) -> Self {
+        Self::SshError(err)
+    }
+}
+
+impl From<serde_json::Error> for CliError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::JsonError(err)
+    }
+}
+
 #[derive(Debug)]
 pub enum ConfigError {
     Deserialization(toml::de::Error),
@@ -80,3 +103,13 @@ impl From<toml::ser::Error> for ConfigError {
         Self::Serialization(err)
     }
 }
+
+/// Describes an error response from the server, using its error body when it has one.
+pub fn describe_error(res: reqwest::blocking::Response) -> Result<String, CliError> {
+    let text = res.text()?;
+
+    match serde_json::from_str::<ErrorBody>(&text) {
+        Ok(body) => Ok(body.to_string()),
+        Err(_) => Ok(text),
+    }
+}
@@ -2,6 +2,7 @@ pub mod args;
 pub mod commands;
 pub mod config;
 pub mod errors;
+pub mod ssh;
 
 use crate::args::Cli;
 use crate::commands::parse_cli;
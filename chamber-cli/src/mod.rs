@@ -2,3 +2,4 @@ pub mod args;
 pub mod commands;
 pub mod config;
 pub mod errors;
+pub mod ssh;
@@ -0,0 +1,208 @@
+use std::path::{Path, PathBuf};
+
+use chamber_shared::ssh::SshChallenge;
+use inquire::Password;
+use ssh_key::{HashAlg, LineEnding, PrivateKey, PublicKey};
+
+use crate::errors::CliError;
+
+/// Signs a challenge with a private key file, prompting for its passphrase if it has one.
+pub fn sign_with_key_file(path: &Path, namespace: &str, nonce: &str) -> Result<String, CliError> {
+    let mut key = PrivateKey::read_openssh_file(path)?;
+
+    if key.is_encrypted() {
+        let passphrase = Password::new("Please enter the passphrase for your SSH key:")
+            .without_confirmation()
+            .prompt()?;
+
+        key = key.decrypt(passphrase)?;
+    }
+
+    let sig = key.sign(namespace, HashAlg::Sha512, nonce.as_bytes())?;
+
+    Ok(sig.to_pem(LineEnding::LF)?)
+}
+
+/// Signs a challenge from the server with a key file, or through ssh-agent if `agent` is set.
+pub fn sign_challenge(
+    key: Option<&Path>,
+    agent: bool,
+    challenge: &SshChallenge,
+) -> Result<String, CliError> {
+    match key {
+        Some(path) if !agent => sign_with_key_file(path, &challenge.namespace, &challenge.nonce),
+        key => sign_with_agent(key, &challenge.namespace, &challenge.nonce),
+    }
+}
+
+/// Reads a public key, accepting either the `.pub` file or the private key path next to it.
+pub fn read_public_key(path: &Path) -> Result<PublicKey, CliError> {
+    if path.extension().is_some_and(|x| x == "pub") {
+        return Ok(PublicKey::read_openssh_file(path)?);
+    }
+
+    let mut public = path.as_os_str().to_owned();
+    public.push(".pub");
+
+    Ok(PublicKey::read_openssh_file(&PathBuf::from(public))?)
+}
+
+/// Signs a challenge through the ssh-agent at `SSH_AUTH_SOCK`.
+/// Uses the given key if there is one, otherwise the agent's first ed25519 key.
+#[cfg(unix)]
+pub fn sign_with_agent(
+    key: Option<&Path>,
+    namespace: &str,
+    nonce: &str,
+) -> Result<String, CliError> {
+    use ssh_key::{Algorithm, Signature, SshSig};
+    use std::os::unix::net::UnixStream;
+
+    let socket = std::env::var("SSH_AUTH_SOCK")
+        .map_err(|_| CliError::SshAgentError("SSH_AUTH_SOCK isn't set".to_string()))?;
+    let mut agent = UnixStream::connect(socket)?;
+
+    let wanted = key.map(read_public_key).transpose()?;
+
+    let public_key = agent::identities(&mut agent)?
+        .into_iter()
+        .find(|x| match &wanted {
+            Some(wanted) => x.key_data() == wanted.key_data(),
+            None => x.algorithm() == Algorithm::Ed25519,
+        })
+        .ok_or_else(|| {
+            CliError::SshAgentError("The agent doesn't have a matching ed25519 key".to_string())
+        })?;
+
+    let data = SshSig::signed_data(namespace, HashAlg::Sha512, nonce.as_bytes())?;
+    let signature = agent::sign(&mut agent, &public_key.to_bytes()?, &data)?;
+
+    let sig = SshSig::new(
+        public_key.key_data().clone(),
+        namespace,
+        HashAlg::Sha512,
+        Signature::try_from(signature.as_slice())?,
+    )?;
+
+    Ok(sig.to_pem(LineEnding::LF)?)
+}
+
+#[cfg(not(unix))]
+pub fn sign_with_agent(
+    _key: Option<&Path>,
+    _namespace: &str,
+    _nonce: &str,
+) -> Result<String, CliError> {
+    Err(CliError::SshAgentError(
+        "ssh-agent is only supported on Unix".to_string(),
+    ))
+}
+
+/// Just enough of the ssh-agent protocol to list keys and sign with them.
+/// See https://datatracker.ietf.org/doc/html/draft-miller-ssh-agent
+#[cfg(unix)]
+mod agent {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    use ssh_key::PublicKey;
+
+    use crate::errors::CliError;
+
+    const SSH_AGENT_FAILURE: u8 = 5;
+    const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+    const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+    const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+    const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+
+    pub fn identities(agent: &mut UnixStream) -> Result<Vec<PublicKey>, CliError> {
+        let res = call(agent, &[SSH_AGENTC_REQUEST_IDENTITIES])?;
+        let mut body = expect(&res, SSH_AGENT_IDENTITIES_ANSWER)?;
+
+        let count = read_u32(&mut body)?;
+        let mut keys = Vec::new();
+
+        for _ in 0..count {
+            let blob = read_string(&mut body)?;
+            let _comment = read_string(&mut body)?;
+
+            // Skip over keys that ssh-key doesn't understand, like certificates
+            if let Ok(key) = PublicKey::from_bytes(blob) {
+                keys.push(key);
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Returns the encoded signature blob.
+    pub fn sign(agent: &mut UnixStream, key_blob: &[u8], data: &[u8]) -> Result<Vec<u8>, CliError> {
+        let mut req = vec![SSH_AGENTC_SIGN_REQUEST];
+        write_string(&mut req, key_blob);
+        write_string(&mut req, data);
+        req.extend_from_slice(&0u32.to_be_bytes());
+
+        let res = call(agent, &req)?;
+        let mut body = expect(&res, SSH_AGENT_SIGN_RESPONSE)?;
+
+        Ok(read_string(&mut body)?.to_vec())
+    }
+
+    fn call(agent: &mut UnixStream, req: &[u8]) -> Result<Vec<u8>, CliError> {
+        agent.write_all(&(req.len() as u32).to_be_bytes())?;
+        agent.write_all(req)?;
+
+        let mut len = [0u8; 4];
+        agent.read_exact(&mut len)?;
+
+        let mut res = vec![0u8; u32::from_be_bytes(len) as usize];
+        agent.read_exact(&mut res)?;
+
+        Ok(res)
+    }
+
+    fn expect(res: &[u8], kind: u8) -> Result<&[u8], CliError> {
+        match res.split_first() {
+            Some((x, body)) if *x == kind => Ok(body),
+            Some((&SSH_AGENT_FAILURE, _)) => Err(CliError::SshAgentError(
+                "The agent refused the request".to_string(),
+            )),
+            _ => Err(CliError::SshAgentError(
+                "The agent sent an unexpected response".to_string(),
+            )),
+        }
+    }
+
+    fn read_u32(buf: &mut &[u8]) -> Result<u32, CliError> {
+        if buf.len() < 4 {
+            return Err(CliError::SshAgentError(
+                "The agent's response was truncated".to_string(),
+            ));
+        }
+
+        let (head, rest) = buf.split_at(4);
+        *buf = rest;
+
+        Ok(u32::from_be_bytes([head[0], head[1], head[2], head[3]]))
+    }
+
+    fn read_string<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8], CliError> {
+        let len = read_u32(buf)? as usize;
+
+        if buf.len() < len {
+            return Err(CliError::SshAgentError(
+                "The agent's response was truncated".to_string(),
+            ));
+        }
+
+        let (head, rest) = buf.split_at(len);
+        *buf = rest;
+
+        Ok(head)
+    }
+
+    fn write_string(buf: &mut Vec<u8>, value: &[u8]) {
+        buf.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        buf.extend_from_slice(value);
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::errors::DatabaseError;
+
+/// Once this many challenges are outstanding, expired ones get dropped and new ones are refused
+/// until there's room, so that the map can't grow forever.
+const MAX_OUTSTANDING: usize = 10_000;
+
+/// Only a handful of operators unseal at once, so the unseal store is kept much smaller.
+pub const MAX_UNSEAL_CHALLENGES: usize = 100;
+
+#[derive(Clone, Debug)]
+struct Challenge {
+    subject: String,
+    issued_at: Instant,
+}
+
+/// Single-use nonces for signature based authentication, tied to whoever asked for them.
+/// State is kept in memory, so restarting the server invalidates outstanding challenges.
+#[derive(Clone, Debug)]
+pub struct ChallengeStore {
+    ttl: Duration,
+    max_outstanding: usize,
+    challenges: Arc<Mutex<HashMap<String, Challenge>>>,
+}
+
+impl Default for ChallengeStore {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(60))
+    }
+}
+
+impl ChallengeStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            max_outstanding: MAX_OUTSTANDING,
+            challenges: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn with_max_outstanding(mut self, max_outstanding: usize) -> Self {
+        self.max_outstanding = max_outstanding;
+        self
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Creates a random nonce for the subject (a username, for example).
+    pub fn issue(&self, subject: &str) -> Result<String, DatabaseError> {
+        let mut bytes = [0u8; 32];
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .map_err(|_| DatabaseError::EncryptionError)?;
+        let nonce = hex::encode(bytes);
+
+        let mut challenges = self.challenges.lock().unwrap_or_else(|e| e.into_inner());
+
+        if challenges.len() >= self.max_outstanding {
+            let ttl = self.ttl;
+            challenges.retain(|_, x| x.issued_at.elapsed() <= ttl);
+
+            if challenges.len() >= self.max_outstanding {
+                // The oldest challenge is the next one to expire and make room
+                let oldest = challenges.values().map(|x| x.issued_at).min();
+                let wait = oldest.map_or(ttl, |x| ttl.saturating_sub(x.elapsed()));
+
+                return Err(DatabaseError::TooManyChallenges(wait));
+            }
+        }
+
+        challenges.insert(
+            nonce.clone(),
+            Challenge {
+                subject: subject.to_string(),
+                issued_at: Instant::now(),
+            },
+        );
+
+        Ok(nonce)
+    }
+
+    /// Uses up a nonce, returning whether it was issued to this subject and hasn't expired.
+    pub fn take(&self, nonce: &str, subject: &str) -> bool {
+        let mut challenges = self.challenges.lock().unwrap_or_else(|e| e.into_inner());
+
+        challenges
+            .remove(nonce)
+            .is_some_and(|x| x.subject == subject && x.issued_at.elapsed() <= self.ttl)
+    }
+}
@@ -1 +1,10 @@
 pub static KEYFILE_PATH: &str = "data/chamber.bin";
+
+/// Where the public keys of unseal operators are persisted, next to the keyfile.
+pub static UNSEAL_KEYS_PERSIST_KEY: &str = "UNSEAL_KEYS";
+
+/// Where each namespace's wrapped data key is persisted, next to the keyfile.
+pub static DATA_KEYS_PERSIST_KEY: &str = "DATA_KEYS";
+
+/// The access level given to the first admin created when bootstrapping an instance.
+pub static ROOT_ACCESS_LEVEL: i32 = 9001;
@@ -1,49 +1,126 @@
 use crate::errors::DatabaseError;
-use chamber_crypto::secrets::{EncryptedSecret, Secret, SecretInfo};
+use chamber_crypto::secrets::{EncryptedSecret, Secret};
+use chamber_shared::attempts::FailedAttempt;
+use chamber_shared::audit::AuditEntry;
+use chamber_shared::groups::Group;
+use chamber_shared::namespaces::Namespace;
+use chamber_shared::policy::{Capability, Decision, Policy, SecretAccess, UserAccess};
+use chamber_shared::roles::{Role, RoleUsage};
+use chamber_shared::secrets::{FolderListing, MovedSecret, SecretFilter, SecretPage};
+use chamber_shared::ssh::SshKey;
 use chrono::{DateTime, Utc};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use serde::{Deserialize};
-
 use crate::users::User;
 
-#[derive(Deserialize, Debug)]
-pub struct CreateSecretParams {
-    pub key: String,
-    pub value: String,
-    pub tags: Option<Vec<String>>,
-    pub access_level: Option<i32>,
-    pub role_whitelist: Option<Vec<String>>,
-}
-
 #[async_trait::async_trait]
 pub trait Database {
+    /// A handle to the same database that only sees the data in the given namespace.
+    fn in_namespace(&self, namespace: &str) -> Self
+    where
+        Self: Sized;
+    /// The namespace that this handle sees. Handles start out in the default namespace.
+    fn namespace(&self) -> &str;
+    /// A handle to the same namespace that reads and writes the secrets' values in the given
+    /// environment.
+    fn in_environment(&self, environment: &str) -> Self
+    where
+        Self: Sized;
+    /// The environment that this handle sees. Handles start out in the default environment.
+    fn environment(&self) -> &str;
+    async fn view_namespaces(&self) -> Result<Vec<Namespace>, DatabaseError>;
+    async fn get_namespace(&self, name: String) -> Result<Namespace, DatabaseError>;
+    async fn create_namespace(&self, namespace: Namespace) -> Result<(), DatabaseError>;
+    async fn delete_namespace(&self, name: String) -> Result<(), DatabaseError>;
+    /// Whether the namespace's secrets are sealed with its data key yet.
+    async fn has_data_key(&self) -> Result<bool, DatabaseError>;
+    /// Stores the namespace's secrets, re-encrypted under its first data key, and marks it as
+    /// having one. Returns false without changing anything if it already has one.
+    async fn seal_with_data_key(
+        &self,
+        secrets: Vec<EncryptedSecret>,
+    ) -> Result<bool, DatabaseError>;
     async fn view_all_secrets_admin(&self) -> Result<Vec<EncryptedSecret>, DatabaseError>;
     async fn view_all_secrets(
         &self,
         user: User,
-        tag: Option<String>,
-    ) -> Result<Vec<SecretInfo>, DatabaseError>;
+        filter: SecretFilter,
+    ) -> Result<SecretPage, DatabaseError>;
+    async fn view_folder(&self, user: User, prefix: String) -> Result<FolderListing, DatabaseError>;
+    async fn move_secrets(
+        &self,
+        user: User,
+        from: String,
+        to: String,
+    ) -> Result<Vec<MovedSecret>, DatabaseError>;
     async fn view_secret_decrypted(&self, user: User, key: String)
         -> Result<Secret, DatabaseError>;
     async fn view_secrets_decrypted_by_tag(&self, user: User, key: String)
         -> Result<Vec<Secret>, DatabaseError>;
     async fn view_secret(&self, user: User, key: String) -> Result<EncryptedSecret, DatabaseError>;
-    async fn create_secret(&self, secret: EncryptedSecret) -> Result<(), DatabaseError>;
+    async fn create_secret(&self, user: User, secret: EncryptedSecret) -> Result<(), DatabaseError>;
     async fn update_secret(
         &self,
+        user: User,
         key: String,
         secret: EncryptedSecret,
     ) -> Result<(), DatabaseError>;
-    async fn rekey_all_secrets(&self, secrets: Vec<EncryptedSecret>) -> Result<(), DatabaseError>;
-    async fn delete_secret(&self, key: String) -> Result<(), DatabaseError>;
+    async fn delete_secret(&self, user: User, key: String) -> Result<(), DatabaseError>;
+    /// Stores a value read from another environment in this one, and records it in the audit log.
+    /// A secret that's already here keeps its tags, access level and role whitelist.
+    async fn promote_secret(
+        &self,
+        user: User,
+        from: String,
+        secret: EncryptedSecret,
+    ) -> Result<(), DatabaseError>;
+    async fn view_audit_log(&self, limit: i64) -> Result<Vec<AuditEntry>, DatabaseError>;
     async fn view_users(&self) -> Result<Vec<User>, DatabaseError>;
     async fn get_user_from_name(&self, id: String) -> Result<User, DatabaseError>;
     async fn get_user_from_password(&self, password: String) -> Result<User, DatabaseError>;
     async fn create_user(&self, user: User) -> Result<String, DatabaseError>;
+    async fn bootstrap_user(&self, user: User) -> Result<(), DatabaseError>;
+    async fn record_failed_attempt(&self, attempt: FailedAttempt) -> Result<(), DatabaseError>;
+    async fn view_failed_attempts(&self, limit: i64) -> Result<Vec<FailedAttempt>, DatabaseError>;
     async fn update_user(&self, user: User) -> Result<(), DatabaseError>;
+    async fn update_user_password(&self, user: User) -> Result<(), DatabaseError>;
+    async fn update_user_two_factor(&self, user: User) -> Result<(), DatabaseError>;
+    async fn view_two_factor_level(&self) -> Result<Option<i32>, DatabaseError>;
+    async fn set_two_factor_level(&self, level: Option<i32>) -> Result<(), DatabaseError>;
+    async fn view_ssh_keys(&self, username: String) -> Result<Vec<SshKey>, DatabaseError>;
+    async fn add_ssh_key(&self, username: String, key: SshKey) -> Result<(), DatabaseError>;
+    async fn delete_ssh_key(
+        &self,
+        username: String,
+        fingerprint: String,
+    ) -> Result<(), DatabaseError>;
     async fn delete_user(&self, name: String) -> Result<(), DatabaseError>;
+    async fn view_policies(&self) -> Result<Vec<Policy>, DatabaseError>;
+    async fn view_policies_for_user(&self, user: &User) -> Result<Vec<Policy>, DatabaseError>;
+    async fn get_policy_from_name(&self, name: String) -> Result<Policy, DatabaseError>;
+    async fn create_policy(&self, policy: Policy) -> Result<(), DatabaseError>;
+    async fn update_policy(&self, policy: Policy) -> Result<(), DatabaseError>;
+    async fn delete_policy(&self, name: String) -> Result<(), DatabaseError>;
+    async fn explain_secret_access(&self, key: String) -> Result<SecretAccess, DatabaseError>;
+    async fn explain_user_access(&self, username: String) -> Result<UserAccess, DatabaseError>;
+    async fn simulate_access(
+        &self,
+        username: String,
+        key: String,
+        capability: Capability,
+    ) -> Result<Decision, DatabaseError>;
+    async fn view_groups(&self) -> Result<Vec<Group>, DatabaseError>;
+    async fn view_groups_for_user(&self, username: String) -> Result<Vec<Group>, DatabaseError>;
+    async fn get_group_from_name(&self, name: String) -> Result<Group, DatabaseError>;
+    async fn create_group(&self, group: Group) -> Result<(), DatabaseError>;
+    async fn update_group(&self, group: Group) -> Result<(), DatabaseError>;
+    async fn delete_group(&self, name: String) -> Result<(), DatabaseError>;
+    async fn view_roles(&self) -> Result<Vec<RoleUsage>, DatabaseError>;
+    async fn get_role_from_name(&self, name: String) -> Result<RoleUsage, DatabaseError>;
+    async fn create_role(&self, role: Role) -> Result<(), DatabaseError>;
+    async fn update_role(&self, role: Role) -> Result<(), DatabaseError>;
+    async fn delete_role(&self, name: String) -> Result<(), DatabaseError>;
 }
 
 #[derive(Clone, Debug)]
@@ -4,14 +4,60 @@ use thiserror::Error;
 pub enum DatabaseError {
     #[error("Key wasn't found")]
     KeyNotFound,
+    #[error("Secret already exists: {0}")]
+    SecretAlreadyExists(String),
     #[error("User wasn't found")]
     UserNotFound,
     #[error("User already exists")]
     UserAlreadyExists,
-    #[error("Role doesn't exist")]
-    RoleNotFound,
+    #[error("Chamber has already been initialised")]
+    AlreadyInitialised,
+    #[error("User is disabled")]
+    UserDisabled,
+    #[error("User has expired")]
+    UserExpired,
+    #[error("Invalid two-factor code")]
+    InvalidTwoFactorCode,
+    #[error("Two-factor authentication hasn't been enrolled")]
+    TwoFactorNotEnrolled,
+    #[error("Invalid SSH key: {0}")]
+    InvalidSshKey(String),
+    #[error("SSH key is already registered")]
+    SshKeyAlreadyExists,
+    #[error("SSH key wasn't found")]
+    SshKeyNotFound,
+    #[error("Challenge is invalid or has expired")]
+    InvalidChallenge,
+    #[error("Too many outstanding challenges")]
+    TooManyChallenges(std::time::Duration),
+    #[error("Role doesn't exist: {0}")]
+    RoleNotFound(String),
     #[error("Role already exists")]
     RoleAlreadyExists,
+    #[error("Role is still in use: {0}")]
+    RoleInUse(String),
+    #[error("Group wasn't found: {0}")]
+    GroupNotFound(String),
+    #[error("Group already exists")]
+    GroupAlreadyExists,
+    #[error("Policy wasn't found")]
+    PolicyNotFound,
+    #[error("Policy already exists")]
+    PolicyAlreadyExists,
+    #[error("Invalid policy: {0}")]
+    InvalidPolicy(String),
+    #[error("Invalid cursor")]
+    InvalidCursor,
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+    #[error("Namespace wasn't found: {0}")]
+    NamespaceNotFound(String),
+    #[error("Namespace already exists")]
+    NamespaceAlreadyExists,
+    #[error("Invalid namespace: {0}")]
+    InvalidNamespace(String),
+    #[error("Invalid environment: {0}")]
+    InvalidEnvironment(String),
     #[error("Forbidden")]
     Forbidden,
     #[error("UTF8 error")]
@@ -24,6 +70,10 @@ pub enum DatabaseError {
     SQLError(#[from] sqlx::Error),
     #[error("Argon2id error: {0}")]
     Argon2Error(argon2::password_hash::Error),
+    #[error("Invalid Argon2 parameters: {0}")]
+    InvalidHashConfig(String),
+    #[error("Background task failed: {0}")]
+    TaskError(#[from] tokio::task::JoinError),
     #[error("shuttle-persist error: {0}")]
     ShuttlePersist(#[from] shuttle_persist::PersistError),
     #[error("bincode error: {0}")]
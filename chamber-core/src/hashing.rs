@@ -0,0 +1,114 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+
+use crate::errors::DatabaseError;
+
+pub static ARGON2_MEMORY_VAR: &str = "CHAMBER_ARGON2_MEMORY_KIB";
+pub static ARGON2_ITERATIONS_VAR: &str = "CHAMBER_ARGON2_ITERATIONS";
+pub static ARGON2_PARALLELISM_VAR: &str = "CHAMBER_ARGON2_PARALLELISM";
+
+/// Argon2id parameters for new password hashes.
+/// Existing hashes keep working as their parameters are stored alongside them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HashConfig {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for HashConfig {
+    /// The `argon2` crate's defaults, which are one of the OWASP recommended configurations.
+    fn default() -> Self {
+        Self {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl HashConfig {
+    /// Reads the parameters from the environment, falling back to the defaults for any that aren't set.
+    pub fn from_env() -> Result<Self, DatabaseError> {
+        let default = Self::default();
+
+        let config = Self {
+            memory_kib: env_or(ARGON2_MEMORY_VAR, default.memory_kib)?,
+            iterations: env_or(ARGON2_ITERATIONS_VAR, default.iterations)?,
+            parallelism: env_or(ARGON2_PARALLELISM_VAR, default.parallelism)?,
+        };
+
+        // Catches parameters that Argon2 won't accept before the first login does
+        config.argon2()?;
+
+        Ok(config)
+    }
+
+    fn argon2(&self) -> Result<Argon2<'static>, DatabaseError> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+            .map_err(|e| DatabaseError::InvalidHashConfig(e.to_string()))?;
+
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+
+    /// Whether a stored hash was made with a different algorithm or parameters to these.
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        let Ok(parsed) = PasswordHash::new(hash) else {
+            return true;
+        };
+
+        if parsed.algorithm != Algorithm::Argon2id.ident() {
+            return true;
+        }
+
+        match Params::try_from(&parsed) {
+            Ok(params) => {
+                params.m_cost() != self.memory_kib
+                    || params.t_cost() != self.iterations
+                    || params.p_cost() != self.parallelism
+            }
+            Err(_) => true,
+        }
+    }
+
+    /// Hashes a password on the blocking thread pool, as Argon2 is deliberately slow.
+    pub async fn hash(&self, password: &str) -> Result<String, DatabaseError> {
+        let argon2 = self.argon2()?;
+        let password = password.to_owned();
+
+        tokio::task::spawn_blocking(move || {
+            let salt = SaltString::generate(&mut OsRng);
+
+            Ok(argon2
+                .hash_password(password.as_bytes(), &salt)?
+                .to_string())
+        })
+        .await?
+    }
+}
+
+/// Checks a password against a stored hash on the blocking thread pool.
+/// The parameters are taken from the hash itself, so this works for outdated hashes too.
+pub async fn verify(hash: &str, password: &str) -> Result<(), DatabaseError> {
+    let hash = hash.to_owned();
+    let password = password.to_owned();
+
+    tokio::task::spawn_blocking(move || {
+        let parsed = PasswordHash::new(&hash)?;
+        Argon2::default().verify_password(password.as_bytes(), &parsed)?;
+
+        Ok(())
+    })
+    .await?
+}
+
+fn env_or(name: &str, default: u32) -> Result<u32, DatabaseError> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| DatabaseError::InvalidHashConfig(format!("{name} should be a number"))),
+        Err(_) => Ok(default),
+    }
+}
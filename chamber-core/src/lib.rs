@@ -1,8 +1,14 @@
+pub mod challenges;
 pub mod core;
 pub mod consts;
 pub mod errors;
+pub mod hashing;
+pub mod limits;
+pub mod policy;
 pub mod postgres;
+pub mod ssh;
 
+pub mod totp;
 pub mod traits;
 pub mod users;
 
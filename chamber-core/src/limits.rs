@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Once this many keys are tracked, stale ones get dropped so that the map can't grow forever.
+const MAX_TRACKED_KEYS: usize = 10_000;
+
+/// How many failures an attempt limiter allows before locking a key out, and for how long.
+#[derive(Clone, Copy, Debug)]
+pub struct LimiterConfig {
+    /// Failures allowed within `window` before the key gets locked out.
+    pub max_failures: u32,
+    pub window: Duration,
+    /// The first lockout lasts this long; every lockout after that doubles it.
+    pub base_lockout: Duration,
+    pub max_lockout: Duration,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct AttemptRecord {
+    failures: u32,
+    window_start: Instant,
+    lockouts: u32,
+    locked_until: Option<Instant>,
+}
+
+/// Counts failed attempts per key (an IP address, a username...) and locks keys out progressively.
+/// State is kept in memory, so restarting the server clears it.
+#[derive(Clone, Debug)]
+pub struct AttemptLimiter {
+    config: LimiterConfig,
+    records: Arc<Mutex<HashMap<String, AttemptRecord>>>,
+}
+
+impl AttemptLimiter {
+    pub fn new(config: LimiterConfig) -> Self {
+        Self {
+            config,
+            records: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Returns how long the key is still locked out for, if it is.
+    pub fn check(&self, key: &str) -> Option<Duration> {
+        let records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        let locked_until = records.get(key)?.locked_until?;
+
+        locked_until.checked_duration_since(Instant::now())
+    }
+
+    /// Records a failed attempt and returns the lockout that it caused, if any.
+    pub fn record_failure(&self, key: &str) -> Option<Duration> {
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+
+        if records.len() >= MAX_TRACKED_KEYS {
+            let window = self.config.window;
+            records.retain(|_, x| {
+                x.locked_until.is_some_and(|x| x > now)
+                    || now.duration_since(x.window_start) <= window
+            });
+        }
+
+        let record = records.entry(key.to_string()).or_insert(AttemptRecord {
+            failures: 0,
+            window_start: now,
+            lockouts: 0,
+            locked_until: None,
+        });
+
+        if now.duration_since(record.window_start) > self.config.window {
+            record.failures = 0;
+            record.window_start = now;
+        }
+
+        record.failures += 1;
+
+        if record.failures < self.config.max_failures {
+            return None;
+        }
+
+        let lockout = self
+            .config
+            .base_lockout
+            .saturating_mul(2u32.saturating_pow(record.lockouts))
+            .min(self.config.max_lockout);
+
+        record.failures = 0;
+        record.window_start = now;
+        record.lockouts += 1;
+        record.locked_until = Some(now + lockout);
+
+        Some(lockout)
+    }
+
+    /// Counts an attempt that uses something up whether or not it succeeds, such as asking for a
+    /// challenge. Returns how long the key has to wait if it has already used up its attempts.
+    pub fn throttle(&self, key: &str) -> Option<Duration> {
+        let wait = self.check(key);
+
+        if wait.is_none() {
+            self.record_failure(key);
+        }
+
+        wait
+    }
+
+    /// Forgets about a key's failures, for example after a successful login.
+    pub fn reset(&self, key: &str) {
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+
+        records.remove(key);
+    }
+}
+
+/// The limiters guarding the endpoints that take credentials.
+#[derive(Clone, Debug)]
+pub struct AuthLimits {
+    /// Keyed by both client IP and username.
+    pub login: AttemptLimiter,
+    /// Keyed by client IP, since the root key isn't tied to a user.
+    pub unseal: AttemptLimiter,
+    /// Keyed by client IP, since anyone can ask for a login or unseal challenge.
+    pub challenges: AttemptLimiter,
+}
+
+impl Default for AuthLimits {
+    fn default() -> Self {
+        Self {
+            login: AttemptLimiter::new(LimiterConfig {
+                max_failures: 5,
+                window: Duration::from_secs(15 * 60),
+                base_lockout: Duration::from_secs(60),
+                max_lockout: Duration::from_secs(60 * 60),
+            }),
+            unseal: AttemptLimiter::new(LimiterConfig {
+                max_failures: 1,
+                window: Duration::from_secs(15 * 60),
+                base_lockout: Duration::from_secs(1),
+                max_lockout: Duration::from_secs(5 * 60),
+            }),
+            challenges: AttemptLimiter::new(LimiterConfig {
+                max_failures: 20,
+                window: Duration::from_secs(60),
+                base_lockout: Duration::from_secs(60),
+                max_lockout: Duration::from_secs(15 * 60),
+            }),
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use chamber_shared::environments::DEFAULT_ENVIRONMENT;
+use chamber_shared::policy::{Capability, Decision, Policy, PolicyRule};
+use chamber_shared::secrets::SecretInfo;
+
+use crate::users::User;
+
+pub static LEGACY_POLICY_NAME: &str = "access-level";
+
+/// Every capability that can be granted, in the order that they're reported in.
+pub static CAPABILITIES: [Capability; 5] = [
+    Capability::Read,
+    Capability::List,
+    Capability::Create,
+    Capability::Update,
+    Capability::Delete,
+];
+
+/// The policy that a user's access level and roles are implicitly granted.
+/// This is what the access level and role whitelist checks looked like before named policies.
+pub fn legacy_policy(user: &User) -> Policy {
+    Policy {
+        name: LEGACY_POLICY_NAME.to_string(),
+        description: "Derived from the user's access level and roles".to_string(),
+        rules: vec![
+            PolicyRule {
+                path: "*".to_string(),
+                tags: Vec::new(),
+                max_access_level: Some(user.access_level()),
+                check_whitelist: true,
+                environments: Vec::new(),
+                capabilities: vec![
+                    Capability::Read,
+                    Capability::Create,
+                    Capability::Update,
+                    Capability::Delete,
+                ],
+            },
+            PolicyRule {
+                path: "*".to_string(),
+                tags: Vec::new(),
+                max_access_level: Some(user.access_level()),
+                check_whitelist: false,
+                environments: Vec::new(),
+                capabilities: vec![Capability::List],
+            },
+        ],
+        users: vec![user.username.clone()],
+        roles: Vec::new(),
+    }
+}
+
+pub struct PolicyEvaluator {
+    user: User,
+    policies: Vec<Policy>,
+    environment: String,
+}
+
+impl PolicyEvaluator {
+    /// Creates an evaluator for a user from the named policies attached to them.
+    /// The user's legacy policy is always included.
+    pub fn new(user: User, policies: Vec<Policy>) -> Self {
+        let mut all_policies = vec![legacy_policy(&user)];
+        all_policies.extend(policies);
+
+        Self {
+            user,
+            policies: all_policies,
+            environment: DEFAULT_ENVIRONMENT.to_string(),
+        }
+    }
+
+    /// Evaluates secrets as the copies in the given environment, rather than the default one.
+    pub fn in_environment(mut self, environment: &str) -> Self {
+        self.environment = environment.to_string();
+        self
+    }
+
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    pub fn policies(&self) -> &[Policy] {
+        &self.policies
+    }
+
+    /// Evaluates a capability against a secret. Any matching rule with `deny` wins,
+    /// otherwise the first matching rule granting the capability allows it.
+    pub fn evaluate(&self, capability: Capability, secret: &SecretInfo) -> Decision {
+        let mut allowed_by: Option<(&Policy, &PolicyRule)> = None;
+
+        for policy in &self.policies {
+            for rule in &policy.rules {
+                if !self.rule_matches(rule, secret) {
+                    continue;
+                }
+
+                if rule.capabilities.contains(&Capability::Deny) {
+                    return Decision {
+                        allowed: false,
+                        policy: Some(policy.name.clone()),
+                        rule: Some(rule.clone()),
+                    };
+                }
+
+                if allowed_by.is_none() && rule.capabilities.contains(&capability) {
+                    allowed_by = Some((policy, rule));
+                }
+            }
+        }
+
+        match allowed_by {
+            Some((policy, rule)) => Decision {
+                allowed: true,
+                policy: Some(policy.name.clone()),
+                rule: Some(rule.clone()),
+            },
+            None => Decision {
+                allowed: false,
+                policy: None,
+                rule: None,
+            },
+        }
+    }
+
+    pub fn is_allowed(&self, capability: Capability, secret: &SecretInfo) -> bool {
+        self.evaluate(capability, secret).allowed
+    }
+
+    pub fn allowed_capabilities(&self, secret: &SecretInfo) -> Vec<Capability> {
+        CAPABILITIES
+            .into_iter()
+            .filter(|x| self.is_allowed(*x, secret))
+            .collect()
+    }
+
+    /// Returns the roles of the user that an allowing decision depended on:
+    /// the ones that cleared the secret's role whitelist, or the ones that the policy was attached through.
+    pub fn granting_roles(&self, decision: &Decision, secret: &SecretInfo) -> Vec<String> {
+        let (Some(policy), Some(rule)) = (&decision.policy, &decision.rule) else {
+            return Vec::new();
+        };
+
+        if !decision.allowed {
+            return Vec::new();
+        }
+
+        let mut roles: Vec<String> = Vec::new();
+
+        if rule.check_whitelist {
+            roles.extend(
+                self.user
+                    .roles()
+                    .iter()
+                    .filter(|x| secret.role_whitelist.contains(x))
+                    .cloned(),
+            );
+        }
+
+        if let Some(policy) = self.policies.iter().find(|x| &x.name == policy) {
+            if !policy.users.contains(&self.user.username) {
+                roles.extend(
+                    self.user
+                        .roles()
+                        .iter()
+                        .filter(|x| policy.roles.contains(x) && !roles.contains(x))
+                        .cloned()
+                        .collect::<Vec<String>>(),
+                );
+            }
+        }
+
+        roles
+    }
+
+    fn rule_matches(&self, rule: &PolicyRule, secret: &SecretInfo) -> bool {
+        if !glob_match(&rule.path, &secret.key) {
+            return false;
+        }
+
+        if !rule.tags.is_empty() && !rule.tags.iter().any(|x| secret.tags.contains(x)) {
+            return false;
+        }
+
+        if !rule.environments.is_empty() && !rule.environments.contains(&self.environment) {
+            return false;
+        }
+
+        if let Some(max_access_level) = rule.max_access_level {
+            if secret.access_level > max_access_level {
+                return false;
+            }
+        }
+
+        if rule.check_whitelist
+            && !secret.role_whitelist.is_empty()
+            && !secret
+                .role_whitelist
+                .iter()
+                .any(|x| self.user.roles().contains(x))
+        {
+            return false;
+        }
+
+        true
+    }
+}
+
+/// Matches a key against a glob where `*` matches any run of characters and `?` matches one.
+pub fn glob_match(pattern: &str, key: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let key: Vec<char> = key.chars().collect();
+
+    let (mut p, mut k) = (0, 0);
+    // Position of the last `*` seen in the pattern, and the key position it was tried at.
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while k < key.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == key[k]) {
+            p += 1;
+            k += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, k));
+            p += 1;
+        } else if let Some((star, star_k)) = backtrack {
+            p = star + 1;
+            k = star_k + 1;
+            backtrack = Some((star, star_k + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|x| *x == '*')
+}
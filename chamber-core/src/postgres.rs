@@ -1,39 +1,386 @@
 use crate::core::Database;
 use crate::errors::DatabaseError;
+use crate::policy::{glob_match, PolicyEvaluator};
 use chamber_crypto::secrets::{EncryptedSecret, Secret};
 use crate::users::User;
+use chamber_shared::attempts::FailedAttempt;
+use chamber_shared::audit::AuditEntry;
+use chamber_shared::environments::DEFAULT_ENVIRONMENT;
+use chamber_shared::groups::Group;
+use chamber_shared::namespaces::{check_namespace_name, Namespace, DEFAULT_NAMESPACE};
+use chamber_shared::policy::{
+    AccessGrant, Capability, Decision, Policy, PolicyRule, ReachableSecret, SecretAccess,
+    UserAccess,
+};
+use chamber_shared::roles::{Role, RoleUsage};
+use chamber_shared::secrets::{
+    check_key_path, FolderListing, MovedSecret, SecretFilter, SecretInfo, SecretPage, SecretSort,
+    SortOrder, TagMatch,
+};
+use chamber_shared::ssh::SshKey;
+use serde::{Deserialize, Serialize};
 
 use sqlx::types::BigDecimal;
 use sqlx::PgPool;
+use std::collections::BTreeSet;
 
-use chamber_crypto::secrets::SecretInfo;
-
+/// A handle to the database that only sees one namespace. Every query is scoped to it,
+/// so naming something from another namespace finds nothing. Secrets are also scoped to one of
+/// the namespace's environments.
 #[derive(Clone, Debug)]
-pub struct Postgres(pub PgPool);
+pub struct Postgres(pub PgPool, String, String);
 
 impl Postgres {
     pub fn from_pool(pool: PgPool) -> Self {
-        Self(pool)
+        Self(
+            pool,
+            DEFAULT_NAMESPACE.to_string(),
+            DEFAULT_ENVIRONMENT.to_string(),
+        )
+    }
+
+    pub async fn policy_evaluator(&self, user: User) -> Result<PolicyEvaluator, DatabaseError> {
+        // Otherwise the user would be judged by this namespace's groups and policies
+        if user.namespace() != self.1 {
+            return Err(DatabaseError::Forbidden);
+        }
+
+        let user = self.effective_user(user).await?;
+        let policies = self.view_policies_for_user(&user).await?;
+
+        Ok(PolicyEvaluator::new(user, policies).in_environment(&self.2))
+    }
+
+    /// Same as `policy_evaluator`, but also returns the names of the groups that the user inherits from.
+    async fn explain_evaluator(
+        &self,
+        user: User,
+    ) -> Result<(PolicyEvaluator, Vec<String>), DatabaseError> {
+        let groups = self.view_groups_for_user(user.username.clone()).await?;
+        let user = user.with_groups(&groups);
+        let policies = self.view_policies_for_user(&user).await?;
+
+        let groups = groups.into_iter().map(|x| x.name).collect();
+
+        let evaluator = PolicyEvaluator::new(user, policies).in_environment(&self.2);
+
+        Ok((evaluator, groups))
+    }
+
+    /// Merges the grants of every group that the user belongs to, directly or through nesting.
+    pub async fn effective_user(&self, user: User) -> Result<User, DatabaseError> {
+        let groups = self.view_groups_for_user(user.username.clone()).await?;
+
+        Ok(user.with_groups(&groups))
+    }
+
+    async fn check_users_exist(&self, usernames: &[String]) -> Result<(), DatabaseError> {
+        let missing = sqlx::query_as::<_, SingleValue>(
+            "SELECT username FROM UNNEST($1::TEXT[]) AS username
+            WHERE username NOT IN (SELECT username FROM users WHERE namespace = $2)
+            LIMIT 1",
+        )
+        .bind(usernames)
+        .bind(&self.1)
+        .fetch_optional(&self.0)
+        .await?;
+
+        match missing {
+            Some(_) => Err(DatabaseError::UserNotFound),
+            None => Ok(()),
+        }
+    }
+
+    async fn check_group(&self, group: &Group) -> Result<(), DatabaseError> {
+        if group.subgroups.contains(&group.name) {
+            return Err(DatabaseError::GroupNotFound(group.name.clone()));
+        }
+
+        let missing = sqlx::query_as::<_, SingleValue>(
+            "SELECT name FROM UNNEST($1::TEXT[]) AS name
+            WHERE name NOT IN (SELECT name FROM groups WHERE namespace = $2)
+            LIMIT 1",
+        )
+        .bind(&group.subgroups)
+        .bind(&self.1)
+        .fetch_optional(&self.0)
+        .await?;
+
+        if let Some(SingleValue(name)) = missing {
+            return Err(DatabaseError::GroupNotFound(name));
+        }
+
+        self.check_users_exist(&group.members).await?;
+        self.check_roles_exist(&group.roles).await?;
+
+        Ok(())
+    }
+
+    /// Makes sure that every given role has been created, returning the first one that hasn't.
+    pub async fn check_roles_exist(&self, roles: &[String]) -> Result<(), DatabaseError> {
+        let missing = sqlx::query_as::<_, SingleValue>(
+            "SELECT role FROM UNNEST($1::TEXT[]) AS role
+            WHERE role NOT IN (SELECT name FROM roles WHERE namespace = $2)
+            LIMIT 1",
+        )
+        .bind(roles)
+        .bind(&self.1)
+        .fetch_optional(&self.0)
+        .await?;
+
+        match missing {
+            Some(SingleValue(role)) => Err(DatabaseError::RoleNotFound(role)),
+            None => Ok(()),
+        }
+    }
+
+    async fn view_secret_info(&self, key: &str) -> Result<SecretInfo, DatabaseError> {
+        let info = sqlx::query_as::<_, SecretInfo>(
+            "SELECT key, tags, access_level, role_whitelist FROM secrets
+            WHERE namespace = $1 AND environment = $2 AND key = $3",
+        )
+        .bind(&self.1)
+        .bind(&self.2)
+        .bind(key)
+        .fetch_optional(&self.0)
+        .await?;
+
+        info.ok_or(DatabaseError::KeyNotFound)
+    }
+
+    /// Checks a write capability on an existing secret.
+    /// Secrets that the user can't read are reported as missing so that their existence isn't leaked.
+    fn authorize_write(
+        evaluator: &PolicyEvaluator,
+        capability: Capability,
+        info: &SecretInfo,
+    ) -> Result<(), DatabaseError> {
+        if !evaluator.is_allowed(Capability::Read, info) {
+            return Err(DatabaseError::KeyNotFound);
+        }
+
+        if !evaluator.is_allowed(capability, info) {
+            return Err(DatabaseError::Forbidden);
+        }
+
+        Ok(())
+    }
+
+    async fn attach_rules(&self, rows: Vec<PolicyRow>) -> Result<Vec<Policy>, DatabaseError> {
+        let names: Vec<String> = rows.iter().map(|x| x.name.clone()).collect();
+
+        let rules = sqlx::query_as::<_, PolicyRuleRow>(
+            "SELECT policy_name, path, tags, max_access_level, check_whitelist, environments,
+            capabilities
+            FROM policy_rules WHERE namespace = $1 AND policy_name = ANY($2) ORDER BY id",
+        )
+        .bind(&self.1)
+        .bind(&names)
+        .fetch_all(&self.0)
+        .await?;
+
+        let mut policies: Vec<Policy> = rows.into_iter().map(PolicyRow::into_policy).collect();
+
+        for rule in rules {
+            if let Some(policy) = policies.iter_mut().find(|x| x.name == rule.policy_name) {
+                policy.rules.push(rule.into_rule()?);
+            }
+        }
+
+        Ok(policies)
+    }
+
+    async fn insert_rules(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        policy: &Policy,
+    ) -> Result<(), DatabaseError> {
+        for rule in &policy.rules {
+            let capabilities: Vec<&str> =
+                rule.capabilities.iter().map(Capability::as_str).collect();
+
+            sqlx::query(
+                "INSERT INTO policy_rules
+                (namespace, policy_name, path, tags, max_access_level, check_whitelist,
+                environments, capabilities)
+                VALUES
+                ($1, $2, $3, $4, $5, $6, $7, $8)",
+            )
+            .bind(&self.1)
+            .bind(&policy.name)
+            .bind(&rule.path)
+            .bind(&rule.tags)
+            .bind(rule.max_access_level)
+            .bind(rule.check_whitelist)
+            .bind(&rule.environments)
+            .bind(capabilities)
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        Ok(())
     }
 }
 
 #[async_trait::async_trait]
 impl Database for Postgres {
-    async fn create_secret(&self, new_secret: EncryptedSecret) -> Result<(), DatabaseError> {
+    fn in_namespace(&self, namespace: &str) -> Self {
+        Self(self.0.clone(), namespace.to_string(), self.2.clone())
+    }
+
+    fn namespace(&self) -> &str {
+        &self.1
+    }
+
+    fn in_environment(&self, environment: &str) -> Self {
+        Self(self.0.clone(), self.1.clone(), environment.to_string())
+    }
+
+    fn environment(&self) -> &str {
+        &self.2
+    }
+
+    async fn view_namespaces(&self) -> Result<Vec<Namespace>, DatabaseError> {
+        let res = sqlx::query_as::<_, Namespace>(
+            "SELECT name, description FROM namespaces ORDER BY name",
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(res)
+    }
+
+    async fn get_namespace(&self, name: String) -> Result<Namespace, DatabaseError> {
+        let res = sqlx::query_as::<_, Namespace>(
+            "SELECT name, description FROM namespaces WHERE name = $1",
+        )
+        .bind(&name)
+        .fetch_optional(&self.0)
+        .await?;
+
+        res.ok_or(DatabaseError::NamespaceNotFound(name))
+    }
+
+    async fn create_namespace(&self, namespace: Namespace) -> Result<(), DatabaseError> {
+        check_namespace_name(&namespace.name).map_err(DatabaseError::InvalidNamespace)?;
+
+        let res = sqlx::query("INSERT INTO namespaces (name, description) VALUES ($1, $2)")
+            .bind(namespace.name)
+            .bind(namespace.description)
+            .execute(&self.0)
+            .await;
+
+        match res {
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                Err(DatabaseError::NamespaceAlreadyExists)
+            }
+            Err(e) => Err(DatabaseError::SQLError(e)),
+        }
+    }
+
+    async fn delete_namespace(&self, name: String) -> Result<(), DatabaseError> {
+        if name == DEFAULT_NAMESPACE {
+            return Err(DatabaseError::InvalidNamespace(
+                "The default namespace can't be deleted".to_string(),
+            ));
+        }
+
+        // Everything in the namespace goes with it. Its data key is kept with the keyfile, so
+        // that's up to the caller.
+        let res = sqlx::query("DELETE FROM namespaces WHERE name = $1")
+            .bind(&name)
+            .execute(&self.0)
+            .await?;
+
+        if res.rows_affected() == 0 {
+            return Err(DatabaseError::NamespaceNotFound(name));
+        }
+
+        Ok(())
+    }
+
+    async fn has_data_key(&self) -> Result<bool, DatabaseError> {
+        let res =
+            sqlx::query_scalar::<_, bool>("SELECT has_data_key FROM namespaces WHERE name = $1")
+                .bind(&self.1)
+                .fetch_optional(&self.0)
+                .await?;
+
+        res.ok_or_else(|| DatabaseError::NamespaceNotFound(self.1.clone()))
+    }
+
+    async fn seal_with_data_key(
+        &self,
+        secrets: Vec<EncryptedSecret>,
+    ) -> Result<bool, DatabaseError> {
+        let mut tx = self.0.begin().await?;
+
+        let res = sqlx::query(
+            "UPDATE namespaces SET has_data_key = true WHERE name = $1 AND NOT has_data_key",
+        )
+        .bind(&self.1)
+        .execute(&mut *tx)
+        .await?;
+
+        if res.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+
+        for secret in secrets {
+            sqlx::query(
+                "UPDATE secrets SET ciphertext = $1
+                WHERE namespace = $2 AND environment = $3 AND key = $4",
+            )
+            .bind(secret.ciphertext())
+            .bind(&self.1)
+            .bind(&self.2)
+            .bind(secret.key())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    async fn create_secret(
+        &self,
+        user: User,
+        new_secret: EncryptedSecret,
+    ) -> Result<(), DatabaseError> {
+        check_key_path(new_secret.key()).map_err(DatabaseError::InvalidKey)?;
+
+        let evaluator = self.policy_evaluator(user).await?;
+
+        // Users can't create secrets that they wouldn't be able to read themselves.
+        let info = SecretInfo::from(&new_secret);
+        if !evaluator.is_allowed(Capability::Create, &info)
+            || !evaluator.is_allowed(Capability::Read, &info)
+        {
+            return Err(DatabaseError::Forbidden);
+        }
+
+        self.check_roles_exist(&new_secret.role_whitelist).await?;
+
         // you might need to convert to Vec<u8> here for the Nonce
         sqlx::query(
             "INSERT INTO SECRETS 
-                    (key, nonce, sig, ciphertext, tags, access_level, role_whitelist)
+                    (key, nonce, sig, ciphertext, tags, access_level, role_whitelist, namespace, environment)
                     VALUES
-                    ($1, $2, $3, $4, $5, $6, $7)",
+                    ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
         )
         .bind(new_secret.key())
-        .bind(BigDecimal::from(new_secret.nonce.0 - 1))
+        .bind(BigDecimal::from(new_secret.nonce.0))
         .bind(new_secret.sig.inner())
         .bind(new_secret.ciphertext())
         .bind(new_secret.tags())
         .bind(new_secret.access_level())
         .bind(new_secret.role_whitelist())
+        .bind(&self.1)
+        .bind(&self.2)
         .execute(&self.0)
         .await?;
 
@@ -43,10 +390,12 @@ impl Database for Postgres {
     async fn view_all_secrets_admin(&self) -> Result<Vec<EncryptedSecret>, DatabaseError> {
         let retrieved_keys = sqlx::query_as::<_, EncryptedSecret>(
             "SELECT 
-            key, nonce, ciphertext, tags, access_level, role_whitelist
-            FROM secrets
+            key, nonce, sig, ciphertext, tags, access_level, role_whitelist
+            FROM secrets WHERE namespace = $1 AND environment = $2
                 ",
         )
+        .bind(&self.1)
+        .bind(&self.2)
         .fetch_all(&self.0)
         .await?;
 
@@ -56,68 +405,308 @@ impl Database for Postgres {
     async fn view_all_secrets(
         &self,
         user: User,
-        tag: Option<String>,
-    ) -> Result<Vec<SecretInfo>, DatabaseError> {
-        let retrieved_keys = sqlx::query_as::<_, SecretInfo>(
-            "SELECT 
-            key, tags FROM secrets WHERE (
-                    case when $1 is not null 
-                    then $1 = ANY(tags)
-                    else 1=1 
-                    end)
-                    AND $2 >= access_level
-                ",
-        )
-        .bind(tag)
-        .bind(user.access_level())
-        .fetch_all(&self.0)
-        .await?;
+        filter: SecretFilter,
+    ) -> Result<SecretPage, DatabaseError> {
+        let evaluator = self.policy_evaluator(user).await?;
 
-        Ok(retrieved_keys)
+        let mut cursor = filter
+            .cursor
+            .as_deref()
+            .map(ListingCursor::decode)
+            .transpose()?;
+
+        // Globs are narrowed down to their literal prefix in SQL, then matched here
+        let glob = filter.key.as_deref().filter(|x| x.contains(['*', '?']));
+        let prefix = filter
+            .key
+            .as_deref()
+            .map(|x| x.split(['*', '?']).next().unwrap_or_default())
+            .unwrap_or_default();
+
+        let like = format!("{}%", escape_like(prefix));
+
+        let (order, after) = match filter.order {
+            SortOrder::Asc => ("ASC", ">"),
+            SortOrder::Desc => ("DESC", "<"),
+        };
+
+        let (order_by, after) = match filter.sort {
+            SecretSort::Key => (format!("key {order}"), format!("key {after} $4")),
+            SecretSort::Created => (
+                format!("created_at {order}, key {order}"),
+                format!("(created_at, key) {after} ($5, $4)"),
+            ),
+        };
+
+        let query = format!(
+            "SELECT key, tags, access_level, role_whitelist, created_at FROM secrets
+            WHERE namespace = $7 AND environment = $8
+            AND (cardinality($1::TEXT[]) = 0 OR (
+                case when $2
+                then tags && $1
+                else tags @> $1
+                end))
+            AND key LIKE $3
+            AND ($4::VARCHAR IS NULL OR {after})
+            ORDER BY {order_by}
+            LIMIT $6"
+        );
+
+        // Rows that the user can't list are dropped after they're read, so a page can take
+        // several batches to fill
+        let batch = filter.limit.map(|x| x.max(100));
+        let mut secrets = Vec::new();
+
+        loop {
+            let rows = sqlx::query_as::<_, SecretListingRow>(&query)
+                .bind(&filter.tags)
+                .bind(filter.tag_match == TagMatch::Any)
+                .bind(&like)
+                .bind(cursor.as_ref().map(|x| x.key.clone()))
+                .bind(cursor.as_ref().map(|x| x.created_at))
+                .bind(batch)
+                .bind(&self.1)
+                .bind(&self.2)
+                .fetch_all(&self.0)
+                .await?;
+
+            let exhausted = batch.is_none_or(|x| (rows.len() as i64) < x);
+
+            for row in rows {
+                cursor = Some(ListingCursor {
+                    key: row.key.clone(),
+                    created_at: row.created_at,
+                });
+
+                let info = row.into_info();
+
+                if glob.is_some_and(|x| !glob_match(x, &info.key))
+                    || !evaluator.is_allowed(Capability::List, &info)
+                {
+                    continue;
+                }
+
+                secrets.push(info);
+
+                if filter.limit == Some(secrets.len() as i64) {
+                    return Ok(SecretPage {
+                        secrets,
+                        next_cursor: cursor.map(|x| x.encode()),
+                    });
+                }
+            }
+
+            if exhausted {
+                return Ok(SecretPage {
+                    secrets,
+                    next_cursor: None,
+                });
+            }
+        }
     }
 
-    async fn update_secret(
+    async fn view_folder(
         &self,
-        key: String,
-        secret: EncryptedSecret,
-    ) -> Result<(), DatabaseError> {
-        // Might need to convert back from Vec<u8> to Nonce<U12>
-        sqlx::query("UPDATE secrets SET tags = $1 WHERE key = $2")
-            .bind(secret.tags())
-            .bind(key)
-            .execute(&self.0)
-            .await?;
+        user: User,
+        prefix: String,
+    ) -> Result<FolderListing, DatabaseError> {
+        let prefix = prefix.trim_matches('/').to_string();
+        let path = match prefix.is_empty() {
+            true => String::new(),
+            false => format!("{prefix}/"),
+        };
 
-        Ok(())
+        let filter = SecretFilter {
+            key: Some(path.clone()),
+            ..Default::default()
+        };
+
+        let page = self.view_all_secrets(user, filter).await?;
+
+        let mut folders = BTreeSet::new();
+        let mut secrets = Vec::new();
+
+        for secret in page.secrets {
+            // Prefixes with `*` or `?` in them are matched as globs, so they're checked again here
+            let Some(rest) = secret.key.strip_prefix(&path) else {
+                continue;
+            };
+
+            match rest.split_once('/') {
+                Some((folder, _)) => {
+                    folders.insert(format!("{path}{folder}"));
+                }
+                None => secrets.push(secret),
+            }
+        }
+
+        Ok(FolderListing {
+            prefix,
+            folders: folders.into_iter().collect(),
+            secrets,
+        })
     }
 
-    async fn rekey_all_secrets(&self, secrets: Vec<EncryptedSecret>) -> Result<(), DatabaseError> {
-        let transaction = self.0.begin().await?;
+    async fn move_secrets(
+        &self,
+        user: User,
+        from: String,
+        to: String,
+    ) -> Result<Vec<MovedSecret>, DatabaseError> {
+        let from = from.trim_end_matches('/');
+        let to = to.trim_end_matches('/');
+
+        if from.is_empty() {
+            return Err(DatabaseError::InvalidKey(
+                "Give the secret or folder to move".to_string(),
+            ));
+        }
 
-        for secret in secrets {
-            if let Err(e) = sqlx::query("UPDATE secrets SET ciphertext = $1 WHERE key = $2")
-                .bind(secret.ciphertext())
-                .bind(secret.key())
-                .execute(&self.0)
-                .await
+        check_key_path(to).map_err(DatabaseError::InvalidKey)?;
+
+        let within = |path: &str, folder: &str| path.starts_with(&format!("{folder}/"));
+
+        if from == to || within(to, from) || within(from, to) {
+            return Err(DatabaseError::InvalidKey(format!(
+                "{from} can't be moved into itself or one of its parents"
+            )));
+        }
+
+        let mut tx = self.0.begin().await?;
+
+        // Locked so that they can't be changed or moved by someone else part of the way through
+        let rows = sqlx::query_as::<_, SecretInfo>(
+            "SELECT key, tags, access_level, role_whitelist FROM secrets
+            WHERE namespace = $1 AND environment = $2 AND (key = $3 OR key LIKE $4)
+            FOR UPDATE",
+        )
+        .bind(&self.1)
+        .bind(&self.2)
+        .bind(from)
+        .bind(format!("{}/%", escape_like(from)))
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let evaluator = self.policy_evaluator(user).await?;
+
+        // Like listings, secrets that can't be read are left out, so that a move doesn't give
+        // away what else is in a folder
+        let rows: Vec<SecretInfo> = rows
+            .into_iter()
+            .filter(|x| evaluator.is_allowed(Capability::Read, x))
+            .collect();
+
+        if rows.is_empty() {
+            return Err(DatabaseError::KeyNotFound);
+        }
+
+        let mut moves = Vec::new();
+
+        for info in rows {
+            let moved = SecretInfo {
+                key: format!("{to}{}", &info.key[from.len()..]),
+                ..info.clone()
+            };
+
+            // Moving is a delete and a create, and a folder is only moved if all of it can be
+            if !evaluator.is_allowed(Capability::Delete, &info)
+                || !evaluator.is_allowed(Capability::Create, &moved)
+                || !evaluator.is_allowed(Capability::Read, &moved)
             {
-                transaction.rollback().await?;
-                return Err(DatabaseError::SQLError(e));
+                return Err(DatabaseError::Forbidden);
             }
+
+            moves.push(MovedSecret {
+                from: info.key,
+                to: moved.key,
+            });
+        }
+
+        let (old_keys, new_keys): (Vec<String>, Vec<String>) =
+            moves.iter().map(|x| (x.from.clone(), x.to.clone())).unzip();
+
+        let existing = sqlx::query_scalar::<_, String>(
+            "SELECT key FROM secrets
+            WHERE namespace = $1 AND environment = $2 AND key = ANY($3)
+            ORDER BY key LIMIT 1",
+        )
+        .bind(&self.1)
+        .bind(&self.2)
+        .bind(&new_keys)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(key) = existing {
+            return Err(DatabaseError::SecretAlreadyExists(key));
         }
 
-        transaction.commit().await?;
+        let res = sqlx::query(
+            "UPDATE secrets SET key = moves.new_key
+            FROM UNNEST($1::TEXT[], $2::TEXT[]) AS moves(old_key, new_key)
+            WHERE secrets.namespace = $3 AND secrets.environment = $4
+            AND secrets.key = moves.old_key",
+        )
+        .bind(&old_keys)
+        .bind(&new_keys)
+        .bind(&self.1)
+        .bind(&self.2)
+        .execute(&mut *tx)
+        .await;
+
+        // A secret can still be created at the destination after it was checked
+        match res {
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                return Err(DatabaseError::SecretAlreadyExists(to.to_string()));
+            }
+            res => res?,
+        };
+
+        tx.commit().await?;
+
+        Ok(moves)
+    }
+
+    async fn update_secret(
+        &self,
+        user: User,
+        key: String,
+        secret: EncryptedSecret,
+    ) -> Result<(), DatabaseError> {
+        let info = self.view_secret_info(&key).await?;
+        let evaluator = self.policy_evaluator(user).await?;
+        Self::authorize_write(&evaluator, Capability::Update, &info)?;
+
+        // Might need to convert back from Vec<u8> to Nonce<U12>
+        sqlx::query(
+            "UPDATE secrets SET tags = $1 WHERE namespace = $2 AND environment = $3 AND key = $4",
+        )
+        .bind(secret.tags())
+        .bind(&self.1)
+        .bind(&self.2)
+        .bind(key)
+        .execute(&self.0)
+        .await?;
+
         Ok(())
     }
 
     async fn view_secret(&self, user: User, key: String) -> Result<EncryptedSecret, DatabaseError> {
-        let retrieved_key = sqlx::query_as!(EncryptedSecret,
-            "SELECT key, nonce, sig, ciphertext, tags, access_level, role_whitelist FROM secrets WHERE key = $1 AND $2 >= access_level",
-                key,
-                user.access_level()
+        let retrieved_key = sqlx::query_as::<_, EncryptedSecret>(
+            "SELECT key, nonce, sig, ciphertext, tags, access_level, role_whitelist FROM secrets
+            WHERE namespace = $1 AND environment = $2 AND key = $3",
         )
-        .fetch_one(&self.0)
-        .await?;
+        .bind(&self.1)
+        .bind(&self.2)
+        .bind(key)
+        .fetch_optional(&self.0)
+        .await?
+        .ok_or(DatabaseError::KeyNotFound)?;
+
+        let evaluator = self.policy_evaluator(user).await?;
+
+        if !evaluator.is_allowed(Capability::Read, &SecretInfo::from(&retrieved_key)) {
+            return Err(DatabaseError::KeyNotFound);
+        }
 
         Ok(retrieved_key)
     }
@@ -127,20 +716,20 @@ impl Database for Postgres {
         user: User,
         key: String,
     ) -> Result<Secret, DatabaseError> {
+        let info = self.view_secret_info(&key).await?;
+        let evaluator = self.policy_evaluator(user).await?;
+
+        if !evaluator.is_allowed(Capability::Read, &info) {
+            return Err(DatabaseError::KeyNotFound);
+        }
+
         let retrieved_key = sqlx::query_as::<_, Secret>(
-            "SELECT key, nonce, ciphertext, sig FROM secrets WHERE
-            key = $1 
-            AND $2 >= access_level 
-            AND ( CASE 
-            WHEN ARRAY_LENGTH(role_whitelist, 1) > 0 
-            then role_whitelist && $3
-            else 1=1 end
-            )
-            ",
+            "SELECT key, nonce, ciphertext, sig FROM secrets
+            WHERE namespace = $1 AND environment = $2 AND key = $3",
         )
+        .bind(&self.1)
+        .bind(&self.2)
         .bind(key)
-        .bind(user.access_level())
-        .bind(user.roles())
         .fetch_one(&self.0)
         .await?;
 
@@ -152,46 +741,157 @@ impl Database for Postgres {
         user: User,
         key: String,
     ) -> Result<Vec<Secret>, DatabaseError> {
-        let retrieved_key = sqlx::query_as::<_, Secret>(
-            "SELECT key, nonce, ciphertext, sig FROM secrets WHERE
-            $1 = ANY(tags)
-            AND $2 >= access_level
-            AND ( CASE
-            WHEN ARRAY_LENGTH(role_whitelist, 1) > 0
-            then role_whitelist && $3
-            else 1=1 end
-            )
-            ",
+        let infos = sqlx::query_as::<_, SecretInfo>(
+            "SELECT key, tags, access_level, role_whitelist FROM secrets
+            WHERE namespace = $1 AND environment = $2 AND $3 = ANY(tags)",
         )
+        .bind(&self.1)
+        .bind(&self.2)
         .bind(key)
-        .bind(user.access_level())
-        .bind(user.roles())
+        .fetch_all(&self.0)
+        .await?;
+
+        let evaluator = self.policy_evaluator(user).await?;
+
+        let keys: Vec<String> = infos
+            .into_iter()
+            .filter(|x| evaluator.is_allowed(Capability::Read, x))
+            .map(|x| x.key)
+            .collect();
+
+        let retrieved_key = sqlx::query_as::<_, Secret>(
+            "SELECT key, nonce, ciphertext, sig FROM secrets
+            WHERE namespace = $1 AND environment = $2 AND key = ANY($3)",
+        )
+        .bind(&self.1)
+        .bind(&self.2)
+        .bind(keys)
         .fetch_all(&self.0)
         .await?;
 
         Ok(retrieved_key)
     }
 
-    async fn delete_secret(&self, key: String) -> Result<(), DatabaseError> {
-        sqlx::query("DELETE FROM secrets WHERE key = $1")
+    async fn delete_secret(&self, user: User, key: String) -> Result<(), DatabaseError> {
+        let info = self.view_secret_info(&key).await?;
+        let evaluator = self.policy_evaluator(user).await?;
+        Self::authorize_write(&evaluator, Capability::Delete, &info)?;
+
+        sqlx::query("DELETE FROM secrets WHERE namespace = $1 AND environment = $2 AND key = $3")
+            .bind(&self.1)
+            .bind(&self.2)
             .bind(key)
             .execute(&self.0)
             .await?;
 
         Ok(())
     }
-    async fn view_users(&self) -> Result<Vec<User>, DatabaseError> {
-        let query = sqlx::query_as::<_, User>("SELECT username, role FROM USERS")
-            .fetch_all(&self.0)
-            .await?;
 
-        Ok(query)
-    }
+    async fn promote_secret(
+        &self,
+        user: User,
+        from: String,
+        secret: EncryptedSecret,
+    ) -> Result<(), DatabaseError> {
+        if from == self.2 {
+            return Err(DatabaseError::InvalidEnvironment(format!(
+                "{from} can't be promoted to itself"
+            )));
+        }
+
+        let username = user.username.clone();
+        let evaluator = self.policy_evaluator(user).await?;
+
+        // Promoting over a secret updates it, otherwise it's created like any other secret
+        match self.view_secret_info(secret.key()).await {
+            Ok(info) => Self::authorize_write(&evaluator, Capability::Update, &info)?,
+            Err(DatabaseError::KeyNotFound) => {
+                let info = SecretInfo::from(&secret);
+                if !evaluator.is_allowed(Capability::Create, &info)
+                    || !evaluator.is_allowed(Capability::Read, &info)
+                {
+                    return Err(DatabaseError::Forbidden);
+                }
+            }
+            Err(e) => return Err(e),
+        }
+
+        let mut tx = self.0.begin().await?;
+
+        sqlx::query(
+            "INSERT INTO secrets
+            (key, nonce, sig, ciphertext, tags, access_level, role_whitelist, namespace, environment)
+            VALUES
+            ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ON CONFLICT (namespace, environment, key) DO UPDATE SET
+            nonce = EXCLUDED.nonce,
+            sig = EXCLUDED.sig,
+            ciphertext = EXCLUDED.ciphertext",
+        )
+        .bind(secret.key())
+        .bind(BigDecimal::from(secret.nonce.0))
+        .bind(secret.sig.inner())
+        .bind(secret.ciphertext())
+        .bind(secret.tags())
+        .bind(secret.access_level())
+        .bind(secret.role_whitelist())
+        .bind(&self.1)
+        .bind(&self.2)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO audit_log
+            (namespace, action, username, key, from_environment, environment)
+            VALUES
+            ($1, 'promote', $2, $3, $4, $5)",
+        )
+        .bind(&self.1)
+        .bind(username)
+        .bind(secret.key())
+        .bind(from)
+        .bind(&self.2)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn view_audit_log(&self, limit: i64) -> Result<Vec<AuditEntry>, DatabaseError> {
+        let res = sqlx::query_as::<_, AuditEntryRow>(
+            "SELECT action, username, key, from_environment, environment, created_at
+            FROM audit_log WHERE namespace = $1
+            ORDER BY created_at DESC, id DESC LIMIT $2",
+        )
+        .bind(&self.1)
+        .bind(limit)
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(res.into_iter().map(AuditEntryRow::into_entry).collect())
+    }
+    async fn view_users(&self) -> Result<Vec<User>, DatabaseError> {
+        let query = sqlx::query_as::<_, User>(
+            "SELECT namespace, username, password, access_level, roles, namespace_admin, disabled,
+            expires_at, created_at, totp_secret, totp_pending_secret, totp_last_step, recovery_codes
+            FROM users WHERE namespace = $1 ORDER BY username",
+        )
+        .bind(&self.1)
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(query)
+    }
 
     async fn get_user_from_name(&self, username: String) -> Result<User, DatabaseError> {
         let query = sqlx::query_as::<_, User>(
-            "SELECT username, password, access_level, roles FROM USERS WHERE USERNAME = $1",
+            "SELECT namespace, username, password, access_level, roles, namespace_admin, disabled,
+            expires_at, created_at, totp_secret, totp_pending_secret, totp_last_step, recovery_codes
+            FROM users WHERE namespace = $1 AND username = $2",
         )
+        .bind(&self.1)
         .bind(username)
         .fetch_one(&self.0)
         .await?;
@@ -201,8 +901,11 @@ impl Database for Postgres {
 
     async fn get_user_from_password(&self, password: String) -> Result<User, DatabaseError> {
         let query = sqlx::query_as::<_, User>(
-            "SELECT username, password, access_level, roles FROM users WHERE PASSWORD = $1",
+            "SELECT namespace, username, password, access_level, roles, namespace_admin, disabled,
+            expires_at, created_at, totp_secret, totp_pending_secret, totp_last_step, recovery_codes
+            FROM users WHERE namespace = $1 AND password = $2",
         )
+        .bind(&self.1)
         .bind(password)
         .fetch_one(&self.0)
         .await?;
@@ -213,29 +916,159 @@ impl Database for Postgres {
     async fn create_user(&self, user: User) -> Result<String, DatabaseError> {
         let query = sqlx::query_as::<_, SingleValue>(
             "INSERT INTO users
-            (username, password)
+            (namespace, username, password, namespace_admin)
             VALUES
-            ($1, $2) RETURNING PASSWORD",
+            ($1, $2, $3, $4) RETURNING PASSWORD",
         )
-        .bind(user.username)
-        .bind(user.password)
+        .bind(&self.1)
+        .bind(&user.username)
+        .bind(&user.password)
+        .bind(user.is_namespace_admin())
         .fetch_one(&self.0)
-        .await?;
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.is_unique_violation() => DatabaseError::UserAlreadyExists,
+            e => e.into(),
+        })?;
 
         Ok(query.0)
     }
 
+    async fn bootstrap_user(&self, user: User) -> Result<(), DatabaseError> {
+        // Only insert if there are no users yet, so that two bootstraps can't both succeed
+        let res = sqlx::query(
+            "INSERT INTO users
+            (namespace, username, password, access_level, namespace_admin)
+            SELECT $1, $2, $3, $4, $5
+            WHERE NOT EXISTS (SELECT 1 FROM users WHERE namespace = $1)",
+        )
+        .bind(&self.1)
+        .bind(&user.username)
+        .bind(&user.password)
+        .bind(user.access_level())
+        .bind(user.is_namespace_admin())
+        .execute(&self.0)
+        .await?;
+
+        if res.rows_affected() == 0 {
+            return Err(DatabaseError::AlreadyInitialised);
+        }
+
+        Ok(())
+    }
+
+    async fn record_failed_attempt(&self, attempt: FailedAttempt) -> Result<(), DatabaseError> {
+        sqlx::query(
+            "INSERT INTO failed_attempts
+            (kind, username, ip, reason, created_at)
+            VALUES
+            ($1, $2, $3, $4, $5)",
+        )
+        .bind(attempt.kind)
+        .bind(attempt.username)
+        .bind(attempt.ip)
+        .bind(attempt.reason)
+        .bind(attempt.created_at)
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn view_failed_attempts(&self, limit: i64) -> Result<Vec<FailedAttempt>, DatabaseError> {
+        let res = sqlx::query_as::<_, FailedAttemptRow>(
+            "SELECT kind, username, ip, reason, created_at FROM failed_attempts
+            ORDER BY created_at DESC LIMIT $1",
+        )
+        .bind(limit)
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(res
+            .into_iter()
+            .map(FailedAttemptRow::into_attempt)
+            .collect())
+    }
+
+    async fn view_ssh_keys(&self, username: String) -> Result<Vec<SshKey>, DatabaseError> {
+        let res = sqlx::query_as::<_, SshKeyRow>(
+            "SELECT fingerprint, public_key, comment, created_at FROM ssh_keys
+            WHERE namespace = $1 AND username = $2 ORDER BY created_at",
+        )
+        .bind(&self.1)
+        .bind(username)
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(res.into_iter().map(SshKeyRow::into_key).collect())
+    }
+
+    async fn add_ssh_key(&self, username: String, key: SshKey) -> Result<(), DatabaseError> {
+        let res = sqlx::query(
+            "INSERT INTO ssh_keys
+            (namespace, fingerprint, username, public_key, comment, created_at)
+            VALUES
+            ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(&self.1)
+        .bind(key.fingerprint)
+        .bind(username)
+        .bind(key.public_key)
+        .bind(key.comment)
+        .bind(key.created_at)
+        .execute(&self.0)
+        .await;
+
+        match res {
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                Err(DatabaseError::SshKeyAlreadyExists)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete_ssh_key(
+        &self,
+        username: String,
+        fingerprint: String,
+    ) -> Result<(), DatabaseError> {
+        let res = sqlx::query(
+            "DELETE FROM ssh_keys WHERE namespace = $1 AND username = $2 AND fingerprint = $3",
+        )
+        .bind(&self.1)
+        .bind(username)
+        .bind(fingerprint)
+        .execute(&self.0)
+        .await?;
+
+        if res.rows_affected() == 0 {
+            return Err(DatabaseError::SshKeyNotFound);
+        }
+
+        Ok(())
+    }
+
     async fn update_user(&self, user: User) -> Result<(), DatabaseError> {
+        self.check_roles_exist(user.roles()).await?;
+
         sqlx::query(
             "
             UPDATE users SET
             access_level = $1,
-            roles = $2
-            where username = $3
+            roles = $2,
+            disabled = $3,
+            expires_at = $4,
+            namespace_admin = $5
+            where namespace = $6 AND username = $7
             ",
         )
         .bind(user.access_level())
         .bind(user.clone().roles())
+        .bind(user.is_disabled())
+        .bind(user.expires_at())
+        .bind(user.is_namespace_admin())
+        .bind(&self.1)
         .bind(user.username)
         .execute(&self.0)
         .await?;
@@ -243,15 +1076,739 @@ impl Database for Postgres {
         Ok(())
     }
 
+    async fn update_user_password(&self, user: User) -> Result<(), DatabaseError> {
+        sqlx::query("UPDATE users SET password = $1 WHERE namespace = $2 AND username = $3")
+            .bind(user.password)
+            .bind(&self.1)
+            .bind(user.username)
+            .execute(&self.0)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_user_two_factor(&self, user: User) -> Result<(), DatabaseError> {
+        sqlx::query(
+            "UPDATE users SET
+            totp_secret = $1,
+            totp_pending_secret = $2,
+            totp_last_step = $3,
+            recovery_codes = $4
+            WHERE namespace = $5 AND username = $6",
+        )
+        .bind(user.totp_secret)
+        .bind(user.totp_pending_secret)
+        .bind(user.totp_last_step)
+        .bind(user.recovery_codes)
+        .bind(&self.1)
+        .bind(user.username)
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn view_two_factor_level(&self) -> Result<Option<i32>, DatabaseError> {
+        let res = sqlx::query_as::<_, SingleValue>(
+            "SELECT value FROM settings WHERE name = 'two_factor_required_above'",
+        )
+        .fetch_optional(&self.0)
+        .await?;
+
+        Ok(res.and_then(|x| x.0.parse().ok()))
+    }
+
+    async fn set_two_factor_level(&self, level: Option<i32>) -> Result<(), DatabaseError> {
+        match level {
+            Some(level) => {
+                sqlx::query(
+                    "INSERT INTO settings (name, value) VALUES ('two_factor_required_above', $1)
+                    ON CONFLICT (name) DO UPDATE SET value = EXCLUDED.value",
+                )
+                .bind(level.to_string())
+                .execute(&self.0)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM settings WHERE name = 'two_factor_required_above'")
+                    .execute(&self.0)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
     async fn delete_user(&self, name: String) -> Result<(), DatabaseError> {
-        sqlx::query("DELETE FROM USERS WHERE USERNAME = $1")
+        let mut tx = self.0.begin().await?;
+
+        sqlx::query("DELETE FROM USERS WHERE NAMESPACE = $1 AND USERNAME = $2")
+            .bind(&self.1)
+            .bind(&name)
+            .execute(&mut *tx)
+            .await?;
+
+        // Otherwise a new user with the same name would inherit the old user's groups.
+        sqlx::query("UPDATE groups SET members = ARRAY_REMOVE(members, $1) WHERE namespace = $2")
+            .bind(&name)
+            .bind(&self.1)
+            .execute(&mut *tx)
+            .await?;
+
+        // ...or be able to log in with their SSH keys
+        sqlx::query("DELETE FROM ssh_keys WHERE namespace = $1 AND username = $2")
+            .bind(&self.1)
+            .bind(&name)
+            .execute(&mut *tx)
+            .await?;
+
+        // ...or the policies that named the old user
+        sqlx::query("UPDATE policies SET users = ARRAY_REMOVE(users, $1) WHERE namespace = $2")
+            .bind(&name)
+            .bind(&self.1)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn view_groups(&self) -> Result<Vec<Group>, DatabaseError> {
+        let rows = sqlx::query_as::<_, GroupRow>(
+            "SELECT name, description, members, subgroups, roles, access_level
+            FROM groups WHERE namespace = $1 ORDER BY name",
+        )
+        .bind(&self.1)
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(rows.into_iter().map(GroupRow::into_group).collect())
+    }
+
+    async fn view_groups_for_user(&self, username: String) -> Result<Vec<Group>, DatabaseError> {
+        // UNION rather than UNION ALL so that cyclic nesting terminates.
+        let rows = sqlx::query_as::<_, GroupRow>(
+            "WITH RECURSIVE member_of(name) AS (
+                SELECT name FROM groups WHERE namespace = $1 AND $2 = ANY(members)
+                UNION
+                SELECT g.name FROM groups g JOIN member_of m ON m.name = ANY(g.subgroups)
+                WHERE g.namespace = $1
+            )
+            SELECT name, description, members, subgroups, roles, access_level
+            FROM groups WHERE namespace = $1 AND name IN (SELECT name FROM member_of)
+            ORDER BY name",
+        )
+        .bind(&self.1)
+        .bind(username)
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(rows.into_iter().map(GroupRow::into_group).collect())
+    }
+
+    async fn get_group_from_name(&self, name: String) -> Result<Group, DatabaseError> {
+        let row = sqlx::query_as::<_, GroupRow>(
+            "SELECT name, description, members, subgroups, roles, access_level
+            FROM groups WHERE namespace = $1 AND name = $2",
+        )
+        .bind(&self.1)
+        .bind(&name)
+        .fetch_optional(&self.0)
+        .await?
+        .ok_or(DatabaseError::GroupNotFound(name))?;
+
+        Ok(row.into_group())
+    }
+
+    async fn create_group(&self, group: Group) -> Result<(), DatabaseError> {
+        self.check_group(&group).await?;
+
+        let res = sqlx::query(
+            "INSERT INTO groups
+            (name, description, members, subgroups, roles, access_level, namespace)
+            VALUES
+            ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(group.name)
+        .bind(group.description)
+        .bind(group.members)
+        .bind(group.subgroups)
+        .bind(group.roles)
+        .bind(group.access_level)
+        .bind(&self.1)
+        .execute(&self.0)
+        .await;
+
+        match res {
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                Err(DatabaseError::GroupAlreadyExists)
+            }
+            Err(e) => Err(DatabaseError::SQLError(e)),
+        }
+    }
+
+    async fn update_group(&self, group: Group) -> Result<(), DatabaseError> {
+        self.check_group(&group).await?;
+
+        let res = sqlx::query(
+            "UPDATE groups SET
+            description = $1,
+            members = $2,
+            subgroups = $3,
+            roles = $4,
+            access_level = $5
+            WHERE namespace = $6 AND name = $7",
+        )
+        .bind(group.description)
+        .bind(group.members)
+        .bind(group.subgroups)
+        .bind(group.roles)
+        .bind(group.access_level)
+        .bind(&self.1)
+        .bind(&group.name)
+        .execute(&self.0)
+        .await?;
+
+        if res.rows_affected() == 0 {
+            return Err(DatabaseError::GroupNotFound(group.name));
+        }
+
+        Ok(())
+    }
+
+    async fn delete_group(&self, name: String) -> Result<(), DatabaseError> {
+        let mut tx = self.0.begin().await?;
+
+        let res = sqlx::query("DELETE FROM groups WHERE namespace = $1 AND name = $2")
+            .bind(&self.1)
+            .bind(&name)
+            .execute(&mut *tx)
+            .await?;
+
+        if res.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Err(DatabaseError::GroupNotFound(name));
+        }
+
+        sqlx::query("UPDATE groups SET subgroups = ARRAY_REMOVE(subgroups, $1) WHERE namespace = $2")
+            .bind(&name)
+            .bind(&self.1)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn view_policies(&self) -> Result<Vec<Policy>, DatabaseError> {
+        let rows = sqlx::query_as::<_, PolicyRow>(
+            "SELECT name, description, users, roles FROM policies WHERE namespace = $1 ORDER BY name",
+        )
+        .bind(&self.1)
+        .fetch_all(&self.0)
+        .await?;
+
+        self.attach_rules(rows).await
+    }
+
+    async fn view_policies_for_user(&self, user: &User) -> Result<Vec<Policy>, DatabaseError> {
+        let rows = sqlx::query_as::<_, PolicyRow>(
+            "SELECT name, description, users, roles FROM policies
+            WHERE namespace = $1 AND ($2 = ANY(users) OR roles && $3)
+            ORDER BY name",
+        )
+        .bind(&self.1)
+        .bind(&user.username)
+        .bind(user.roles())
+        .fetch_all(&self.0)
+        .await?;
+
+        self.attach_rules(rows).await
+    }
+
+    async fn get_policy_from_name(&self, name: String) -> Result<Policy, DatabaseError> {
+        let row = sqlx::query_as::<_, PolicyRow>(
+            "SELECT name, description, users, roles FROM policies WHERE namespace = $1 AND name = $2",
+        )
+        .bind(&self.1)
+        .bind(name)
+        .fetch_optional(&self.0)
+        .await?
+        .ok_or(DatabaseError::PolicyNotFound)?;
+
+        let mut policies = self.attach_rules(vec![row]).await?;
+
+        policies.pop().ok_or(DatabaseError::PolicyNotFound)
+    }
+
+    async fn create_policy(&self, policy: Policy) -> Result<(), DatabaseError> {
+        self.check_roles_exist(&policy.roles).await?;
+        self.check_users_exist(&policy.users).await?;
+
+        let mut tx = self.0.begin().await?;
+
+        let res = sqlx::query(
+            "INSERT INTO policies
+            (name, description, users, roles, namespace)
+            VALUES
+            ($1, $2, $3, $4, $5)",
+        )
+        .bind(&policy.name)
+        .bind(&policy.description)
+        .bind(&policy.users)
+        .bind(&policy.roles)
+        .bind(&self.1)
+        .execute(&mut *tx)
+        .await;
+
+        if let Err(e) = res {
+            tx.rollback().await?;
+            return match e {
+                sqlx::Error::Database(e) if e.is_unique_violation() => {
+                    Err(DatabaseError::PolicyAlreadyExists)
+                }
+                e => Err(DatabaseError::SQLError(e)),
+            };
+        }
+
+        self.insert_rules(&mut tx, &policy).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn update_policy(&self, policy: Policy) -> Result<(), DatabaseError> {
+        self.check_roles_exist(&policy.roles).await?;
+        self.check_users_exist(&policy.users).await?;
+
+        let mut tx = self.0.begin().await?;
+
+        let res = sqlx::query(
+            "UPDATE policies SET
+            description = $1,
+            users = $2,
+            roles = $3
+            WHERE namespace = $4 AND name = $5",
+        )
+        .bind(&policy.description)
+        .bind(&policy.users)
+        .bind(&policy.roles)
+        .bind(&self.1)
+        .bind(&policy.name)
+        .execute(&mut *tx)
+        .await?;
+
+        if res.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Err(DatabaseError::PolicyNotFound);
+        }
+
+        sqlx::query("DELETE FROM policy_rules WHERE namespace = $1 AND policy_name = $2")
+            .bind(&self.1)
+            .bind(&policy.name)
+            .execute(&mut *tx)
+            .await?;
+
+        self.insert_rules(&mut tx, &policy).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn delete_policy(&self, name: String) -> Result<(), DatabaseError> {
+        let res = sqlx::query("DELETE FROM policies WHERE namespace = $1 AND name = $2")
+            .bind(&self.1)
             .bind(name)
             .execute(&self.0)
             .await?;
 
+        if res.rows_affected() == 0 {
+            return Err(DatabaseError::PolicyNotFound);
+        }
+
         Ok(())
     }
+
+    async fn explain_secret_access(&self, key: String) -> Result<SecretAccess, DatabaseError> {
+        let info = self.view_secret_info(&key).await?;
+
+        let mut users = Vec::new();
+        let mut roles: Vec<String> = Vec::new();
+
+        for user in self.view_users().await? {
+            let (evaluator, groups) = self.explain_evaluator(user).await?;
+
+            let decision = evaluator.evaluate(Capability::Read, &info);
+            if !decision.allowed {
+                continue;
+            }
+
+            let granting_roles = evaluator.granting_roles(&decision, &info);
+            for role in &granting_roles {
+                if !roles.contains(role) {
+                    roles.push(role.clone());
+                }
+            }
+
+            users.push(AccessGrant {
+                username: evaluator.user().username.clone(),
+                groups,
+                roles: granting_roles,
+                decision,
+            });
+        }
+
+        Ok(SecretAccess { key, users, roles })
+    }
+
+    async fn explain_user_access(&self, username: String) -> Result<UserAccess, DatabaseError> {
+        let user = self.get_user_from_name(username.clone()).await?;
+        let (evaluator, groups) = self.explain_evaluator(user).await?;
+
+        let infos = sqlx::query_as::<_, SecretInfo>(
+            "SELECT key, tags, access_level, role_whitelist FROM secrets
+            WHERE namespace = $1 AND environment = $2 ORDER BY key",
+        )
+        .bind(&self.1)
+        .bind(&self.2)
+        .fetch_all(&self.0)
+        .await?;
+
+        let secrets = infos
+            .into_iter()
+            .filter_map(|info| {
+                let capabilities = evaluator.allowed_capabilities(&info);
+                if capabilities.is_empty() {
+                    return None;
+                }
+
+                Some(ReachableSecret {
+                    decision: evaluator.evaluate(Capability::Read, &info),
+                    key: info.key,
+                    capabilities,
+                })
+            })
+            .collect();
+
+        Ok(UserAccess {
+            username,
+            groups,
+            secrets,
+        })
+    }
+
+    async fn simulate_access(
+        &self,
+        username: String,
+        key: String,
+        capability: Capability,
+    ) -> Result<Decision, DatabaseError> {
+        let user = self.get_user_from_name(username).await?;
+        let info = self.view_secret_info(&key).await?;
+        let evaluator = self.policy_evaluator(user).await?;
+
+        Ok(evaluator.evaluate(capability, &info))
+    }
+
+    async fn view_roles(&self) -> Result<Vec<RoleUsage>, DatabaseError> {
+        let rows = sqlx::query_as::<_, RoleUsageRow>(
+            "SELECT r.name, r.description,
+            ARRAY(SELECT username FROM users WHERE users.namespace = r.namespace AND r.name = ANY(users.roles) ORDER BY username)::TEXT[] AS users,
+            ARRAY(SELECT name FROM groups WHERE groups.namespace = r.namespace AND r.name = ANY(groups.roles) ORDER BY name)::TEXT[] AS groups,
+            ARRAY(SELECT DISTINCT key FROM secrets WHERE secrets.namespace = r.namespace AND r.name = ANY(secrets.role_whitelist) ORDER BY key)::TEXT[] AS secrets
+            FROM roles r WHERE r.namespace = $1 ORDER BY r.name",
+        )
+        .bind(&self.1)
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(rows.into_iter().map(RoleUsageRow::into_usage).collect())
+    }
+
+    async fn get_role_from_name(&self, name: String) -> Result<RoleUsage, DatabaseError> {
+        let row = sqlx::query_as::<_, RoleUsageRow>(
+            "SELECT r.name, r.description,
+            ARRAY(SELECT username FROM users WHERE users.namespace = r.namespace AND r.name = ANY(users.roles) ORDER BY username)::TEXT[] AS users,
+            ARRAY(SELECT name FROM groups WHERE groups.namespace = r.namespace AND r.name = ANY(groups.roles) ORDER BY name)::TEXT[] AS groups,
+            ARRAY(SELECT DISTINCT key FROM secrets WHERE secrets.namespace = r.namespace AND r.name = ANY(secrets.role_whitelist) ORDER BY key)::TEXT[] AS secrets
+            FROM roles r WHERE r.namespace = $1 AND r.name = $2",
+        )
+        .bind(&self.1)
+        .bind(&name)
+        .fetch_optional(&self.0)
+        .await?
+        .ok_or(DatabaseError::RoleNotFound(name))?;
+
+        Ok(row.into_usage())
+    }
+
+    async fn create_role(&self, role: Role) -> Result<(), DatabaseError> {
+        let res = sqlx::query(
+            "INSERT INTO roles
+            (name, description, namespace)
+            VALUES
+            ($1, $2, $3)",
+        )
+        .bind(role.name)
+        .bind(role.description)
+        .bind(&self.1)
+        .execute(&self.0)
+        .await;
+
+        match res {
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                Err(DatabaseError::RoleAlreadyExists)
+            }
+            Err(e) => Err(DatabaseError::SQLError(e)),
+        }
+    }
+
+    async fn update_role(&self, role: Role) -> Result<(), DatabaseError> {
+        let res =
+            sqlx::query("UPDATE roles SET description = $1 WHERE namespace = $2 AND name = $3")
+                .bind(role.description)
+                .bind(&self.1)
+                .bind(&role.name)
+                .execute(&self.0)
+                .await?;
+
+        if res.rows_affected() == 0 {
+            return Err(DatabaseError::RoleNotFound(role.name));
+        }
+
+        Ok(())
+    }
+
+    async fn delete_role(&self, name: String) -> Result<(), DatabaseError> {
+        // Removing a role from a whitelist would widen access to the secret,
+        // so roles that are still referenced have to be unassigned first.
+        let usage = self.get_role_from_name(name.clone()).await?;
+        if !usage.users.is_empty() || !usage.groups.is_empty() || !usage.secrets.is_empty() {
+            return Err(DatabaseError::RoleInUse(name));
+        }
+
+        sqlx::query("DELETE FROM roles WHERE namespace = $1 AND name = $2")
+            .bind(&self.1)
+            .bind(name)
+            .execute(&self.0)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Escapes the wildcards of a `LIKE` pattern, so that a key prefix is matched literally.
+fn escape_like(prefix: &str) -> String {
+    prefix
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
 }
 
 #[derive(sqlx::FromRow)]
 pub struct SingleValue(String);
+
+#[derive(sqlx::FromRow)]
+struct PolicyRow {
+    name: String,
+    description: String,
+    users: Vec<String>,
+    roles: Vec<String>,
+}
+
+impl PolicyRow {
+    fn into_policy(self) -> Policy {
+        Policy {
+            name: self.name,
+            description: self.description,
+            rules: Vec::new(),
+            users: self.users,
+            roles: self.roles,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct PolicyRuleRow {
+    policy_name: String,
+    path: String,
+    tags: Vec<String>,
+    max_access_level: Option<i32>,
+    check_whitelist: bool,
+    environments: Vec<String>,
+    capabilities: Vec<String>,
+}
+
+impl PolicyRuleRow {
+    fn into_rule(self) -> Result<PolicyRule, DatabaseError> {
+        let capabilities = self
+            .capabilities
+            .iter()
+            .map(|x| x.parse::<Capability>())
+            .collect::<Result<Vec<Capability>, String>>()
+            .map_err(DatabaseError::InvalidPolicy)?;
+
+        Ok(PolicyRule {
+            path: self.path,
+            tags: self.tags,
+            max_access_level: self.max_access_level,
+            check_whitelist: self.check_whitelist,
+            environments: self.environments,
+            capabilities,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct RoleUsageRow {
+    name: String,
+    description: String,
+    users: Vec<String>,
+    groups: Vec<String>,
+    secrets: Vec<String>,
+}
+
+impl RoleUsageRow {
+    fn into_usage(self) -> RoleUsage {
+        RoleUsage {
+            name: self.name,
+            description: self.description,
+            users: self.users,
+            groups: self.groups,
+            secrets: self.secrets,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct GroupRow {
+    name: String,
+    description: String,
+    members: Vec<String>,
+    subgroups: Vec<String>,
+    roles: Vec<String>,
+    access_level: i32,
+}
+
+impl GroupRow {
+    fn into_group(self) -> Group {
+        Group {
+            name: self.name,
+            description: self.description,
+            members: self.members,
+            subgroups: self.subgroups,
+            roles: self.roles,
+            access_level: self.access_level,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct FailedAttemptRow {
+    kind: String,
+    username: Option<String>,
+    ip: Option<String>,
+    reason: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl FailedAttemptRow {
+    fn into_attempt(self) -> FailedAttempt {
+        FailedAttempt {
+            kind: self.kind,
+            username: self.username,
+            ip: self.ip,
+            reason: self.reason,
+            created_at: self.created_at,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct AuditEntryRow {
+    action: String,
+    username: String,
+    key: String,
+    from_environment: Option<String>,
+    environment: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl AuditEntryRow {
+    fn into_entry(self) -> AuditEntry {
+        AuditEntry {
+            action: self.action,
+            username: self.username,
+            key: self.key,
+            from_environment: self.from_environment,
+            environment: self.environment,
+            created_at: self.created_at,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct SshKeyRow {
+    fingerprint: String,
+    public_key: String,
+    comment: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl SshKeyRow {
+    fn into_key(self) -> SshKey {
+        SshKey {
+            fingerprint: self.fingerprint,
+            public_key: self.public_key,
+            comment: self.comment,
+            created_at: self.created_at,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct SecretListingRow {
+    key: String,
+    tags: Vec<String>,
+    access_level: i32,
+    role_whitelist: Vec<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl SecretListingRow {
+    fn into_info(self) -> SecretInfo {
+        SecretInfo {
+            key: self.key,
+            tags: self.tags,
+            access_level: self.access_level,
+            role_whitelist: self.role_whitelist,
+        }
+    }
+}
+
+/// Where a page of a secret listing ended. Clients get it as hex-encoded JSON, and since it
+/// has both sort columns, it can carry on a listing in either order.
+#[derive(Serialize, Deserialize)]
+struct ListingCursor {
+    key: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl ListingCursor {
+    fn encode(&self) -> String {
+        hex::encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(cursor: &str) -> Result<Self, DatabaseError> {
+        hex::decode(cursor)
+            .ok()
+            .and_then(|x| serde_json::from_slice(&x).ok())
+            .ok_or(DatabaseError::InvalidCursor)
+    }
+}
@@ -0,0 +1,58 @@
+use chamber_shared::ssh::SshKey;
+use chrono::Utc;
+use ssh_key::{Algorithm, HashAlg, PublicKey, SshSig};
+
+use crate::errors::DatabaseError;
+
+/// Parses an OpenSSH public key. Only ed25519 keys are accepted.
+pub fn parse_public_key(openssh: &str) -> Result<SshKey, DatabaseError> {
+    let key = PublicKey::from_openssh(openssh.trim())
+        .map_err(|e| DatabaseError::InvalidSshKey(e.to_string()))?;
+
+    if key.algorithm() != Algorithm::Ed25519 {
+        return Err(DatabaseError::InvalidSshKey(format!(
+            "{} keys aren't supported, use an ed25519 key",
+            key.algorithm()
+        )));
+    }
+
+    let comment = key.comment().to_string();
+    let mut stripped = key.clone();
+    stripped.set_comment("");
+
+    Ok(SshKey {
+        fingerprint: key.fingerprint(HashAlg::Sha256).to_string(),
+        public_key: stripped
+            .to_openssh()
+            .map_err(|e| DatabaseError::InvalidSshKey(e.to_string()))?,
+        comment,
+        created_at: Utc::now(),
+    })
+}
+
+/// Parses an armored SSHSIG signature and returns the fingerprint of the key that made it,
+/// so that the matching stored key can be looked up.
+pub fn signature_fingerprint(signature: &str) -> Result<(SshSig, String), DatabaseError> {
+    let sig = SshSig::from_pem(signature.trim())
+        .map_err(|e| DatabaseError::InvalidSshKey(e.to_string()))?;
+    let fingerprint = PublicKey::from(sig.public_key().clone())
+        .fingerprint(HashAlg::Sha256)
+        .to_string();
+
+    Ok((sig, fingerprint))
+}
+
+/// Checks that a signature over the message was made by the stored key, in the given namespace.
+pub fn verify_signature(
+    key: &SshKey,
+    namespace: &str,
+    message: &[u8],
+    sig: &SshSig,
+) -> Result<(), DatabaseError> {
+    let public_key = PublicKey::from_openssh(&key.public_key)
+        .map_err(|e| DatabaseError::InvalidSshKey(e.to_string()))?;
+
+    public_key
+        .verify(namespace, message, sig)
+        .map_err(|_| DatabaseError::Forbidden)
+}
@@ -0,0 +1,152 @@
+use ring::digest::{digest, SHA256};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::errors::DatabaseError;
+
+/// Codes are valid for 30 seconds, as expected by authenticator apps.
+pub static TOTP_STEP: u64 = 30;
+pub static TOTP_DIGITS: u32 = 6;
+pub static RECOVERY_CODE_COUNT: usize = 10;
+
+static BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Generates a random 160-bit secret, base32 encoded.
+pub fn generate_secret() -> Result<String, DatabaseError> {
+    let mut bytes = [0u8; 20];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| DatabaseError::EncryptionError)?;
+
+    Ok(base32_encode(&bytes))
+}
+
+/// Builds the URI that authenticator apps read from a QR code.
+pub fn otpauth_uri(issuer: &str, username: &str, secret: &str) -> String {
+    let issuer = percent_encode(issuer);
+
+    format!(
+        "otpauth://totp/{issuer}:{}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={TOTP_DIGITS}&period={TOTP_STEP}",
+        percent_encode(username)
+    )
+}
+
+/// Returns the time step that a code is valid for.
+pub fn time_step(unix_time: u64) -> u64 {
+    unix_time / TOTP_STEP
+}
+
+/// Computes the code for a time step as described in RFC 6238.
+pub fn code_at(secret: &str, step: u64) -> Option<String> {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, &base32_decode(secret)?);
+    let tag = hmac::sign(&key, &step.to_be_bytes());
+    let tag = tag.as_ref();
+
+    let offset = (tag[tag.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        tag[offset] & 0x7f,
+        tag[offset + 1],
+        tag[offset + 2],
+        tag[offset + 3],
+    ]);
+
+    Some(format!(
+        "{:0width$}",
+        binary % 10u32.pow(TOTP_DIGITS),
+        width = TOTP_DIGITS as usize
+    ))
+}
+
+/// Checks a code against the current time step and the ones either side of it, to allow for clock drift.
+/// Returns the matching time step so that it can't be used again.
+pub fn verify_code(secret: &str, code: &str, unix_time: u64) -> Option<u64> {
+    let step = time_step(unix_time);
+
+    [step.saturating_sub(1), step, step + 1]
+        .into_iter()
+        .find(|x| code_at(secret, *x).is_some_and(|expected| expected == code.trim()))
+}
+
+/// Generates single-use recovery codes, returned in plaintext so that they can be shown once.
+pub fn generate_recovery_codes() -> Result<Vec<String>, DatabaseError> {
+    let rng = SystemRandom::new();
+
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 10];
+            rng.fill(&mut bytes)
+                .map_err(|_| DatabaseError::EncryptionError)?;
+
+            let code = base32_encode(&bytes).to_lowercase();
+            Ok(format!("{}-{}", &code[..8], &code[8..]))
+        })
+        .collect()
+}
+
+/// Recovery codes are random enough that a plain SHA-256 is sufficient for storing them.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalised = code.trim().to_lowercase();
+
+    hex::encode(digest(&SHA256, normalised.as_bytes()))
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut res = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            res.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+
+        // Only keep the bits that haven't been written out yet
+        buffer &= (1 << bits) - 1;
+    }
+
+    if bits > 0 {
+        res.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+
+    res
+}
+
+fn base32_decode(input: &str) -> Option<Vec<u8>> {
+    let mut res = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for c in input.trim_end_matches('=').chars() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|x| *x as char == c.to_ascii_uppercase())?;
+
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+
+        if bits >= 8 {
+            bits -= 8;
+            res.push((buffer >> bits) as u8);
+        }
+
+        buffer &= (1 << bits) - 1;
+    }
+
+    Some(res)
+}
+
+fn percent_encode(input: &str) -> String {
+    input
+        .bytes()
+        .map(|x| match x {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (x as char).to_string()
+            }
+            _ => format!("%{x:02X}"),
+        })
+        .collect()
+}
@@ -1,18 +1,28 @@
+use crate::challenges::{ChallengeStore, MAX_UNSEAL_CHALLENGES};
 use crate::core::{Database, LockedStatus};
 use crate::errors::DatabaseError;
+use crate::hashing::HashConfig;
+use crate::limits::AuthLimits;
 use chamber_crypto::secrets::KeyFile;
+use chamber_shared::ssh::UnsealKey;
 use crate::Postgres;
 use sqlx::PgPool;
 
-use crate::consts::KEYFILE_PATH;
+use crate::consts::{DATA_KEYS_PERSIST_KEY, KEYFILE_PATH, UNSEAL_KEYS_PERSIST_KEY};
 use shuttle_persist::PersistInstance;
+use std::collections::HashMap;
 
 #[async_trait::async_trait]
 pub trait AppState: std::fmt::Debug + Clone + Send + Sync + 'static {
-    type D: Database;
+    type D: Database + Send + Sync;
 
     fn db(&self) -> &Self::D;
     fn locked_status(&self) -> LockedStatus;
+    fn auth_limits(&self) -> AuthLimits;
+    fn hash_config(&self) -> HashConfig;
+    fn challenges(&self) -> ChallengeStore;
+    /// Kept apart from the login challenges, so that asking for those can't lock operators out.
+    fn unseal_challenges(&self) -> ChallengeStore;
     fn get_keyfile(&self) -> Result<KeyFile, DatabaseError>;
     async fn unlock(&self, key: String) -> Result<bool, DatabaseError> {
         let keyfile = self.get_keyfile();
@@ -21,42 +31,65 @@ pub trait AppState: std::fmt::Debug + Clone + Send + Sync + 'static {
             return Err(DatabaseError::Forbidden);
         }
 
-        self.locked_status().unlock().await.unwrap();
-
-        Ok(true)
+        self.locked_status().unlock().await
     }
 
     #[tracing::instrument]
-    fn check_keyfile_exists(&self) {
+    fn check_keyfile_exists(&self) -> Result<(), DatabaseError> {
         if std::fs::read(KEYFILE_PATH).is_err() {
             println!("No chamber.bin file attached, generating one now...");
             let key = KeyFile::new();
-            tracing::warn!("Your root key is: {}", key.unseal_key());
 
-            let encoded = bincode::serialize(&key).unwrap();
+            let encoded = bincode::serialize(&key)?;
 
-            std::fs::create_dir("data").unwrap();
+            std::fs::create_dir_all("data")?;
 
-            std::fs::write(KEYFILE_PATH, encoded).unwrap();
+            std::fs::write(KEYFILE_PATH, encoded)?;
             println!("Successfully saved. Don't forget that you can generate a new chamber file from the CLI and upload it!");
+
+            // Only shown this once, and never through tracing, so that it doesn't end up in logs
+            println!("Your root key is: {}", key.unseal_key());
         }
+
+        Ok(())
     }
 
     fn save_keyfile(&self, keyfile: KeyFile) -> Result<(), DatabaseError>;
+
+    /// The public keys that can unseal the instance. These are stored with the keyfile rather than
+    /// in the database, so that database access alone isn't enough to register one.
+    fn get_unseal_keys(&self) -> Result<Vec<UnsealKey>, DatabaseError>;
+    fn save_unseal_keys(&self, keys: Vec<UnsealKey>) -> Result<(), DatabaseError>;
+
+    /// Each namespace's data key, wrapped by the keyfile's crypto key. These are kept out of the
+    /// database too, so that deleting one leaves copies of the namespace's secrets, such as those
+    /// in database backups, unreadable. A key is wrapped more than once while a new keyfile is
+    /// being uploaded, so that it opens with whichever keyfile ends up saved.
+    fn get_data_keys(&self) -> Result<HashMap<String, Vec<Vec<u8>>>, DatabaseError>;
+    fn save_data_keys(&self, keys: HashMap<String, Vec<Vec<u8>>>) -> Result<(), DatabaseError>;
 }
 
 #[derive(Clone, Debug)]
 pub struct ShuttleAppState {
     pub db: Postgres,
     pub lock: LockedStatus,
+    pub limits: AuthLimits,
+    pub hash_config: HashConfig,
+    pub challenges: ChallengeStore,
+    pub unseal_challenges: ChallengeStore,
     pub persist: PersistInstance,
 }
 
 impl ShuttleAppState {
-    pub fn new(db: PgPool, persist: PersistInstance) -> Self {
+    pub fn new(db: PgPool, persist: PersistInstance, hash_config: HashConfig) -> Self {
         Self {
             db: Postgres::from_pool(db),
             lock: LockedStatus::default(),
+            limits: AuthLimits::default(),
+            hash_config,
+            challenges: ChallengeStore::default(),
+            unseal_challenges: ChallengeStore::default()
+                .with_max_outstanding(MAX_UNSEAL_CHALLENGES),
             persist,
         }
     }
@@ -72,11 +105,27 @@ impl AppState for ShuttleAppState {
         self.lock.to_owned()
     }
 
+    fn auth_limits(&self) -> AuthLimits {
+        self.limits.to_owned()
+    }
+
+    fn hash_config(&self) -> HashConfig {
+        self.hash_config
+    }
+
+    fn challenges(&self) -> ChallengeStore {
+        self.challenges.to_owned()
+    }
+
+    fn unseal_challenges(&self) -> ChallengeStore {
+        self.unseal_challenges.to_owned()
+    }
+
     fn get_keyfile(&self) -> Result<KeyFile, DatabaseError> {
         let mut res = match self.persist.load::<KeyFile>("KEYFILE") {
             Ok(res) => res,
             Err(_) => {
-                self.check_keyfile_exists();
+                self.check_keyfile_exists()?;
                 let res = match std::fs::read(KEYFILE_PATH) {
                     Ok(res) => res,
                     Err(e) => return Err(DatabaseError::IoError(e)),
@@ -85,7 +134,7 @@ impl AppState for ShuttleAppState {
                 let decoded: KeyFile = bincode::deserialize(&res)?;
 
                 self.persist.save::<KeyFile>("KEYFILE", decoded)?;
-                self.persist.load::<KeyFile>("KEYFILE").unwrap()
+                self.persist.load::<KeyFile>("KEYFILE")?
             }
         };
 
@@ -99,4 +148,41 @@ impl AppState for ShuttleAppState {
 
         Ok(())
     }
+
+    fn get_unseal_keys(&self) -> Result<Vec<UnsealKey>, DatabaseError> {
+        // Nothing has been saved until the first key is registered
+        Ok(self
+            .persist
+            .load::<Vec<UnsealKey>>(UNSEAL_KEYS_PERSIST_KEY)
+            .unwrap_or_default())
+    }
+
+    fn save_unseal_keys(&self, keys: Vec<UnsealKey>) -> Result<(), DatabaseError> {
+        self.persist.save(UNSEAL_KEYS_PERSIST_KEY, keys)?;
+
+        Ok(())
+    }
+
+    fn get_data_keys(&self) -> Result<HashMap<String, Vec<Vec<u8>>>, DatabaseError> {
+        // Nothing has been saved until the first namespace gets a key. Anything else is an error
+        // rather than an empty list, since saving over the keys would lose them.
+        if !self
+            .persist
+            .list()?
+            .iter()
+            .any(|x| x == DATA_KEYS_PERSIST_KEY)
+        {
+            return Ok(HashMap::new());
+        }
+
+        Ok(self
+            .persist
+            .load::<HashMap<String, Vec<Vec<u8>>>>(DATA_KEYS_PERSIST_KEY)?)
+    }
+
+    fn save_data_keys(&self, keys: HashMap<String, Vec<Vec<u8>>>) -> Result<(), DatabaseError> {
+        self.persist.save(DATA_KEYS_PERSIST_KEY, keys)?;
+
+        Ok(())
+    }
 }
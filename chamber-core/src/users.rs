@@ -1,44 +1,75 @@
 use crate::errors::DatabaseError;
-use argon2::{
-    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
-    Argon2,
-};
+use crate::hashing::{self, HashConfig};
+use crate::totp;
+use chamber_shared::groups::Group;
+use chamber_shared::namespaces::DEFAULT_NAMESPACE;
+use chamber_shared::users::UserSummary;
+use chrono::{DateTime, Utc};
 
 use serde::Serialize;
 
 #[derive(Clone, sqlx::FromRow, Serialize)]
 pub struct User {
+    namespace: String,
     pub username: String,
     pub password: String,
     access_level: i32,
     roles: Vec<String>,
+    namespace_admin: bool,
+    disabled: bool,
+    expires_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    #[serde(skip)]
+    pub(crate) totp_secret: Option<String>,
+    #[serde(skip)]
+    pub(crate) totp_pending_secret: Option<String>,
+    #[serde(skip)]
+    pub(crate) totp_last_step: Option<i64>,
+    /// SHA-256 hashes of the unused recovery codes.
+    #[serde(skip)]
+    pub(crate) recovery_codes: Vec<String>,
 }
 
 impl<'a> User {
-    pub fn new(username: String, password: String) -> Self {
-        let password = password.into_bytes();
-
-        let salt = SaltString::generate(&mut OsRng);
-
-        let argon2 = Argon2::default();
-
-        let password_hash = argon2.hash_password(&password, &salt).unwrap().to_string();
-
+    /// Creates a user without a password, which can't log in until [`User::set_password`] is called.
+    pub fn new(username: String) -> Self {
         Self {
+            namespace: DEFAULT_NAMESPACE.to_string(),
             username,
-            password: password_hash,
+            password: String::new(),
             access_level: 0,
             roles: Vec::new(),
+            namespace_admin: false,
+            disabled: false,
+            expires_at: None,
+            created_at: Utc::now(),
+            totp_secret: None,
+            totp_pending_secret: None,
+            totp_last_step: None,
+            recovery_codes: Vec::new(),
         }
     }
 
-    pub fn verify(&self, pw: &str) -> Result<(), DatabaseError> {
-        let parsed_hash = PasswordHash::new(&self.password)?;
-        Argon2::default().verify_password(pw.as_bytes(), &parsed_hash)?;
+    /// Generates a random password for users that weren't given one.
+    pub fn generate_password() -> String {
+        nanoid::nanoid!(32)
+    }
+
+    /// Hashes and sets a new password.
+    pub async fn set_password(
+        &mut self,
+        config: &HashConfig,
+        pw: &str,
+    ) -> Result<(), DatabaseError> {
+        self.password = config.hash(pw).await?;
 
         Ok(())
     }
 
+    pub async fn verify(&self, pw: &str) -> Result<(), DatabaseError> {
+        hashing::verify(&self.password, pw).await
+    }
+
     pub fn access_level(&self) -> i32 {
         self.access_level
     }
@@ -55,6 +86,148 @@ impl<'a> User {
         self.roles = vec;
     }
 
+    /// The namespace that the user belongs to. New users are created in the namespace of the
+    /// database handle that stores them, so this only matters for users that aren't stored.
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    pub fn set_namespace(&mut self, namespace: String) {
+        self.namespace = namespace;
+    }
+
+    pub fn is_namespace_admin(&self) -> bool {
+        self.namespace_admin
+    }
+
+    pub fn set_namespace_admin(&mut self, namespace_admin: bool) {
+        self.namespace_admin = namespace_admin;
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.disabled
+    }
+
+    pub fn set_disabled(&mut self, disabled: bool) {
+        self.disabled = disabled;
+    }
+
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.expires_at
+    }
+
+    pub fn set_expires_at(&mut self, expires_at: Option<DateTime<Utc>>) {
+        self.expires_at = expires_at;
+    }
+
+    /// Makes sure that the user hasn't been disabled and hasn't expired.
+    pub fn check_active(&self) -> Result<(), DatabaseError> {
+        if self.disabled {
+            return Err(DatabaseError::UserDisabled);
+        }
+
+        if self.expires_at.is_some_and(|x| x <= Utc::now()) {
+            return Err(DatabaseError::UserExpired);
+        }
+
+        Ok(())
+    }
+
+    pub fn summary(&self) -> UserSummary {
+        UserSummary {
+            username: self.username.clone(),
+            access_level: self.access_level,
+            roles: self.roles.clone(),
+            namespace_admin: self.namespace_admin,
+            disabled: self.disabled,
+            expires_at: self.expires_at,
+            created_at: self.created_at,
+            two_factor_enabled: self.two_factor_enabled(),
+        }
+    }
+
+    pub fn two_factor_enabled(&self) -> bool {
+        self.totp_secret.is_some()
+    }
+
+    /// Generates a TOTP secret that becomes active once a code from it has been confirmed.
+    pub fn start_two_factor_enrollment(&mut self) -> Result<String, DatabaseError> {
+        let secret = totp::generate_secret()?;
+        self.totp_pending_secret = Some(secret.clone());
+
+        Ok(secret)
+    }
+
+    /// Activates the pending TOTP secret, returning a fresh set of plaintext recovery codes.
+    pub fn confirm_two_factor(&mut self, code: &str) -> Result<Vec<String>, DatabaseError> {
+        let Some(secret) = self.totp_pending_secret.clone() else {
+            return Err(DatabaseError::TwoFactorNotEnrolled);
+        };
+
+        let step = totp::verify_code(&secret, code, Utc::now().timestamp() as u64)
+            .ok_or(DatabaseError::InvalidTwoFactorCode)?;
+
+        let recovery_codes = totp::generate_recovery_codes()?;
+
+        self.totp_secret = Some(secret);
+        self.totp_pending_secret = None;
+        self.totp_last_step = Some(step as i64);
+        self.recovery_codes = recovery_codes
+            .iter()
+            .map(|x| totp::hash_recovery_code(x))
+            .collect();
+
+        Ok(recovery_codes)
+    }
+
+    pub fn disable_two_factor(&mut self) {
+        self.totp_secret = None;
+        self.totp_pending_secret = None;
+        self.totp_last_step = None;
+        self.recovery_codes = Vec::new();
+    }
+
+    /// Checks a TOTP code or an unused recovery code. Either one can only be used once.
+    pub fn verify_second_factor(&mut self, code: &str) -> Result<(), DatabaseError> {
+        let Some(secret) = &self.totp_secret else {
+            return Err(DatabaseError::TwoFactorNotEnrolled);
+        };
+
+        if let Some(step) = totp::verify_code(secret, code, Utc::now().timestamp() as u64) {
+            if self.totp_last_step.is_some_and(|x| x >= step as i64) {
+                return Err(DatabaseError::InvalidTwoFactorCode);
+            }
+
+            self.totp_last_step = Some(step as i64);
+            return Ok(());
+        }
+
+        let hash = totp::hash_recovery_code(code);
+        match self.recovery_codes.iter().position(|x| x == &hash) {
+            Some(idx) => {
+                self.recovery_codes.remove(idx);
+                Ok(())
+            }
+            None => Err(DatabaseError::InvalidTwoFactorCode),
+        }
+    }
+
+    /// Returns this user with the grants of the given groups merged in:
+    /// the highest access level wins and roles are combined.
+    pub fn with_groups(mut self, groups: &[Group]) -> Self {
+        for group in groups {
+            self.access_level = self.access_level.max(group.access_level);
+
+            for role in &group.roles {
+                if !self.roles.contains(role) {
+                    self.roles.push(role.clone());
+                }
+            }
+        }
+
+        self
+    }
+
     pub fn grant_user_role(mut self, role: String) -> Result<(), DatabaseError> {
         if self.roles.contains(&role) {
             return Err(DatabaseError::RoleAlreadyExists);
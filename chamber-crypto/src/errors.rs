@@ -4,6 +4,18 @@ use thiserror::Error;
 pub enum DatabaseError {
     #[error("Encryption error")]
     EncryptionError,
+    #[error("Decryption error")]
+    DecryptionError,
+    #[error("The signing key is invalid")]
+    InvalidSigningKey,
+    #[error("The signature is invalid")]
+    InvalidSignature,
+    #[error("The nonce is out of range")]
+    InvalidNonce,
+    #[error("The crypto key is invalid")]
+    InvalidCryptoKey,
+    #[error("UTF8 error: {0}")]
+    Utf8Error(#[from] std::string::FromUtf8Error),
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
     #[error("SQL error: {0}")]
@@ -11,3 +23,9 @@ pub enum DatabaseError {
     #[error("bincode error: {0}")]
     Bincode(bincode::ErrorKind)
 }
+
+impl From<Box<bincode::ErrorKind>> for DatabaseError {
+    fn from(e: Box<bincode::ErrorKind>) -> Self {
+        Self::Bincode(*e)
+    }
+}
@@ -1,11 +1,12 @@
 use crate::errors::DatabaseError;
 use crate::signing::{fetch_signing_key, verify_bytes, SigWrapper};
+use chamber_shared::secrets::SecretInfo;
 use ed25519_dalek::Signer;
 use num_traits::cast::ToPrimitive;
 use ring::rand::SecureRandom;
 use ring::rand::SystemRandom;
 use ring::{
-    aead::{Aad, BoundKey, Nonce, NonceSequence, OpeningKey, SealingKey},
+    aead::{Aad, BoundKey, LessSafeKey, Nonce, NonceSequence, OpeningKey, SealingKey, NONCE_LEN},
     error::Unspecified,
 };
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -71,8 +72,8 @@ impl EncryptedSecretBuilder {
         self,
         mut sealing_key: SealingKey<NonceCounter>,
         nonce_num: u64,
-    ) -> EncryptedSecret {
-        let signing_key = fetch_signing_key().unwrap();
+    ) -> Result<EncryptedSecret, DatabaseError> {
+        let signing_key = fetch_signing_key()?;
 
         let value_as_bytes = self.value.into_bytes();
 
@@ -84,9 +85,9 @@ impl EncryptedSecretBuilder {
 
         sealing_key
             .seal_in_place_append_tag(aad, &mut transformed_in_place)
-            .unwrap();
+            .map_err(|_| DatabaseError::EncryptionError)?;
 
-        EncryptedSecret {
+        Ok(EncryptedSecret {
             key: self.key,
             nonce: U64Wrapper(nonce_num),
             sig: SigWrapper::new(sig),
@@ -106,7 +107,7 @@ impl EncryptedSecretBuilder {
             } else {
                 Vec::new()
             },
-        }
+        })
     }
 }
 
@@ -120,20 +121,28 @@ pub struct Secret {
 }
 
 impl Secret {
-    pub fn decrypt(&self, mut seq: OpeningKey<NonceCounter>) -> String {
-        let sig: [u8; 64] = self.sig.clone().try_into().unwrap();
+    /// Decrypts the secret and checks its signature, so that a corrupted or tampered row
+    /// is reported as an error rather than returned.
+    pub fn decrypt(&self, mut seq: OpeningKey<NonceCounter>) -> Result<String, DatabaseError> {
+        let sig: [u8; 64] = self
+            .sig
+            .clone()
+            .try_into()
+            .map_err(|_| DatabaseError::InvalidSignature)?;
 
         let aad = Aad::empty();
 
         let mut tag = self.ciphertext.clone();
 
-        let plaintext = seq.open_in_place(aad, &mut tag).unwrap();
+        let plaintext = seq
+            .open_in_place(aad, &mut tag)
+            .map_err(|_| DatabaseError::DecryptionError)?;
 
-        let signing_key = fetch_signing_key().unwrap();
+        let signing_key = fetch_signing_key()?;
 
-        verify_bytes(plaintext, &sig, signing_key).unwrap();
+        verify_bytes(plaintext, &sig, signing_key)?;
 
-        String::from_utf8(plaintext.to_vec()).unwrap()
+        Ok(String::from_utf8(plaintext.to_vec())?)
     }
 }
 
@@ -202,22 +211,26 @@ impl<'a> EncryptedSecret {
         &mut self,
         mut open_key: OpeningKey<NonceCounter>,
         mut sealing_key: SealingKey<NonceCounter>,
-    ) {
+    ) -> Result<(), DatabaseError> {
         let aad = Aad::empty();
 
         let mut tag = self.ciphertext.clone();
 
-        let key = open_key.open_in_place(aad, &mut tag).unwrap();
+        let key = open_key
+            .open_in_place(aad, &mut tag)
+            .map_err(|_| DatabaseError::DecryptionError)?;
 
-        let plaintext = String::from_utf8(key.to_vec()).unwrap();
+        let plaintext = String::from_utf8(key.to_vec())?;
 
         let mut transformed_in_place: Vec<u8> = plaintext.into_bytes();
 
         sealing_key
             .seal_in_place_append_tag(aad, &mut transformed_in_place)
-            .unwrap();
+            .map_err(|_| DatabaseError::EncryptionError)?;
 
         self.ciphertext = transformed_in_place;
+
+        Ok(())
     }
 }
 
@@ -232,8 +245,17 @@ impl SerializeKey {
         Self(key.to_vec())
     }
 
-    pub fn make_key(&self) -> ring::aead::UnboundKey {
-        ring::aead::UnboundKey::new(&ring::aead::AES_256_GCM, &self.0).unwrap()
+    pub fn make_key(&self) -> Result<ring::aead::UnboundKey, DatabaseError> {
+        ring::aead::UnboundKey::new(&ring::aead::AES_256_GCM, &self.0)
+            .map_err(|_| DatabaseError::InvalidCryptoKey)
+    }
+
+    pub fn seal_key(&self, num: u64) -> Result<SealingKey<NonceCounter>, DatabaseError> {
+        Ok(SealingKey::new(self.make_key()?, NonceCounter(num)))
+    }
+
+    pub fn open_key(&self, num: u64) -> Result<OpeningKey<NonceCounter>, DatabaseError> {
+        Ok(OpeningKey::new(self.make_key()?, NonceCounter(num)))
     }
 }
 
@@ -258,9 +280,7 @@ impl<'de> Deserialize<'de> for SerializeKey {
     where
         D: Deserializer<'de>,
     {
-        let bytes = Deserialize::deserialize(deserializer)
-            .map(ByteBuf::into_vec)
-            .unwrap();
+        let bytes = Deserialize::deserialize(deserializer).map(ByteBuf::into_vec)?;
 
         Ok(Self(bytes))
     }
@@ -300,7 +320,7 @@ impl<'b> KeyFile {
 
     pub fn save(&self) -> Result<(), DatabaseError> {
         let _thing = self;
-        let encoded = bincode::serialize(&self).unwrap();
+        let encoded = bincode::serialize(&self)?;
 
         match std::fs::write(KEYFILE_PATH, encoded) {
             Ok(res) => res,
@@ -310,21 +330,81 @@ impl<'b> KeyFile {
         Ok(())
     }
 
-    pub fn get_crypto_seal_key(&mut self) -> SealingKey<NonceCounter> {
+    pub fn get_crypto_seal_key(&mut self) -> Result<SealingKey<NonceCounter>, DatabaseError> {
         let nonce_sequence = NonceCounter(self.nonce_number);
 
-        let unbound_key = self.crypto_key.make_key();
+        let unbound_key = self.crypto_key.make_key()?;
         self.nonce_number += 1;
 
         let _ = self.save();
-        SealingKey::new(unbound_key, nonce_sequence)
+        Ok(SealingKey::new(unbound_key, nonce_sequence))
     }
 
-    pub fn get_crypto_open_key(&self, num: u64) -> OpeningKey<NonceCounter> {
+    pub fn get_crypto_open_key(&self, num: u64) -> Result<OpeningKey<NonceCounter>, DatabaseError> {
         let nonce_sequence = NonceCounter(num);
 
-        let unbound_key = self.crypto_key.make_key();
-        OpeningKey::new(unbound_key, nonce_sequence)
+        let unbound_key = self.crypto_key.make_key()?;
+        Ok(OpeningKey::new(unbound_key, nonce_sequence))
+    }
+
+    /// Takes a nonce for sealing a secret. Every data key draws from the same counter, so
+    /// nonces stay unique whichever key a secret is sealed with.
+    pub fn next_nonce(&mut self) -> u64 {
+        let nonce = self.nonce_number;
+        self.nonce_number += 1;
+
+        nonce
+    }
+
+    /// Seals a namespace's data key with the crypto key, so that it can be stored next to the
+    /// namespace. The name is authenticated too, so a wrapped key only opens for its own namespace.
+    pub fn wrap_data_key(
+        &self,
+        namespace: &str,
+        data_key: &SerializeKey,
+    ) -> Result<Vec<u8>, DatabaseError> {
+        let key = LessSafeKey::new(self.crypto_key.make_key()?);
+
+        // Data keys are rare enough that random nonces won't collide
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| DatabaseError::EncryptionError)?;
+
+        let mut sealed = data_key.0.clone();
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(namespace.as_bytes()),
+            &mut sealed,
+        )
+        .map_err(|_| DatabaseError::EncryptionError)?;
+
+        Ok([nonce.as_slice(), &sealed].concat())
+    }
+
+    pub fn unwrap_data_key(
+        &self,
+        namespace: &str,
+        wrapped: &[u8],
+    ) -> Result<SerializeKey, DatabaseError> {
+        if wrapped.len() < NONCE_LEN {
+            return Err(DatabaseError::DecryptionError);
+        }
+
+        let key = LessSafeKey::new(self.crypto_key.make_key()?);
+        let (nonce, sealed) = wrapped.split_at(NONCE_LEN);
+        let nonce =
+            Nonce::try_assume_unique_for_key(nonce).map_err(|_| DatabaseError::DecryptionError)?;
+
+        // Opened in place, so that the data key isn't left behind in a copy
+        let mut data_key = sealed.to_vec();
+        let len = key
+            .open_in_place(nonce, Aad::from(namespace.as_bytes()), &mut data_key)
+            .map_err(|_| DatabaseError::DecryptionError)?
+            .len();
+        data_key.truncate(len);
+
+        Ok(SerializeKey(data_key))
     }
 }
 
@@ -343,9 +423,14 @@ impl U64Wrapper {
     }
 }
 
-impl From<BigDecimal> for U64Wrapper {
-    fn from(decimal: BigDecimal) -> Self {
-        Self(decimal.to_u64().unwrap())
+impl TryFrom<BigDecimal> for U64Wrapper {
+    type Error = DatabaseError;
+
+    fn try_from(decimal: BigDecimal) -> Result<Self, Self::Error> {
+        decimal
+            .to_u64()
+            .map(Self)
+            .ok_or(DatabaseError::InvalidNonce)
     }
 }
 
@@ -381,14 +466,17 @@ impl NonceSequence for NonceCounter {
         nonce_bytes[4..].copy_from_slice(&bytes);
 
         self.0 += 1; // advance the counter
-        Ok(Nonce::try_assume_unique_for_key(&nonce_bytes).unwrap())
+        Nonce::try_assume_unique_for_key(&nonce_bytes)
     }
 }
 
-#[derive(sqlx::FromRow, Clone, Serialize, Deserialize, Debug)]
-pub struct SecretInfo {
-    pub key: String,
-    pub tags: Vec<String>,
-    pub access_level: i32,
-    pub role_whitelist: Vec<String>,
+impl From<&EncryptedSecret> for SecretInfo {
+    fn from(secret: &EncryptedSecret) -> Self {
+        Self {
+            key: secret.key.clone(),
+            tags: secret.tags.clone(),
+            access_level: secret.access_level,
+            role_whitelist: secret.role_whitelist.clone(),
+        }
+    }
 }
@@ -25,11 +25,13 @@ pub fn check_signing_key_exists() -> Result<(), DatabaseError> {
 }
 
 pub fn fetch_signing_key() -> Result<SigningKey, DatabaseError> {
-    let bytes = std::fs::read(SIGNING_KEY_PATH).unwrap();
+    let bytes = std::fs::read(SIGNING_KEY_PATH)?;
 
-    let bytes: [u8; 64] = bytes.try_into().unwrap();
+    let bytes: [u8; 64] = bytes
+        .try_into()
+        .map_err(|_| DatabaseError::InvalidSigningKey)?;
 
-    Ok(SigningKey::from_keypair_bytes(&bytes).unwrap())
+    SigningKey::from_keypair_bytes(&bytes).map_err(|_| DatabaseError::InvalidSigningKey)
 }
 
 pub fn verify_bytes(
@@ -39,20 +41,24 @@ pub fn verify_bytes(
 ) -> Result<(), DatabaseError> {
     let sig: Signature = Signature::from_bytes(signature);
 
-    signing_key.verify(message, &sig).unwrap();
-
-    Ok(())
+    signing_key
+        .verify(message, &sig)
+        .map_err(|_| DatabaseError::InvalidSignature)
 }
 
 #[derive(Zeroize, ZeroizeOnDrop)]
 pub struct SigWrapper([u8; 64]);
 
-impl From<Vec<u8>> for SigWrapper {
-    fn from(vec: Vec<u8>) -> Self {
-        let bytes: [u8; 64] = vec.try_into().unwrap();
+impl TryFrom<Vec<u8>> for SigWrapper {
+    type Error = DatabaseError;
+
+    fn try_from(vec: Vec<u8>) -> Result<Self, Self::Error> {
+        let bytes: [u8; 64] = vec
+            .try_into()
+            .map_err(|_| DatabaseError::InvalidSignature)?;
         let sig = Signature::from_bytes(&bytes);
 
-        Self(sig.into())
+        Ok(Self(sig.into()))
     }
 }
 
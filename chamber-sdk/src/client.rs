@@ -1,44 +1,64 @@
-use crate::consts::{GET_SECRETS_URL, LOGIN_URL, GET_SECRETS_BY_TAG_URL};
-use chamber_shared::SecretPublic;
+use crate::consts::{LOGIN_URL, SECRETS_URL, TAGS_URL};
+use chamber_shared::environments::{DEFAULT_ENVIRONMENT, ENVIRONMENT_HEADER};
+use chamber_shared::errors::ErrorBody;
+use chamber_shared::secrets::{
+    ListSecretsQuery, SecretInfo, SecretPage, SecretPublic, NEXT_CURSOR_HEADER,
+};
+use chamber_shared::users::UserLoginParams;
+use chamber_shared::AuthBody;
 use reqwest::Client as ReqClient;
+use reqwest::Response;
 use reqwest::StatusCode;
-use serde::{Deserialize, Serialize};
-use serde_json::json;
 use url::Url;
 
 pub struct Client {
     ctx: ReqClient,
     url: Url,
     credentials: Credentials,
+    environment: String,
 }
 
 impl Client {
-    fn builder() -> ClientBuilder {
+    pub fn builder() -> ClientBuilder {
         ClientBuilder {
             ctx: ReqClient::new(),
             url: None,
             credentials: None,
+            environment: None,
         }
     }
 }
 
 impl Client {
+    /// Joins a route and path segments onto the server URL, escaping the segments.
+    fn endpoint(&self, route: &str, segments: &[&str]) -> Url {
+        let mut url = self.url.join(route).unwrap_or_else(|_| self.url.clone());
+
+        if let Ok(mut path) = url.path_segments_mut() {
+            path.pop_if_empty().extend(segments);
+        }
+
+        url
+    }
+
     pub async fn login(self, username: String, password: String) -> Result<Self, ClientError> {
-        let json = json!({
-            "username": username,
-            "password": password
-        });
+        let json = UserLoginParams {
+            namespace: None,
+            username,
+            password,
+            code: None,
+        };
 
         let response = self
             .ctx
-            .post(format!("{}{}", self.url, LOGIN_URL))
+            .post(self.endpoint(LOGIN_URL, &[]))
             .json(&json)
             .send()
             .await?;
 
         let token = match response.status() {
             StatusCode::OK => response.json::<AuthBody>().await?,
-            _ => return Err(ClientError::RequestError(response.text().await?)),
+            _ => return Err(ClientError::from_response(response).await),
         };
 
         self.credentials
@@ -48,27 +68,31 @@ impl Client {
         Ok(self)
     }
 
+    /// Uses a token from an external identity provider, such as a CI workload identity token,
+    /// instead of logging in.
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.credentials.jwt = Some(format!("Bearer {token}"));
+
+        self
+    }
+
     pub async fn get_secret(&self, key: &str) -> Result<String, ClientError> {
         let jwt = match &self.credentials.jwt {
             Some(res) => res,
             None => todo!("Implement error here"),
         };
 
-        let json = json!({
-            "key": key
-        });
-
         let response = self
             .ctx
-            .post(format!("{}{}", self.url, GET_SECRETS_URL))
+            .get(self.endpoint(SECRETS_URL, &key.split('/').collect::<Vec<_>>()))
             .header("Authorization", jwt)
-            .json(&json)
+            .header(ENVIRONMENT_HEADER, &self.environment)
             .send()
             .await?;
 
         match response.status() {
             StatusCode::OK => Ok(response.text().await?),
-            _ => Err(ClientError::RequestError(response.text().await?)),
+            _ => Err(ClientError::from_response(response).await),
         }
     }
 
@@ -78,85 +102,93 @@ impl Client {
             None => todo!("Implement error here"),
         };
 
-        let json = json!({
-            "key": tag
-        });
-
         let response = self
             .ctx
-            .post(format!("{}{}", self.url, GET_SECRETS_BY_TAG_URL))
+            .get(self.endpoint(TAGS_URL, &[tag, "secrets"]))
             .header("Authorization", jwt)
-            .json(&json)
+            .header(ENVIRONMENT_HEADER, &self.environment)
             .send()
             .await?;
 
         match response.status() {
             StatusCode::OK => Ok(response.json::<Vec<SecretPublic>>().await?),
-            _ => Err(ClientError::RequestError(response.text().await?)),
+            _ => Err(ClientError::from_response(response).await),
         }
     }
 
     pub async fn get_secret_info_with_tag(&self, tag: &str) -> Result<Vec<SecretInfo>, ClientError> {
+        let query = ListSecretsQuery {
+            tag: Some(tag.to_owned()),
+            ..Default::default()
+        };
+
+        Ok(self.list_secrets(&query).await?.secrets)
+    }
+
+    /// Lists the secrets that match a query, without their values. Set `limit` to get a page
+    /// at a time, and pass each page's `next_cursor` back as the `cursor` of the next query.
+    pub async fn list_secrets(&self, query: &ListSecretsQuery) -> Result<SecretPage, ClientError> {
         let jwt = match &self.credentials.jwt {
             Some(res) => res,
             None => todo!("Implement error here"),
         };
 
-        let json = json!({
-            "tag_filter": tag
-        });
-
         let response = self
             .ctx
-            .post(format!("{}{}", self.url, GET_SECRETS_URL))
+            .get(self.endpoint(SECRETS_URL, &[]))
+            .query(query)
             .header("Authorization", jwt)
-            .json(&json)
+            .header(ENVIRONMENT_HEADER, &self.environment)
             .send()
             .await?;
 
-        match response.status() {
-            StatusCode::OK => Ok(response.json::<Vec<SecretInfo>>().await?),
-            _ => Err(ClientError::RequestError(response.text().await?)),
+        if response.status() != StatusCode::OK {
+            return Err(ClientError::from_response(response).await);
         }
-    }
-}
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct SecretInfo {
-    pub key: String,
-    pub tags: Vec<String>,
-    pub access_level: i32,
-    pub role_whitelist: Vec<String>,
-}
+        let next_cursor = response
+            .headers()
+            .get(NEXT_CURSOR_HEADER)
+            .and_then(|x| x.to_str().ok())
+            .map(String::from);
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct AuthBody {
-    pub access_token: String,
-    pub token_type: String,
+        Ok(SecretPage {
+            secrets: response.json::<Vec<SecretInfo>>().await?,
+            next_cursor,
+        })
+    }
 }
 
 pub struct ClientBuilder {
     ctx: ReqClient,
     url: Option<Url>,
     credentials: Option<Credentials>,
+    environment: Option<String>,
 }
 
 impl ClientBuilder {
-    fn url(mut self, url: &str) -> Self {
+    pub fn url(mut self, url: &str) -> Self {
         let url = Url::parse(url).unwrap();
 
         self.url = Some(url);
         self
     }
 
-    fn credentials(mut self, api_key: &str) -> Self {
+    pub fn credentials(mut self, api_key: &str) -> Self {
         let creds = Credentials::new(api_key);
         self.credentials = Some(creds);
 
         self
     }
 
-    fn build(self) -> Client {
+    /// Reads secrets from an environment, such as `prod`, rather than the default one.
+    pub fn environment(mut self, environment: &str) -> Self {
+        self.environment = Some(environment.to_owned());
+
+        self
+    }
+
+    pub fn build(self) -> Client {
         if self.url.is_none() | self.credentials.is_none() {
             panic!("The URL or API key is unset!");
         }
@@ -165,12 +197,17 @@ impl ClientBuilder {
             ctx: self.ctx,
             url: self.url.unwrap(),
             credentials: self.credentials.unwrap(),
+            environment: self
+                .environment
+                .unwrap_or_else(|| DEFAULT_ENVIRONMENT.to_owned()),
         }
     }
 }
 
 #[derive(Clone)]
 pub struct Credentials {
+    // None of the endpoints that the SDK calls need the root key yet
+    #[allow(dead_code)]
     api_key: String,
     jwt: Option<String>,
 }
@@ -189,9 +226,26 @@ impl Credentials {
 }
 
 #[derive(thiserror::Error, Debug)]
-enum ClientError {
+pub enum ClientError {
     #[error("Reqwest error: {0}")]
     Reqwest(#[from] reqwest::Error),
     #[error("Error during HTTP request: {0}")]
     RequestError(String),
+    /// An error returned by the server. Match on its `code` rather than the message.
+    #[error("{0}")]
+    Api(ErrorBody),
+}
+
+impl ClientError {
+    async fn from_response(response: Response) -> Self {
+        let status = response.status();
+
+        match response.text().await {
+            Ok(text) => match serde_json::from_str::<ErrorBody>(&text) {
+                Ok(body) => Self::Api(body),
+                Err(_) => Self::RequestError(format!("{status}: {text}")),
+            },
+            Err(e) => Self::Reqwest(e),
+        }
+    }
 }
@@ -1,3 +1,3 @@
-pub const LOGIN_URL: &str = "/login";
-pub const GET_SECRETS_URL: &str = "/secrets/get";
-pub const GET_SECRETS_BY_TAG_URL: &str = "/secrets/get/by_tag";
+pub const LOGIN_URL: &str = "/v1/login";
+pub const SECRETS_URL: &str = "/v1/secrets";
+pub const TAGS_URL: &str = "/v1/tags";
@@ -1,2 +1,8 @@
 mod client;
 mod consts;
+
+pub use chamber_shared::errors::{ErrorBody, ErrorCode};
+pub use chamber_shared::secrets::{
+    ListSecretsQuery, SecretInfo, SecretPage, SecretPublic, SecretSort, SortOrder, TagMatch,
+};
+pub use client::{Client, ClientBuilder, ClientError, Credentials};
@@ -0,0 +1,20 @@
+-- Named policies granting capabilities on secrets, attached to users and roles.
+-- Each user's access level and roles still apply as an implicit policy, so existing data keeps working.
+CREATE TABLE IF NOT EXISTS policies (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL UNIQUE,
+    description VARCHAR NOT NULL DEFAULT '',
+	users TEXT[] NOT NULL DEFAULT array[]::TEXT[],
+	roles TEXT[] NOT NULL DEFAULT array[]::TEXT[],
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS policy_rules (
+    id SERIAL PRIMARY KEY,
+    policy_name VARCHAR NOT NULL REFERENCES policies(name) ON DELETE CASCADE ON UPDATE CASCADE,
+    path VARCHAR NOT NULL DEFAULT '*',
+	tags TEXT[] NOT NULL DEFAULT array[]::TEXT[],
+	max_access_level INT,
+	check_whitelist BOOLEAN NOT NULL DEFAULT false,
+	capabilities TEXT[] NOT NULL DEFAULT array[]::TEXT[]
+);
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS roles (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL UNIQUE,
+    description VARCHAR NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Roles used to be free text, so register every role that's already referenced somewhere.
+INSERT INTO roles (name)
+SELECT UNNEST(roles) FROM users
+UNION
+SELECT UNNEST(role_whitelist) FROM secrets
+UNION
+SELECT UNNEST(roles) FROM policies
+ON CONFLICT (name) DO NOTHING;
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS groups (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL UNIQUE,
+    description VARCHAR NOT NULL DEFAULT '',
+	members TEXT[] NOT NULL DEFAULT array[]::TEXT[],
+	subgroups TEXT[] NOT NULL DEFAULT array[]::TEXT[],
+	roles TEXT[] NOT NULL DEFAULT array[]::TEXT[],
+	access_level INT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
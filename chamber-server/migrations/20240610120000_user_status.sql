@@ -0,0 +1,3 @@
+ALTER TABLE users
+	ADD COLUMN IF NOT EXISTS disabled BOOLEAN NOT NULL DEFAULT false,
+	ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ;
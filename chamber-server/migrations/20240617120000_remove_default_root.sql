@@ -0,0 +1,5 @@
+-- The first admin is now created through the root-key-guarded bootstrap flow (`chamber init`).
+-- Only remove the seeded root user if it still has the default password.
+DELETE FROM users
+WHERE username = 'root'
+AND password = '$argon2id$v=19$m=16,t=2,p=1$aEFxcjZlUlYwS21nVTNWWA$S92gSdO/RSqgRgAUlNe3Rw';
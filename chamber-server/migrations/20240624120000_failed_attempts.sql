@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS failed_attempts (
+    id SERIAL PRIMARY KEY,
+    kind VARCHAR NOT NULL,
+    username VARCHAR,
+    ip VARCHAR,
+    reason VARCHAR NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS failed_attempts_created_at_idx ON failed_attempts (created_at);
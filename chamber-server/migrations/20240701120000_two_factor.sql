@@ -0,0 +1,10 @@
+ALTER TABLE users
+	ADD COLUMN IF NOT EXISTS totp_secret VARCHAR,
+	ADD COLUMN IF NOT EXISTS totp_pending_secret VARCHAR,
+	ADD COLUMN IF NOT EXISTS totp_last_step BIGINT,
+	ADD COLUMN IF NOT EXISTS recovery_codes TEXT[] NOT NULL DEFAULT array[]::TEXT[];
+
+CREATE TABLE IF NOT EXISTS settings (
+    name VARCHAR PRIMARY KEY,
+    value VARCHAR NOT NULL
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS ssh_keys (
+    fingerprint VARCHAR PRIMARY KEY,
+    username VARCHAR NOT NULL,
+    public_key VARCHAR NOT NULL,
+    comment VARCHAR NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS ssh_keys_username_idx ON ssh_keys (username);
@@ -0,0 +1,77 @@
+-- Every secret, user, role, group and policy belongs to a namespace, and names only have to be
+-- unique within one. Everything that already exists moves into the default namespace.
+-- Deleting a namespace deletes everything in it.
+CREATE TABLE IF NOT EXISTS namespaces (
+    name VARCHAR PRIMARY KEY,
+    description VARCHAR NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO namespaces (name) VALUES ('default') ON CONFLICT (name) DO NOTHING;
+
+ALTER TABLE secrets ADD COLUMN IF NOT EXISTS namespace VARCHAR NOT NULL DEFAULT 'default'
+	REFERENCES namespaces(name) ON DELETE CASCADE;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS namespace VARCHAR NOT NULL DEFAULT 'default'
+	REFERENCES namespaces(name) ON DELETE CASCADE;
+ALTER TABLE roles ADD COLUMN IF NOT EXISTS namespace VARCHAR NOT NULL DEFAULT 'default'
+	REFERENCES namespaces(name) ON DELETE CASCADE;
+ALTER TABLE groups ADD COLUMN IF NOT EXISTS namespace VARCHAR NOT NULL DEFAULT 'default'
+	REFERENCES namespaces(name) ON DELETE CASCADE;
+ALTER TABLE policies ADD COLUMN IF NOT EXISTS namespace VARCHAR NOT NULL DEFAULT 'default'
+	REFERENCES namespaces(name) ON DELETE CASCADE;
+ALTER TABLE policy_rules ADD COLUMN IF NOT EXISTS namespace VARCHAR NOT NULL DEFAULT 'default';
+ALTER TABLE ssh_keys ADD COLUMN IF NOT EXISTS namespace VARCHAR NOT NULL DEFAULT 'default'
+	REFERENCES namespaces(name) ON DELETE CASCADE;
+
+-- The server always says which namespace a row goes in, so a missing one is a bug rather than
+-- something to quietly put in the default namespace.
+ALTER TABLE secrets ALTER COLUMN namespace DROP DEFAULT;
+ALTER TABLE users ALTER COLUMN namespace DROP DEFAULT;
+ALTER TABLE roles ALTER COLUMN namespace DROP DEFAULT;
+ALTER TABLE groups ALTER COLUMN namespace DROP DEFAULT;
+ALTER TABLE policies ALTER COLUMN namespace DROP DEFAULT;
+ALTER TABLE policy_rules ALTER COLUMN namespace DROP DEFAULT;
+ALTER TABLE ssh_keys ALTER COLUMN namespace DROP DEFAULT;
+
+ALTER TABLE secrets DROP CONSTRAINT IF EXISTS secrets_key_key;
+ALTER TABLE secrets ADD CONSTRAINT secrets_namespace_key_key UNIQUE (namespace, key);
+
+ALTER TABLE roles DROP CONSTRAINT IF EXISTS roles_name_key;
+ALTER TABLE roles ADD CONSTRAINT roles_namespace_name_key UNIQUE (namespace, name);
+
+ALTER TABLE groups DROP CONSTRAINT IF EXISTS groups_name_key;
+ALTER TABLE groups ADD CONSTRAINT groups_namespace_name_key UNIQUE (namespace, name);
+
+ALTER TABLE policy_rules DROP CONSTRAINT IF EXISTS policy_rules_policy_name_fkey;
+ALTER TABLE policies DROP CONSTRAINT IF EXISTS policies_name_key;
+ALTER TABLE policies ADD CONSTRAINT policies_namespace_name_key UNIQUE (namespace, name);
+ALTER TABLE policy_rules ADD CONSTRAINT policy_rules_policy_fkey
+	FOREIGN KEY (namespace, policy_name) REFERENCES policies(namespace, name)
+	ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE ssh_keys DROP CONSTRAINT IF EXISTS ssh_keys_pkey;
+ALTER TABLE ssh_keys ADD PRIMARY KEY (namespace, fingerprint);
+DROP INDEX IF EXISTS ssh_keys_username_idx;
+CREATE INDEX IF NOT EXISTS ssh_keys_namespace_username_idx ON ssh_keys (namespace, username);
+
+-- Usernames weren't unique before, but logins and tokens only ever name a user. Which of two
+-- users with the same name should keep it can't be guessed, so the migration stops and says who
+-- they are rather than deleting or renaming anyone.
+DO $$
+DECLARE
+	duplicates TEXT;
+BEGIN
+	SELECT string_agg(DISTINCT username, ', ' ORDER BY username) INTO duplicates
+	FROM (
+		SELECT username FROM users GROUP BY namespace, username HAVING COUNT(*) > 1
+	) AS duplicated;
+
+	IF duplicates IS NOT NULL THEN
+		RAISE EXCEPTION 'More than one user has the same username: %', duplicates
+			USING HINT = 'Rename or delete all but one of each of them, then start the server again.';
+	END IF;
+END $$;
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_namespace_username_idx ON users (namespace, username);
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS namespace_admin BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,6 @@
+-- Each namespace's secrets are sealed with its own data key. The keys are kept with the keyfile
+-- rather than here, so that deleting a namespace's key leaves any copies of its ciphertexts, such
+-- as those in database backups, unreadable. Namespaces get a key, and have their secrets
+-- re-encrypted with it, the first time that one is needed, since only the server has the crypto
+-- key to wrap it with. This records which namespaces have been re-encrypted.
+ALTER TABLE namespaces ADD COLUMN IF NOT EXISTS has_data_key BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,29 @@
+-- A secret has a value in each environment that it's used in, such as `staging` and `prod`, and
+-- keys only have to be unique within an environment. Everything that already exists moves into
+-- the default environment.
+ALTER TABLE secrets ADD COLUMN IF NOT EXISTS environment VARCHAR NOT NULL DEFAULT 'default';
+ALTER TABLE secrets ALTER COLUMN environment DROP DEFAULT;
+
+ALTER TABLE secrets DROP CONSTRAINT IF EXISTS secrets_namespace_key_key;
+ALTER TABLE secrets ADD CONSTRAINT secrets_namespace_environment_key_key
+	UNIQUE (namespace, environment, key);
+
+-- Signatures are deterministic, so a value that's been promoted to another environment has the
+-- same signature in both.
+ALTER TABLE secrets DROP CONSTRAINT IF EXISTS secrets_sig_key;
+
+-- Rules with environments only match secrets in one of them.
+ALTER TABLE policy_rules ADD COLUMN IF NOT EXISTS environments TEXT[] NOT NULL DEFAULT array[]::TEXT[];
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id SERIAL PRIMARY KEY,
+    namespace VARCHAR NOT NULL REFERENCES namespaces(name) ON DELETE CASCADE,
+    action VARCHAR NOT NULL,
+    username VARCHAR NOT NULL,
+    key VARCHAR NOT NULL,
+    from_environment VARCHAR,
+    environment VARCHAR NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_namespace_created_at_idx ON audit_log (namespace, created_at);
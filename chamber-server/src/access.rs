@@ -0,0 +1,94 @@
+use axum::{extract::State, Json};
+use chamber_shared::policy::{
+    Capability, Decision, SecretAccess, SecretAccessParams, SimulateParams, UserAccess,
+    UserAccessParams,
+};
+use std::sync::Arc;
+
+use crate::auth::NamespaceAdmin;
+use crate::environments::Environment;
+use crate::errors::ApiError;
+
+use chamber_core::core::Database;
+use chamber_core::traits::AppState;
+
+#[utoipa::path(
+    post,
+    path = "/v1/access/secret",
+    tag = "access",
+    params(Environment),
+    request_body = SecretAccessParams,
+    responses((status = 200, body = SecretAccess)),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn explain_secret_access<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Environment(environment): Environment,
+    Json(SecretAccessParams { key }): Json<SecretAccessParams>,
+) -> Result<Json<SecretAccess>, ApiError> {
+    let db = state
+        .db()
+        .in_namespace(&namespace)
+        .in_environment(&environment);
+
+    let res = db.explain_secret_access(key).await?;
+
+    Ok(Json(res))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/access/user",
+    tag = "access",
+    params(Environment),
+    request_body = UserAccessParams,
+    responses((status = 200, body = UserAccess)),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn explain_user_access<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Environment(environment): Environment,
+    Json(UserAccessParams { username }): Json<UserAccessParams>,
+) -> Result<Json<UserAccess>, ApiError> {
+    let db = state
+        .db()
+        .in_namespace(&namespace)
+        .in_environment(&environment);
+
+    let res = db.explain_user_access(username).await?;
+
+    Ok(Json(res))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/access/simulate",
+    tag = "access",
+    params(Environment),
+    request_body = SimulateParams,
+    responses((status = 200, body = Decision)),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn simulate_access<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Environment(environment): Environment,
+    Json(params): Json<SimulateParams>,
+) -> Result<Json<Decision>, ApiError> {
+    let db = state
+        .db()
+        .in_namespace(&namespace)
+        .in_environment(&environment);
+
+    let res = db
+        .simulate_access(
+            params.username,
+            params.key,
+            params.capability.unwrap_or(Capability::Read),
+        )
+        .await?;
+
+    Ok(Json(res))
+}
@@ -1,26 +1,35 @@
 use axum::{
     async_trait,
-    extract::{FromRequestParts, State},
-    http::{request::Parts, StatusCode},
+    extract::{ConnectInfo, FromRequestParts, State},
+    http::{request::Parts, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
-    Json, RequestPartsExt,
+    Extension, Json, RequestPartsExt,
 };
 use axum_extra::TypedHeader;
 use axum_extra::headers::{authorization::Bearer, Authorization};
 use chamber_core::errors::DatabaseError;
+use chamber_shared::attempts::{FailedAttempt, FailedAttemptsParams, MAX_FAILED_ATTEMPTS};
+use chamber_shared::errors::{ErrorBody, ErrorCode};
+use chamber_shared::namespaces::{DEFAULT_NAMESPACE, NAMESPACE_HEADER};
+use chamber_shared::users::{CertLoginParams, UserLoginParams};
 use chamber_shared::AuthBody;
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use once_cell::sync::Lazy;
 use ring::rand::SecureRandom;
 use ring::rand::SystemRandom;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 use std::fmt::Display;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use chamber_core::core::Database;
 use chamber_core::traits::AppState;
+use chamber_core::users::User;
+
+use crate::errors::{error_response, ApiError};
+use crate::external::{self, ExternalAuthError};
+use crate::header::ChamberHeader;
 
 static KEYS: Lazy<Keys> = Lazy::new(|| {
     let random = SystemRandom::new();
@@ -29,96 +38,569 @@ static KEYS: Lazy<Keys> = Lazy::new(|| {
     Keys::new(&secret)
 });
 
-#[derive(Deserialize)]
-pub struct UserLoginParams {
-    username: String,
-    password: String,
-}
-
+#[utoipa::path(
+    post,
+    path = "/v1/login",
+    tag = "auth",
+    request_body = UserLoginParams,
+    responses((status = 200, body = AuthBody)),
+)]
 pub async fn login<S: AppState>(
     State(state): State<Arc<S>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
     Json(user): Json<UserLoginParams>,
 ) -> Result<(StatusCode, Json<AuthBody>), AuthError> {
-    let state = state.db();
     // Check if the user sent the credentials
     if user.username.is_empty() | user.password.is_empty() {
         return Err(AuthError::MissingCredentials);
     }
+
+    let namespace = user
+        .namespace
+        .unwrap_or_else(|| DEFAULT_NAMESPACE.to_string());
+    let db = state.db().in_namespace(&namespace);
+
+    let ip = client_ip(&headers, connect_info);
+    let ip_key = format!("ip:{ip}");
+    let username = qualified_name(&namespace, &user.username);
+    let user_key = format!("user:{username}");
+
+    let limits = state.auth_limits();
+    let ip_wait = limits.login.check(&ip_key);
+    if let Some(wait) = ip_wait.or(limits.login.check(&user_key)) {
+        return Err(AuthError::TooManyAttempts(wait));
+    }
+
     // Here you can check the user credentials from a database
-    let returned_user = match state.get_user_from_name(user.username).await {
+    let verified = match db.get_user_from_name(user.username.clone()).await {
+        Ok(res) => res.verify(&user.password).await.map(|_| res),
+        Err(e) => Err(e),
+    };
+
+    let mut returned_user = match verified {
         Ok(res) => res,
-        Err(e) => return Err(AuthError::WrongCredentials(e)),
-    }; 
+        Err(e) => return Err(fail_login(state.as_ref(), "login", username, ip, e).await),
+    };
+
+    let mfa =
+        check_second_factor(state.as_ref(), &mut returned_user, user.code, "login", ip).await?;
+
+    limits.login.reset(&user_key);
+
+    returned_user
+        .check_active()
+        .map_err(AuthError::InactiveUser)?;
+
+    // Upgrade hashes made with older parameters while we have the plaintext password
+    let hash_config = state.hash_config();
+    if hash_config.needs_rehash(&returned_user.password) {
+        let rehashed = match returned_user
+            .set_password(&hash_config, &user.password)
+            .await
+        {
+            Ok(()) => db.update_user_password(returned_user.clone()).await,
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = rehashed {
+            tracing::warn!(
+                "Couldn't rehash the password for {}: {e}",
+                returned_user.username
+            );
+        }
+    }
+
+    // Send the authorized token
+    Ok((StatusCode::OK, Json(issue_token(&returned_user, mfa)?)))
+}
+
+/// The identity from a client certificate that was verified during the TLS handshake.
+/// Only set when the server terminates TLS itself with a client CA configured.
+#[derive(Clone, Debug)]
+pub struct ClientCertificate {
+    pub username: String,
+    /// The CA is shared by every namespace, so certificates say which one they're for.
+    pub namespace: String,
+    pub subject: String,
+}
+
+/// Exchanges a verified client certificate for a token, so that workloads don't need a password.
+#[utoipa::path(
+    post,
+    path = "/v1/login/cert",
+    tag = "auth",
+    request_body(content = Option<CertLoginParams>),
+    responses((status = 200, body = AuthBody)),
+)]
+pub async fn cert_login<S: AppState>(
+    State(state): State<Arc<S>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    cert: Option<Extension<Option<ClientCertificate>>>,
+    params: Option<Json<CertLoginParams>>,
+) -> Result<(StatusCode, Json<AuthBody>), AuthError> {
+    let Some(Extension(Some(cert))) = cert else {
+        return Err(AuthError::MissingClientCertificate);
+    };
+
+    let (namespace, code) = match params {
+        Some(Json(x)) => (x.namespace, x.code),
+        None => (None, None),
+    };
+
+    // Otherwise a certificate for a user in one namespace would log in as any user with the same
+    // name in another
+    if namespace.is_some_and(|x| x != cert.namespace) {
+        return Err(AuthError::WrongCertificateNamespace);
+    }
+    let namespace = cert.namespace.clone();
+
+    let ip = client_ip(&headers, connect_info);
+    let username = qualified_name(&namespace, &cert.username);
+    let user_key = format!("user:{username}");
+
+    let limits = state.auth_limits();
+    let ip_wait = limits.login.check(&format!("ip:{ip}"));
+    if let Some(wait) = ip_wait.or(limits.login.check(&user_key)) {
+        return Err(AuthError::TooManyAttempts(wait));
+    }
+
+    // The CA vouches for the certificate, so all that's left is making sure the user exists
+    let db = state.db().in_namespace(&namespace);
+    let mut user = match db.get_user_from_name(cert.username.clone()).await {
+        Ok(res) => res,
+        Err(e) => return Err(fail_login(state.as_ref(), "cert_login", username, ip, e).await),
+    };
+
+    let mfa = check_second_factor(state.as_ref(), &mut user, code, "cert_login", ip).await?;
 
-    if let Err(e) = returned_user.verify(&user.password) {
-       return Err(AuthError::WrongCredentials(e)); 
+    limits.login.reset(&user_key);
+
+    user.check_active().map_err(AuthError::InactiveUser)?;
+
+    tracing::info!("{username} logged in with certificate {}", cert.subject);
+
+    Ok((StatusCode::OK, Json(issue_token(&user, mfa)?)))
+}
+
+/// Counts a failed login against both the client's IP and the username, and records it.
+/// The username should be qualified with [`qualified_name`].
+pub async fn fail_login<S: AppState>(
+    state: &S,
+    kind: &str,
+    username: String,
+    ip: String,
+    e: DatabaseError,
+) -> AuthError {
+    let limits = state.auth_limits();
+    limits.login.record_failure(&format!("ip:{ip}"));
+    limits.login.record_failure(&format!("user:{username}"));
+    record_failed_attempt(state, kind, Some(username), ip, &e).await;
+
+    AuthError::WrongCredentials(e)
+}
+
+/// Checks the second factor of users who have enabled two-factor authentication.
+/// Returns whether one was used, which ends up in the token's claims.
+pub async fn check_second_factor<S: AppState>(
+    state: &S,
+    user: &mut User,
+    code: Option<String>,
+    kind: &str,
+    ip: String,
+) -> Result<bool, AuthError> {
+    if !user.two_factor_enabled() {
+        return Ok(false);
     }
 
-    // 24 hour timer
+    let Some(code) = code else {
+        return Err(AuthError::TwoFactorRequired);
+    };
+
+    if let Err(e) = user.verify_second_factor(&code) {
+        let username = qualified_name(user.namespace(), &user.username);
+        return Err(fail_login(state, kind, username, ip, e).await);
+    }
+
+    // Stops the code from being used again
+    state
+        .db()
+        .in_namespace(user.namespace())
+        .update_user_two_factor(user.clone())
+        .await
+        .map_err(|_| AuthError::TokenCreation)?;
+
+    Ok(true)
+}
+
+/// Names a user across namespaces, for login limits and failed attempts.
+pub fn qualified_name(namespace: &str, username: &str) -> String {
+    format!("{namespace}/{username}")
+}
+
+/// Creates a token that's valid for 24 hours, tied to the user's namespace.
+pub fn issue_token(user: &User, mfa: bool) -> Result<AuthBody, AuthError> {
     let exp = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
+        .map_err(|_| AuthError::TokenCreation)?
         .as_secs()
         + 86400;
 
     let claims = Claims {
-        sub: returned_user.username.to_owned(),
-        exp: exp.try_into().unwrap(), 
+        namespace: user.namespace().to_owned(),
+        sub: user.username.clone(),
+        exp: exp.try_into().map_err(|_| AuthError::TokenCreation)?,
+        mfa,
     };
     // Create the authorization token
     let token = encode(&Header::default(), &claims, &KEYS.encoding)
         .map_err(|_| AuthError::TokenCreation)?;
 
-    // Send the authorized token
-    Ok((StatusCode::OK, Json(AuthBody::new(token))))
+    Ok(AuthBody::new(token))
+}
+
+/// Whether the user's effective access level is above the level that requires two-factor authentication.
+pub async fn two_factor_required<S: AppState>(
+    state: &S,
+    user: &User,
+) -> Result<bool, DatabaseError> {
+    let Some(required_above) = state.db().view_two_factor_level().await? else {
+        return Ok(false);
+    };
+
+    let groups = state
+        .db()
+        .in_namespace(user.namespace())
+        .view_groups_for_user(user.username.clone())
+        .await?;
+
+    Ok(user.clone().with_groups(&groups).access_level() > required_above)
+}
+
+/// Checks the root key sent in the `x-chamber-key` header against the current keyfile.
+/// Wrong keys count towards the same backoff as `/unseal` for the client's IP.
+pub fn check_root_key<S: AppState>(
+    state: &S,
+    header: ChamberHeader,
+    ip: &str,
+) -> Result<(), ApiError> {
+    let limit_key = unseal_limit_key(ip);
+
+    let limits = state.auth_limits();
+    if let Some(wait) = limits.unseal.check(&limit_key) {
+        return Err(ApiError::TooManyRequests(wait));
+    }
+
+    if header.key() != state.get_keyfile()?.unseal_key() {
+        limits.unseal.record_failure(&limit_key);
+        tracing::warn!("Request from {ip} had the wrong root key");
+        return Err(ApiError::Forbidden);
+    }
+
+    limits.unseal.reset(&limit_key);
+
+    Ok(())
+}
+
+/// Whoever is managing a namespace's users, roles, groups and policies. That's either the holder
+/// of the root key, who picks the namespace with the `x-chamber-namespace` header, or one of the
+/// namespace's admins, who can only manage their own.
+#[derive(Debug)]
+pub struct NamespaceAdmin(pub String);
+
+#[async_trait]
+impl<S> FromRequestParts<Arc<S>> for NamespaceAdmin
+where
+    S: AppState,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<S>,
+    ) -> Result<Self, Self::Rejection> {
+        let namespace = match parts.headers.get(NAMESPACE_HEADER) {
+            Some(value) => match value.to_str() {
+                Ok(value) => Some(value.to_string()),
+                Err(_) => {
+                    let e = ApiError::BadRequest("Invalid namespace header".to_string());
+                    return Err(e.into_response());
+                }
+            },
+            None => None,
+        };
+
+        if let Ok(TypedHeader(key)) = parts.extract::<TypedHeader<ChamberHeader>>().await {
+            let connect_info = parts.extensions.get::<ConnectInfo<SocketAddr>>().cloned();
+            let ip = client_ip(&parts.headers, connect_info);
+            check_root_key(state.as_ref(), key, &ip).map_err(IntoResponse::into_response)?;
+
+            // Otherwise a typo would show up as an empty namespace
+            let namespace = namespace.unwrap_or_else(|| DEFAULT_NAMESPACE.to_string());
+            if let Err(e) = state.db().get_namespace(namespace.clone()).await {
+                return Err(ApiError::from(e).into_response());
+            }
+
+            return Ok(Self(namespace));
+        }
+
+        let (_, user) = enrolled_claims_from_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        if !user.is_namespace_admin() || namespace.is_some_and(|x| x != user.namespace()) {
+            return Err(ApiError::Forbidden.into_response());
+        }
+
+        Ok(Self(user.namespace().to_string()))
+    }
+}
+
+/// Root key checks and unseals are limited per client, so that wrong keys from anyone else
+/// can't lock the operator out.
+pub fn unseal_limit_key(ip: &str) -> String {
+    format!("ip:{ip}")
+}
+
+/// Finds the client's IP. `X-Forwarded-For` is only believed when the request came through one
+/// of the [trusted proxies](crate::proxies).
+pub fn client_ip(headers: &HeaderMap, connect_info: Option<ConnectInfo<SocketAddr>>) -> String {
+    crate::proxies::get().client_ip(headers, connect_info.map(|ConnectInfo(x)| x.ip()))
+}
+
+/// Stores a failed attempt for admins to review. Failing to store it shouldn't fail the request.
+pub async fn record_failed_attempt<S: AppState>(
+    state: &S,
+    kind: &str,
+    username: Option<String>,
+    ip: String,
+    reason: &impl Display,
+) {
+    let attempt = FailedAttempt {
+        kind: kind.to_string(),
+        username,
+        ip: Some(ip),
+        reason: reason.to_string(),
+        created_at: chrono::Utc::now(),
+    };
+
+    if let Err(e) = state.db().record_failed_attempt(attempt).await {
+        tracing::warn!("Couldn't record a failed {kind} attempt: {e}");
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/failed_attempts",
+    tag = "admin",
+    request_body(content = Option<FailedAttemptsParams>),
+    responses((status = 200, body = [FailedAttempt])),
+    security(("root_key" = [])),
+)]
+pub async fn view_failed_attempts<S: AppState>(
+    State(state): State<Arc<S>>,
+    TypedHeader(auth): TypedHeader<ChamberHeader>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    params: Option<Json<FailedAttemptsParams>>,
+) -> Result<Json<Vec<FailedAttempt>>, ApiError> {
+    check_root_key(state.as_ref(), auth, &client_ip(&headers, connect_info))?;
+
+    let limit = params
+        .and_then(|Json(x)| x.limit)
+        .map_or(100, |x| x.clamp(1, MAX_FAILED_ATTEMPTS));
+
+    let res = state.db().view_failed_attempts(limit).await?;
+
+    Ok(Json(res))
 }
 
 impl Display for Claims {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Name: {}", self.sub)
+        write!(f, "Name: {}/{}", self.namespace, self.sub)
     }
 }
 
+/// Decodes the bearer token and makes sure that its user can still log in.
+async fn claims_from_parts<S: AppState>(
+    parts: &mut Parts,
+    state: &Arc<S>,
+) -> Result<(Claims, User), AuthError> {
+    // Extract the token from the authorization header
+    let TypedHeader(Authorization(bearer)) = parts
+        .extract::<TypedHeader<Authorization<Bearer>>>()
+        .await
+        .map_err(|_| AuthError::InvalidToken)?;
+    // Decode the user data
+    let token_data = decode::<Claims>(bearer.token(), &KEYS.decoding, &Validation::default())
+        .map_err(|_| AuthError::InvalidToken)?;
+
+    // Tokens outlive changes to the user, so make sure that they can still log in
+    let user = state
+        .db()
+        .in_namespace(&token_data.claims.namespace)
+        .get_user_from_name(token_data.claims.sub.clone())
+        .await
+        .map_err(|_| AuthError::InvalidToken)?;
+
+    user.check_active().map_err(AuthError::InactiveUser)?;
+
+    Ok((token_data.claims, user))
+}
+
 #[async_trait]
-impl<S> FromRequestParts<S> for Claims
+impl<S> FromRequestParts<Arc<S>> for Claims
 where
-    S: Send + Sync,
+    S: AppState,
 {
     type Rejection = AuthError;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        // Extract the token from the authorization header
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<S>,
+    ) -> Result<Self, Self::Rejection> {
+        let (claims, _) = enrolled_claims_from_parts(parts, state).await?;
+
+        Ok(claims)
+    }
+}
+
+/// Like [`claims_from_parts`], but users above the configured level have to enroll in two-factor
+/// authentication before they can do anything else.
+async fn enrolled_claims_from_parts<S: AppState>(
+    parts: &mut Parts,
+    state: &Arc<S>,
+) -> Result<(Claims, User), AuthError> {
+    let (claims, user) = claims_from_parts(parts, state).await?;
+
+    if !claims.mfa
+        && two_factor_required(state.as_ref(), &user)
+            .await
+            .map_err(|_| AuthError::InvalidToken)?
+    {
+        return Err(AuthError::TwoFactorEnrollmentRequired);
+    }
+
+    Ok((claims, user))
+}
+
+/// The user that a request to the secrets endpoints is made as. As well as Chamber's own tokens,
+/// this accepts tokens from the configured external providers, whose users only exist for the request.
+pub struct AuthUser(pub User);
+
+impl AuthUser {
+    /// A database handle that only sees the user's namespace.
+    pub fn db<S: AppState>(&self, state: &S) -> S::D {
+        state.db().in_namespace(self.0.namespace())
+    }
+}
+
+impl std::fmt::Debug for AuthUser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AuthUser({})", self.0.username)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<Arc<S>> for AuthUser
+where
+    S: AppState,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<S>,
+    ) -> Result<Self, Self::Rejection> {
         let TypedHeader(Authorization(bearer)) = parts
             .extract::<TypedHeader<Authorization<Bearer>>>()
             .await
             .map_err(|_| AuthError::InvalidToken)?;
-        // Decode the user data
-        let token_data = decode::<Claims>(bearer.token(), &KEYS.decoding, &Validation::default())
-            .map_err(|_| AuthError::InvalidToken)?;
 
-        Ok(token_data.claims)
+        if !external::is_external(bearer.token()) {
+            let (_, user) = enrolled_claims_from_parts(parts, state).await?;
+
+            return Ok(Self(user));
+        }
+
+        let Some(auth) = external::get() else {
+            return Err(AuthError::InvalidToken);
+        };
+
+        let user = auth
+            .authenticate(bearer.token())
+            .await
+            .map_err(AuthError::ExternalToken)?;
+
+        Ok(Self(user))
+    }
+}
+
+/// Like [`Claims`], but also accepts tokens from users who still need to enroll in two-factor
+/// authentication. Only used by the endpoints that let them view themselves and enroll.
+pub struct EnrollmentClaims(pub Claims);
+
+#[async_trait]
+impl<S> FromRequestParts<Arc<S>> for EnrollmentClaims
+where
+    S: AppState,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<S>,
+    ) -> Result<Self, Self::Rejection> {
+        let (claims, _) = claims_from_parts(parts, state).await?;
+
+        Ok(Self(claims))
     }
 }
 
 impl IntoResponse for AuthError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
+        let (code, message) = match self {
+            // Why the credentials were wrong could tell the caller whether the user exists
             AuthError::WrongCredentials(e) => {
-                (StatusCode::UNAUTHORIZED, format!("Wrong credentials: {e}"))
+                tracing::warn!("Wrong credentials: {e}");
+
+                (ErrorCode::Unauthorised, "Wrong credentials".to_string())
             }
+            AuthError::InactiveUser(e) => (ErrorCode::Forbidden, e.to_string()),
             AuthError::MissingCredentials => {
-                (StatusCode::BAD_REQUEST, "Missing credentials".to_string())
+                (ErrorCode::BadRequest, "Missing credentials".to_string())
+            }
+            AuthError::TokenCreation => {
+                tracing::error!("Couldn't create a token");
+
+                (ErrorCode::Internal, "Token creation error".to_string())
             }
-            AuthError::TokenCreation => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Token creation error".to_string(),
+            AuthError::InvalidToken => (ErrorCode::Unauthorised, "Invalid token".to_string()),
+            AuthError::ExternalToken(e) => (ErrorCode::Unauthorised, e.to_string()),
+            AuthError::MissingClientCertificate => (
+                ErrorCode::Unauthorised,
+                "A client certificate signed by the configured CA is required".to_string(),
             ),
-            AuthError::InvalidToken => (StatusCode::BAD_REQUEST, "Invalid token".to_string()),
+            AuthError::WrongCertificateNamespace => (
+                ErrorCode::Forbidden,
+                "The client certificate is for another namespace".to_string(),
+            ),
+            AuthError::TwoFactorRequired => (
+                ErrorCode::TwoFactorRequired,
+                "A two-factor code is required".to_string(),
+            ),
+            AuthError::TwoFactorEnrollmentRequired => (
+                ErrorCode::TwoFactorEnrollmentRequired,
+                "Two-factor authentication is required for your access level, enroll with /me/2fa/enroll"
+                    .to_string(),
+            ),
+            AuthError::TooManyAttempts(wait) => {
+                return ApiError::TooManyRequests(wait).into_response();
+            }
         };
-        let body = Json(json!({
-            "error": error_message,
-        }));
-        (status, body).into_response()
+
+        error_response(ErrorBody::new(code, message))
     }
 }
 
@@ -138,14 +620,26 @@ impl Keys {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
+    /// The namespace of the user, which is the only one that the token can see.
+    pub namespace: String,
     pub sub: String,
     exp: usize,
+    /// Whether the login used a second factor.
+    #[serde(default)]
+    pub mfa: bool,
 }
 
 #[derive(Debug)]
 pub enum AuthError {
     WrongCredentials(DatabaseError),
+    InactiveUser(DatabaseError),
     MissingCredentials,
     TokenCreation,
     InvalidToken,
+    TwoFactorRequired,
+    TwoFactorEnrollmentRequired,
+    TooManyAttempts(Duration),
+    MissingClientCertificate,
+    WrongCertificateNamespace,
+    ExternalToken(ExternalAuthError),
 }
@@ -1,8 +1,11 @@
 use shuttle_persist::PersistInstance;
 use sqlx::PgPool;
 
+use chamber_core::hashing::HashConfig;
 use chamber_core::traits::AppState;
 use chamber_core::traits::ShuttleAppState;
+use chamber_server::external::{self, ExternalAuth};
+use chamber_server::proxies::{self, TrustedProxies};
 use chamber_server::router::init_router;
 
 #[shuttle_runtime::main]
@@ -12,9 +15,18 @@ async fn main(
 ) -> shuttle_axum::ShuttleAxum {
     sqlx::migrate!().run(&db).await.unwrap();
 
-    let state = ShuttleAppState::new(db, persist);
+    let hash_config = HashConfig::from_env().unwrap();
 
-    state.check_keyfile_exists();
+    if let Some(auth) = ExternalAuth::from_env().await.unwrap() {
+        external::init(auth);
+    }
+
+    // Shuttle's proxy is the only way in, and the server doesn't see its address
+    proxies::init(TrustedProxies::All);
+
+    let state = ShuttleAppState::new(db, persist, hash_config);
+
+    state.check_keyfile_exists().unwrap();
 
     let router = init_router(state);
 
@@ -0,0 +1,66 @@
+use shuttle_persist::PersistInstance;
+use sqlx::PgPool;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+use chamber_core::hashing::HashConfig;
+use chamber_core::traits::AppState;
+use chamber_core::traits::ShuttleAppState;
+use chamber_server::external::{self, ExternalAuth};
+use chamber_server::proxies::{self, TrustedProxies};
+use chamber_server::router::init_router;
+use chamber_server::tls::{self, TlsConfig, TLS_CERT_VAR, TLS_KEY_VAR};
+
+/// Runs Chamber without Shuttle, terminating TLS itself when it's configured.
+#[tokio::main]
+async fn main() {
+    let db_url = std::env::var("DATABASE_URL").expect("DATABASE_URL needs to be set");
+    let db = PgPool::connect(&db_url).await.unwrap();
+
+    sqlx::migrate!().run(&db).await.unwrap();
+
+    let persist_dir =
+        std::env::var("CHAMBER_PERSIST_DIR").unwrap_or_else(|_| "persist".to_string());
+    let persist = PersistInstance::new(persist_dir.into()).unwrap();
+
+    let hash_config = HashConfig::from_env().unwrap();
+
+    if let Some(auth) = ExternalAuth::from_env().await.unwrap() {
+        external::init(auth);
+    }
+
+    proxies::init(TrustedProxies::from_env().unwrap());
+
+    let state = ShuttleAppState::new(db, persist, hash_config);
+
+    state.check_keyfile_exists().unwrap();
+
+    let router = init_router(state);
+
+    let addr = std::env::var("CHAMBER_ADDR").unwrap_or_else(|_| "0.0.0.0:8000".to_string());
+    let listener = TcpListener::bind(&addr).await.unwrap();
+
+    match TlsConfig::from_env().unwrap() {
+        Some(config) => {
+            let server_config = config.server_config().unwrap();
+
+            if config.client_ca.is_some() {
+                println!("Client certificates can be used to log in with /login/cert");
+            }
+
+            println!("Listening on https://{addr}");
+            tls::serve(listener, server_config, router).await;
+        }
+        None => {
+            println!(
+                "Listening on http://{addr}. Set {TLS_CERT_VAR} and {TLS_KEY_VAR} unless a proxy terminates TLS for you."
+            );
+            axum::serve(
+                listener,
+                router.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .unwrap();
+        }
+    }
+}
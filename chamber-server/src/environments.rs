@@ -0,0 +1,140 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query, State},
+    http::{request::Parts, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chamber_crypto::secrets::U64Wrapper;
+use chamber_shared::audit::{AuditEntry, AuditLogQuery, MAX_AUDIT_ENTRIES};
+use chamber_shared::environments::{
+    check_environment_name, PromoteSecretParams, DEFAULT_ENVIRONMENT, ENVIRONMENT_HEADER,
+};
+use std::sync::Arc;
+use utoipa::openapi::path::{Parameter, ParameterBuilder, ParameterIn};
+use utoipa::openapi::{ObjectBuilder, Required, SchemaType};
+use utoipa::IntoParams;
+
+use crate::auth::{AuthUser, NamespaceAdmin};
+use crate::errors::ApiError;
+use crate::keys::data_key;
+
+use chamber_core::core::Database;
+use chamber_core::traits::AppState;
+
+/// The environment that a secrets request acts on, from the `x-chamber-environment` header.
+/// Requests without one act on the default environment.
+#[derive(Debug)]
+pub struct Environment(pub String);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Environment
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(value) = parts.headers.get(ENVIRONMENT_HEADER) else {
+            return Ok(Self(DEFAULT_ENVIRONMENT.to_string()));
+        };
+
+        let environment = value
+            .to_str()
+            .map_err(|_| ApiError::BadRequest("Invalid environment header".to_string()))?;
+
+        check_environment_name(environment).map_err(ApiError::BadRequest)?;
+
+        Ok(Self(environment.to_string()))
+    }
+}
+
+/// Documents the header on the routes that take it.
+impl IntoParams for Environment {
+    fn into_params(_: impl Fn() -> Option<ParameterIn>) -> Vec<Parameter> {
+        vec![ParameterBuilder::new()
+            .name(ENVIRONMENT_HEADER)
+            .parameter_in(ParameterIn::Header)
+            .required(Required::False)
+            .description(Some(
+                "The environment, such as `prod`. Defaults to `default`",
+            ))
+            .schema(Some(ObjectBuilder::new().schema_type(SchemaType::String)))
+            .build()]
+    }
+}
+
+/// Copies a secret's value from one environment to another, such as from `staging` to `prod`,
+/// and records it in the namespace's audit log. The user needs to be able to read the secret in
+/// the first environment, and to update it, or create it, in the second.
+#[utoipa::path(
+    post,
+    path = "/v1/environments/promote",
+    tag = "secrets",
+    request_body = PromoteSecretParams,
+    responses((status = 200, description = "The value was copied")),
+    security(("bearer" = [])),
+)]
+#[tracing::instrument(skip_all, fields(key = params.key, from = params.from, to = params.to))]
+pub async fn promote_secret<S: AppState>(
+    State(state): State<Arc<S>>,
+    auth: AuthUser,
+    Json(params): Json<PromoteSecretParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    for environment in [&params.from, &params.to] {
+        check_environment_name(environment).map_err(ApiError::BadRequest)?;
+    }
+
+    let db = auth.db(state.as_ref());
+    let user = auth.0;
+    let mut keyfile = state.get_keyfile()?;
+
+    let mut secret = db
+        .in_environment(&params.from)
+        .view_secret(user.clone(), params.key)
+        .await?;
+
+    // Every environment shares the namespace's data key, but the copy needs its own nonce
+    let data_key = data_key(state.as_ref(), db.namespace()).await?;
+    let nonce = keyfile.next_nonce();
+
+    secret.reencrypt(
+        data_key.open_key(secret.nonce())?,
+        data_key.seal_key(nonce)?,
+    )?;
+    secret.nonce = U64Wrapper(nonce);
+
+    db.in_environment(&params.to)
+        .promote_secret(user, params.from, secret)
+        .await?;
+
+    state.save_keyfile(keyfile)?;
+    tracing::info!("Secret promoted!");
+
+    Ok(StatusCode::OK)
+}
+
+/// The namespace's audit log, newest first. Only promotions are recorded so far.
+#[utoipa::path(
+    get,
+    path = "/v1/audit",
+    tag = "admin",
+    params(AuditLogQuery),
+    responses((status = 200, body = [AuditEntry])),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn view_audit_log<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Json<Vec<AuditEntry>>, ApiError> {
+    let limit = query.limit.map_or(100, |x| x.clamp(1, MAX_AUDIT_ENTRIES));
+
+    let res = state
+        .db()
+        .in_namespace(&namespace)
+        .view_audit_log(limit)
+        .await?;
+
+    Ok(Json(res))
+}
@@ -1,10 +1,26 @@
-use axum::http::StatusCode;
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::middleware::Next;
 use axum::response::{IntoResponse, Response};
+use axum::Json;
 use chamber_core::errors::DatabaseError;
+use chamber_shared::errors::{ErrorBody, ErrorCode, REQUEST_ID_HEADER};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::time::Duration;
+use tracing::Instrument;
+
+/// The largest error body from outside of Chamber's handlers (such as a rejected JSON body)
+/// that gets wrapped in an [`ErrorBody`]. Anything bigger gets a generic message instead.
+const MAX_REJECTION_LEN: usize = 4096;
 
 pub enum ApiError {
     Forbidden,
     Unauthorised,
+    BadRequest(String),
+    NotFound(String),
+    Conflict(String),
+    TooManyRequests(Duration),
     Locked,
     IOError(std::io::Error),
     DBError(DatabaseError),
@@ -14,27 +30,94 @@ pub enum ApiError {
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        match self {
-            Self::Forbidden => (StatusCode::FORBIDDEN, "Forbidden!".to_string()).into_response(),
-            Self::Unauthorised => {
-                (StatusCode::UNAUTHORIZED, "Unauthorised!".to_string()).into_response()
-            }
-            Self::Locked => {
-                (StatusCode::LOCKED, "The vault is locked!".to_string()).into_response()
-            }
-            Self::IOError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
-            Self::DBError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
-            Self::CryptoError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
-            Self::Utf8Error(e) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        let (code, message) = match self {
+            Self::Forbidden => (ErrorCode::Forbidden, "Forbidden".to_string()),
+            Self::Unauthorised => (ErrorCode::Unauthorised, "Unauthorised".to_string()),
+            Self::BadRequest(e) => (ErrorCode::BadRequest, e),
+            Self::NotFound(e) => (ErrorCode::NotFound, e),
+            Self::Conflict(e) => (ErrorCode::Conflict, e),
+            Self::TooManyRequests(wait) => {
+                let body = ErrorBody::new(
+                    ErrorCode::TooManyRequests,
+                    "Too many attempts, try again later",
+                );
+
+                let mut res = error_response(body);
+                res.headers_mut()
+                    .insert(header::RETRY_AFTER, wait.as_secs().max(1).into());
+
+                return res;
             }
-        }
+            Self::Locked => (ErrorCode::Locked, "The vault is locked".to_string()),
+            Self::IOError(e) => internal(e),
+            Self::DBError(e) => internal(e),
+            Self::CryptoError(e) => internal(e),
+            Self::Utf8Error(e) => internal(e),
+        };
+
+        error_response(ErrorBody::new(code, message))
     }
 }
 
+/// Logs an unexpected error, which isn't passed on to the client since it can contain
+/// details like SQL that are only useful to operators.
+fn internal(e: impl std::fmt::Display) -> (ErrorCode, String) {
+    tracing::error!("Internal error: {e}");
+
+    (ErrorCode::Internal, "Internal server error".to_string())
+}
+
+/// Responds with an error body. The [`request_id`] middleware fills in the request ID.
+pub fn error_response(body: ErrorBody) -> Response {
+    let status =
+        StatusCode::from_u16(body.code.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+
+    let mut res = (status, Json(body.clone())).into_response();
+    res.extensions_mut().insert(body);
+
+    res
+}
+
 impl From<DatabaseError> for ApiError {
     fn from(e: DatabaseError) -> Self {
-        Self::DBError(e)
+        match e {
+            DatabaseError::Forbidden | DatabaseError::UserDisabled | DatabaseError::UserExpired => {
+                Self::Forbidden
+            }
+            DatabaseError::TooManyChallenges(wait) => Self::TooManyRequests(wait),
+            DatabaseError::KeyNotFound
+            | DatabaseError::UserNotFound
+            | DatabaseError::SshKeyNotFound
+            | DatabaseError::RoleNotFound(_)
+            | DatabaseError::GroupNotFound(_)
+            | DatabaseError::PolicyNotFound
+            | DatabaseError::NamespaceNotFound(_) => Self::NotFound(e.to_string()),
+            DatabaseError::SQLError(sqlx::Error::RowNotFound) => {
+                Self::NotFound("Not found".to_string())
+            }
+            DatabaseError::AlreadyInitialised
+            | DatabaseError::SecretAlreadyExists(_)
+            | DatabaseError::UserAlreadyExists
+            | DatabaseError::SshKeyAlreadyExists
+            | DatabaseError::RoleAlreadyExists
+            | DatabaseError::RoleInUse(_)
+            | DatabaseError::GroupAlreadyExists
+            | DatabaseError::PolicyAlreadyExists
+            | DatabaseError::NamespaceAlreadyExists => Self::Conflict(e.to_string()),
+            DatabaseError::SQLError(sqlx::Error::Database(ref db)) if db.is_unique_violation() => {
+                Self::Conflict("Already exists".to_string())
+            }
+            DatabaseError::InvalidTwoFactorCode
+            | DatabaseError::TwoFactorNotEnrolled
+            | DatabaseError::InvalidSshKey(_)
+            | DatabaseError::InvalidChallenge
+            | DatabaseError::InvalidPolicy(_)
+            | DatabaseError::InvalidCursor
+            | DatabaseError::InvalidKey(_)
+            | DatabaseError::InvalidNamespace(_)
+            | DatabaseError::InvalidEnvironment(_) => Self::BadRequest(e.to_string()),
+            e => Self::DBError(e),
+        }
     }
 }
 
@@ -55,3 +138,74 @@ impl From<chamber_crypto::errors::DatabaseError> for ApiError {
         Self::CryptoError(e)
     }
 }
+
+/// Gives every request an ID, which is echoed in the `x-request-id` header and in error bodies.
+/// A well-formed ID sent by the client (or a proxy) is kept, so requests can be traced across both.
+///
+/// Errors that don't come from Chamber's handlers, such as axum's rejections of invalid JSON
+/// or unknown routes, are wrapped in an [`ErrorBody`] here so that every error has the same shape.
+pub async fn request_id(req: Request, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|x| x.to_str().ok())
+        .filter(|x| is_valid_request_id(x))
+        .map(str::to_string)
+        .unwrap_or_else(new_request_id);
+
+    let span = tracing::info_span!("request", request_id = %id);
+    let (mut parts, body) = next.run(req).instrument(span).await.into_parts();
+
+    let error = parts.extensions.remove::<ErrorBody>();
+    let failed = parts.status.is_client_error() || parts.status.is_server_error();
+
+    let mut res = match error {
+        None if !failed => Response::from_parts(parts, body),
+        error => {
+            let mut error = match error {
+                Some(error) => error,
+                None => rejection(parts.status, body).await,
+            };
+            error.request_id = Some(id.clone());
+
+            parts.headers.remove(header::CONTENT_LENGTH);
+            parts.headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            );
+
+            let body = serde_json::to_vec(&error).unwrap_or_default();
+            Response::from_parts(parts, Body::from(body))
+        }
+    };
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    res
+}
+
+async fn rejection(status: StatusCode, body: Body) -> ErrorBody {
+    let message = match axum::body::to_bytes(body, MAX_REJECTION_LEN).await {
+        Ok(bytes) if !bytes.is_empty() => String::from_utf8_lossy(&bytes).into_owned(),
+        _ => status.canonical_reason().unwrap_or("Error").to_string(),
+    };
+
+    ErrorBody::new(ErrorCode::from_status(status.as_u16()), message)
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|x| x.is_ascii_alphanumeric() || x == '-' || x == '_')
+}
+
+fn new_request_id() -> String {
+    let mut bytes = [0u8; 16];
+    let _ = SystemRandom::new().fill(&mut bytes);
+
+    bytes.iter().map(|x| format!("{x:02x}")).collect()
+}
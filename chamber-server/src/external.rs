@@ -0,0 +1,289 @@
+use chamber_core::policy::glob_match;
+use chamber_core::users::User;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+pub static EXTERNAL_AUTH_VAR: &str = "CHAMBER_EXTERNAL_AUTH";
+
+/// The algorithms accepted from external providers. Chamber's own tokens use HS256.
+pub static ALGORITHMS: [Algorithm; 2] = [Algorithm::RS256, Algorithm::ES256];
+
+/// How long to wait before fetching a provider's JWKS again when a token has an unknown key ID.
+static REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+static EXTERNAL_AUTH: OnceCell<ExternalAuth> = OnceCell::new();
+
+#[derive(thiserror::Error, Debug)]
+pub enum ExternalAuthError {
+    #[error("Couldn't read {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Couldn't fetch the JWKS: {0}")]
+    Fetch(#[from] reqwest::Error),
+    #[error("Invalid provider config: {0}")]
+    InvalidConfig(String),
+    #[error("Invalid token: {0}")]
+    InvalidToken(#[from] jsonwebtoken::errors::Error),
+    #[error("Tokens signed with {0:?} aren't accepted")]
+    UnsupportedAlgorithm(Algorithm),
+    #[error("No provider is configured for the issuer {0}")]
+    UnknownIssuer(String),
+    #[error("The token's key isn't in the provider's JWKS")]
+    UnknownKey,
+    #[error("No rule grants access to this token")]
+    NoMatchingRule,
+}
+
+/// The file that `CHAMBER_EXTERNAL_AUTH` points to.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ExternalAuthConfig {
+    pub providers: Vec<ProviderConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ProviderConfig {
+    /// Prefixes the subjects of the provider's tokens, so that they can't clash with Chamber users.
+    pub name: String,
+    pub issuer: String,
+    pub audience: String,
+    /// Where to load the provider's JWKS from. Exactly one of these should be set.
+    pub jwks_file: Option<PathBuf>,
+    pub jwks_url: Option<String>,
+    /// The namespace that the provider's users act in. Defaults to the default namespace.
+    pub namespace: Option<String>,
+    #[serde(default)]
+    pub rules: Vec<ClaimRule>,
+}
+
+/// Grants roles and an access level to tokens whose claims all match.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ClaimRule {
+    /// Globs that the claims need to match. Array claims match if any of their items do.
+    pub claims: BTreeMap<String, String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub access_level: i32,
+}
+
+impl ClaimRule {
+    pub fn matches(&self, claims: &Map<String, Value>) -> bool {
+        self.claims
+            .iter()
+            .all(|(name, pattern)| match claims.get(name) {
+                Some(Value::Array(items)) => items.iter().any(|x| value_matches(pattern, x)),
+                Some(value) => value_matches(pattern, value),
+                None => false,
+            })
+    }
+}
+
+fn value_matches(pattern: &str, value: &Value) -> bool {
+    match value {
+        Value::String(x) => glob_match(pattern, x),
+        Value::Number(_) | Value::Bool(_) => glob_match(pattern, &value.to_string()),
+        _ => false,
+    }
+}
+
+struct Provider {
+    config: ProviderConfig,
+    keys: RwLock<JwkSet>,
+    fetched_at: RwLock<Instant>,
+}
+
+impl Provider {
+    async fn new(config: ProviderConfig) -> Result<Self, ExternalAuthError> {
+        if config.jwks_file.is_some() == config.jwks_url.is_some() {
+            return Err(ExternalAuthError::InvalidConfig(format!(
+                "{} needs exactly one of jwks_file or jwks_url",
+                config.name
+            )));
+        }
+
+        let keys = load_jwks(&config).await?;
+
+        Ok(Self {
+            config,
+            keys: RwLock::new(keys),
+            fetched_at: RwLock::new(Instant::now()),
+        })
+    }
+
+    /// Finds the key that signed a token. Providers rotate their keys, so a JWKS loaded from a URL
+    /// is fetched again when the key ID isn't known, at most once every [`REFRESH_INTERVAL`].
+    async fn key(&self, kid: &str) -> Result<DecodingKey, ExternalAuthError> {
+        // Whatever a panicking thread left in the locks is still a whole JWKS or time
+        {
+            let keys = self.keys.read().unwrap_or_else(|e| e.into_inner());
+            if let Some(jwk) = keys.find(kid) {
+                return Ok(DecodingKey::from_jwk(jwk)?);
+            }
+        }
+
+        let fetched_at = *self.fetched_at.read().unwrap_or_else(|e| e.into_inner());
+        if self.config.jwks_url.is_none() || fetched_at.elapsed() < REFRESH_INTERVAL {
+            return Err(ExternalAuthError::UnknownKey);
+        }
+
+        *self.fetched_at.write().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+        let keys = load_jwks(&self.config).await?;
+
+        let key = keys.find(kid).map(DecodingKey::from_jwk).transpose()?;
+        *self.keys.write().unwrap_or_else(|e| e.into_inner()) = keys;
+
+        key.ok_or(ExternalAuthError::UnknownKey)
+    }
+
+    /// Merges the grants of every matching rule, in the same way as group grants are merged.
+    fn grants(&self, claims: &Map<String, Value>) -> Option<(Vec<String>, i32)> {
+        let mut matched = self
+            .config
+            .rules
+            .iter()
+            .filter(|x| x.matches(claims))
+            .peekable();
+        matched.peek()?;
+
+        let mut roles: Vec<String> = Vec::new();
+        let mut access_level = 0;
+
+        for rule in matched {
+            access_level = access_level.max(rule.access_level);
+
+            for role in &rule.roles {
+                if !roles.contains(role) {
+                    roles.push(role.clone());
+                }
+            }
+        }
+
+        Some((roles, access_level))
+    }
+}
+
+async fn load_jwks(config: &ProviderConfig) -> Result<JwkSet, ExternalAuthError> {
+    if let Some(path) = &config.jwks_file {
+        let file = std::fs::read(path).map_err(|e| ExternalAuthError::Io(path.to_owned(), e))?;
+
+        return Ok(serde_json::from_slice(&file)?);
+    }
+
+    let url = config.jwks_url.as_deref().unwrap_or_default();
+
+    Ok(reqwest::get(url)
+        .await?
+        .error_for_status()?
+        .json::<JwkSet>()
+        .await?)
+}
+
+/// Validates JWTs from external identity providers, such as CI workload identity tokens.
+pub struct ExternalAuth {
+    providers: Vec<Provider>,
+}
+
+#[derive(Deserialize)]
+struct UnverifiedClaims {
+    iss: String,
+}
+
+impl ExternalAuth {
+    pub async fn new(config: ExternalAuthConfig) -> Result<Self, ExternalAuthError> {
+        let mut providers = Vec::new();
+
+        for provider in config.providers {
+            providers.push(Provider::new(provider).await?);
+        }
+
+        Ok(Self { providers })
+    }
+
+    /// Loads the config file that `CHAMBER_EXTERNAL_AUTH` points to, if it's set.
+    pub async fn from_env() -> Result<Option<Self>, ExternalAuthError> {
+        let Ok(path) = std::env::var(EXTERNAL_AUTH_VAR) else {
+            return Ok(None);
+        };
+
+        let file = std::fs::read(&path).map_err(|e| ExternalAuthError::Io(path.into(), e))?;
+        let config: ExternalAuthConfig = serde_json::from_slice(&file)?;
+
+        Ok(Some(Self::new(config).await?))
+    }
+
+    /// Validates a token and returns a user with the roles and access level granted by the rules
+    /// that its claims match. The user isn't stored, and is named `<provider>:<sub>`.
+    pub async fn authenticate(&self, token: &str) -> Result<User, ExternalAuthError> {
+        let header = decode_header(token)?;
+        if !ALGORITHMS.contains(&header.alg) {
+            return Err(ExternalAuthError::UnsupportedAlgorithm(header.alg));
+        }
+
+        // The issuer decides which provider's keys to check the signature against
+        let mut peek = Validation::new(header.alg);
+        peek.insecure_disable_signature_validation();
+        peek.validate_exp = false;
+        peek.validate_aud = false;
+        peek.required_spec_claims.clear();
+
+        let issuer = decode::<UnverifiedClaims>(token, &DecodingKey::from_secret(&[]), &peek)?
+            .claims
+            .iss;
+
+        let provider = self
+            .providers
+            .iter()
+            .find(|x| x.config.issuer == issuer)
+            .ok_or(ExternalAuthError::UnknownIssuer(issuer))?;
+
+        let kid = header.kid.ok_or(ExternalAuthError::UnknownKey)?;
+        let key = provider.key(&kid).await?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&provider.config.issuer]);
+        validation.set_audience(&[&provider.config.audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        let claims = decode::<Map<String, Value>>(token, &key, &validation)?.claims;
+
+        let (roles, access_level) = provider
+            .grants(&claims)
+            .ok_or(ExternalAuthError::NoMatchingRule)?;
+
+        let sub = claims
+            .get("sub")
+            .and_then(|x| x.as_str())
+            .unwrap_or_default();
+
+        let mut user = User::new(format!("{}:{sub}", provider.config.name));
+        user.set_roles(roles);
+        user.set_access_level(access_level);
+        if let Some(namespace) = &provider.config.namespace {
+            user.set_namespace(namespace.clone());
+        }
+
+        Ok(user)
+    }
+}
+
+/// Sets the providers used by the [`crate::auth::AuthUser`] extractor. Only the first call has an effect.
+pub fn init(auth: ExternalAuth) {
+    let _ = EXTERNAL_AUTH.set(auth);
+}
+
+pub fn get() -> Option<&'static ExternalAuth> {
+    EXTERNAL_AUTH.get()
+}
+
+/// Whether a token was signed by an external provider rather than by Chamber itself.
+pub fn is_external(token: &str) -> bool {
+    decode_header(token).is_ok_and(|x| x.alg != Algorithm::HS256)
+}
@@ -0,0 +1,260 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chamber_shared::groups::{
+    Group, GroupChanges, GroupMembers, GroupMembersParams, GroupParams, UpdateGroupParams,
+};
+use std::sync::Arc;
+
+use crate::auth::NamespaceAdmin;
+use crate::errors::ApiError;
+
+use chamber_core::core::Database;
+use chamber_core::traits::AppState;
+
+#[utoipa::path(
+    post,
+    path = "/v1/groups",
+    tag = "groups",
+    request_body = Group,
+    responses((status = 201, description = "The group was created")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn create_group<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(group): Json<Group>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    db.create_group(group).await?;
+
+    Ok(StatusCode::CREATED)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/groups",
+    tag = "groups",
+    responses((status = 200, body = [Group])),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn view_groups<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+) -> Result<Json<Vec<Group>>, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let res = db.view_groups().await?;
+
+    Ok(Json(res))
+}
+
+pub async fn view_group<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(GroupParams { name }): Json<GroupParams>,
+) -> Result<Json<Group>, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let res = db.get_group_from_name(name).await?;
+
+    Ok(Json(res))
+}
+
+pub async fn update_group<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(UpdateGroupParams {
+        name,
+        description,
+        roles,
+        access_level,
+    }): Json<UpdateGroupParams>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let mut group = db.get_group_from_name(name).await?;
+
+    if let Some(description) = description {
+        group.description = description;
+    }
+
+    if let Some(roles) = roles {
+        group.roles = roles;
+    }
+
+    if let Some(access_level) = access_level {
+        group.access_level = access_level;
+    }
+
+    db.update_group(group).await?;
+
+    Ok(StatusCode::OK)
+}
+
+pub async fn delete_group<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(GroupParams { name }): Json<GroupParams>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    db.delete_group(name).await?;
+
+    Ok(StatusCode::OK)
+}
+
+pub async fn add_group_members<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(GroupMembersParams {
+        name,
+        users,
+        groups,
+    }): Json<GroupMembersParams>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let mut group = db.get_group_from_name(name).await?;
+
+    for user in users.unwrap_or_default() {
+        if !group.members.contains(&user) {
+            group.members.push(user);
+        }
+    }
+
+    for subgroup in groups.unwrap_or_default() {
+        if !group.subgroups.contains(&subgroup) {
+            group.subgroups.push(subgroup);
+        }
+    }
+
+    db.update_group(group).await?;
+
+    Ok(StatusCode::OK)
+}
+
+pub async fn remove_group_members<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(GroupMembersParams {
+        name,
+        users,
+        groups,
+    }): Json<GroupMembersParams>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let mut group = db.get_group_from_name(name).await?;
+
+    let users = users.unwrap_or_default();
+    let groups = groups.unwrap_or_default();
+
+    group.members.retain(|x| !users.contains(x));
+    group.subgroups.retain(|x| !groups.contains(x));
+
+    db.update_group(group).await?;
+
+    Ok(StatusCode::OK)
+}
+
+/// Shows a group and its members.
+#[utoipa::path(
+    get,
+    path = "/v1/groups/{name}",
+    tag = "groups",
+    params(("name" = String, Path, description = "The group's name")),
+    responses((status = 200, body = Group)),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn view_group_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+) -> Result<Json<Group>, ApiError> {
+    view_group(state, admin, Json(GroupParams { name })).await
+}
+
+/// Changes a group's roles or access level. Anything left out is kept.
+#[utoipa::path(
+    patch,
+    path = "/v1/groups/{name}",
+    tag = "groups",
+    params(("name" = String, Path, description = "The group's name")),
+    request_body = GroupChanges,
+    responses((status = 200, description = "The group was updated")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn update_group_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+    Json(changes): Json<GroupChanges>,
+) -> Result<StatusCode, ApiError> {
+    let params = UpdateGroupParams {
+        name,
+        description: changes.description,
+        roles: changes.roles,
+        access_level: changes.access_level,
+    };
+
+    update_group(state, admin, Json(params)).await
+}
+
+/// Deletes a group.
+#[utoipa::path(
+    delete,
+    path = "/v1/groups/{name}",
+    tag = "groups",
+    params(("name" = String, Path, description = "The group's name")),
+    responses((status = 200, description = "The group was deleted")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn delete_group_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    delete_group(state, admin, Json(GroupParams { name })).await
+}
+
+/// Adds users and groups to a group.
+#[utoipa::path(
+    post,
+    path = "/v1/groups/{name}/members",
+    tag = "groups",
+    params(("name" = String, Path, description = "The group's name")),
+    request_body = GroupMembers,
+    responses((status = 200, description = "The members were added")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn add_group_members_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+    Json(GroupMembers { users, groups }): Json<GroupMembers>,
+) -> Result<StatusCode, ApiError> {
+    add_group_members(state, admin, Json(GroupMembersParams { name, users, groups })).await
+}
+
+/// Removes users and groups from a group.
+#[utoipa::path(
+    delete,
+    path = "/v1/groups/{name}/members",
+    tag = "groups",
+    params(("name" = String, Path, description = "The group's name")),
+    request_body = GroupMembers,
+    responses((status = 200, description = "The members were removed")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn remove_group_members_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+    Json(GroupMembers { users, groups }): Json<GroupMembers>,
+) -> Result<StatusCode, ApiError> {
+    remove_group_members(state, admin, Json(GroupMembersParams { name, users, groups })).await
+}
@@ -3,9 +3,15 @@ use axum_extra::headers::{Error, Header, HeaderName, HeaderValue};
 static X: HeaderName = HeaderName::from_static("x-chamber-key");
 static CUSTOM_CHAMBER_HEADER: &HeaderName = &X;
 
-#[derive(Debug)]
 pub struct ChamberHeader(String);
 
+// The header holds the root key, so keep it out of logs and tracing spans
+impl std::fmt::Debug for ChamberHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ChamberHeader(<redacted>)")
+    }
+}
+
 impl ChamberHeader {
     pub fn key(self) -> String {
         self.0
@@ -23,17 +29,17 @@ impl Header for ChamberHeader {
     {
         let value = values.next().ok_or_else(Error::invalid)?;
 
-        Ok(ChamberHeader(value.to_str().unwrap().to_owned()))
+        let value = value.to_str().map_err(|_| Error::invalid())?;
+
+        Ok(ChamberHeader(value.to_owned()))
     }
 
     fn encode<E>(&self, values: &mut E)
     where
         E: Extend<HeaderValue>,
     {
-        let s = &self.0;
-
-        let value = HeaderValue::from_str(s).unwrap();
-
-        values.extend(std::iter::once(value));
+        if let Ok(value) = HeaderValue::from_str(&self.0) {
+            values.extend(std::iter::once(value));
+        }
     }
 }
@@ -0,0 +1,167 @@
+use chamber_core::consts::KEYFILE_PATH;
+use chamber_core::core::Database;
+use chamber_core::errors::DatabaseError;
+use chamber_core::traits::AppState;
+use chamber_crypto::errors::DatabaseError as CryptoError;
+use chamber_crypto::secrets::{EncryptedSecret, KeyFile, SerializeKey};
+use chamber_shared::environments::DEFAULT_ENVIRONMENT;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+use crate::errors::ApiError;
+
+/// Data keys are created, re-wrapped and destroyed one at a time, so that two requests can't each
+/// give a namespace a different key, or save over each other's changes.
+static DATA_KEYS: Mutex<()> = Mutex::const_new(());
+
+/// The data key that seals the secrets of a namespace. Namespaces get one the first time that
+/// it's needed, and any secrets that were sealed with the crypto key before then are
+/// re-encrypted with it.
+pub async fn data_key<S: AppState>(state: &S, namespace: &str) -> Result<SerializeKey, ApiError> {
+    let keyfile = state.get_keyfile()?;
+
+    if state.db().in_namespace(namespace).has_data_key().await? {
+        return unwrap_data_key(state, &keyfile, namespace);
+    }
+
+    let _guard = DATA_KEYS.lock().await;
+
+    // The keyfile may have been replaced while waiting
+    create_data_key(state, &state.get_keyfile()?, namespace).await
+}
+
+/// Destroys a deleted namespace's data key, which leaves any copies of its secrets unreadable.
+pub async fn delete_data_key<S: AppState>(state: &S, namespace: &str) -> Result<(), ApiError> {
+    let _guard = DATA_KEYS.lock().await;
+
+    let mut keys = state.get_data_keys()?;
+
+    if keys.remove(namespace).is_some() {
+        state.save_data_keys(keys)?;
+    }
+
+    Ok(())
+}
+
+/// Replaces the keyfile, wrapping every namespace's data key with its crypto key. The secrets
+/// themselves don't need to be re-encrypted.
+pub async fn replace_keyfile<S: AppState>(state: &S, mut new: KeyFile) -> Result<(), ApiError> {
+    let _guard = DATA_KEYS.lock().await;
+
+    let current = state.get_keyfile()?;
+
+    // The data keys are re-wrapped rather than replaced, so secrets keep being sealed with them
+    // and the new keyfile has to carry on from the old one's nonces rather than reuse them.
+    new.nonce_number = new.nonce_number.max(current.nonce_number);
+
+    // Namespaces without a key still have secrets sealed with the old crypto key
+    for namespace in state.db().view_namespaces().await? {
+        create_data_key(state, &current, &namespace.name).await?;
+    }
+
+    let keys = state.get_data_keys()?;
+
+    let mut rewrapped = HashMap::new();
+    for (namespace, wrapped) in &keys {
+        let data_key = open_data_key(&current, namespace, wrapped)?;
+        rewrapped.insert(namespace.clone(), new.wrap_data_key(namespace, &data_key)?);
+    }
+
+    // The keys are saved wrapped with both keyfiles until the new one is saved, so that whichever
+    // keyfile is in use can open them if saving fails part of the way through
+    state.save_data_keys(
+        keys.into_iter()
+            .map(|(namespace, mut wrapped)| {
+                wrapped.insert(0, rewrapped[&namespace].clone());
+
+                (namespace, wrapped)
+            })
+            .collect(),
+    )?;
+
+    let encoded = bincode::serialize(&new).map_err(DatabaseError::from)?;
+    std::fs::write(KEYFILE_PATH, encoded)?;
+
+    state.save_keyfile(new)?;
+
+    state.save_data_keys(
+        rewrapped
+            .into_iter()
+            .map(|(namespace, wrapped)| (namespace, vec![wrapped]))
+            .collect(),
+    )?;
+
+    Ok(())
+}
+
+fn unwrap_data_key<S: AppState>(
+    state: &S,
+    keyfile: &KeyFile,
+    namespace: &str,
+) -> Result<SerializeKey, ApiError> {
+    let keys = state.get_data_keys()?;
+    let wrapped = keys.get(namespace).ok_or(CryptoError::DecryptionError)?;
+
+    open_data_key(keyfile, namespace, wrapped)
+}
+
+/// Opens whichever of a data key's wrappings was made with the given keyfile.
+fn open_data_key(
+    keyfile: &KeyFile,
+    namespace: &str,
+    wrapped: &[Vec<u8>],
+) -> Result<SerializeKey, ApiError> {
+    wrapped
+        .iter()
+        .find_map(|x| keyfile.unwrap_data_key(namespace, x).ok())
+        .ok_or_else(|| CryptoError::DecryptionError.into())
+}
+
+/// Gives a namespace its data key if it doesn't have one yet. Only called while holding
+/// `DATA_KEYS`.
+async fn create_data_key<S: AppState>(
+    state: &S,
+    keyfile: &KeyFile,
+    namespace: &str,
+) -> Result<SerializeKey, ApiError> {
+    // Secrets from before data keys were added are all in the default environment
+    let db = state
+        .db()
+        .in_namespace(namespace)
+        .in_environment(DEFAULT_ENVIRONMENT);
+
+    if db.has_data_key().await? {
+        return unwrap_data_key(state, keyfile, namespace);
+    }
+
+    let data_key = SerializeKey::new();
+
+    // Saved before anything is sealed with it, so that the key can't be lost. If re-encrypting
+    // fails, it's replaced the next time around.
+    let mut keys = state.get_data_keys()?;
+    keys.insert(
+        namespace.to_string(),
+        vec![keyfile.wrap_data_key(namespace, &data_key)?],
+    );
+    state.save_data_keys(keys)?;
+
+    let secrets = db
+        .view_all_secrets_admin()
+        .await?
+        .into_iter()
+        .map(|mut secret| {
+            let nonce = secret.nonce();
+
+            secret.reencrypt(
+                keyfile.get_crypto_open_key(nonce)?,
+                data_key.seal_key(nonce)?,
+            )?;
+
+            Ok(secret)
+        })
+        .collect::<Result<Vec<EncryptedSecret>, ApiError>>()?;
+
+    db.seal_with_data_key(secrets).await?;
+
+    Ok(data_key)
+}
@@ -1,6 +1,19 @@
+pub mod access;
 pub mod auth;
+pub mod environments;
 pub mod errors;
+pub mod external;
+pub mod groups;
 pub mod header;
+pub mod keys;
+pub mod namespaces;
+pub mod openapi;
+pub mod policies;
+pub mod proxies;
+pub mod roles;
 pub mod router;
 pub mod secrets;
+pub mod ssh;
+pub mod tls;
+pub mod unseal;
 pub mod users;
@@ -0,0 +1,89 @@
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use axum_extra::TypedHeader;
+use chamber_shared::namespaces::Namespace;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::auth::{check_root_key, client_ip};
+use crate::errors::ApiError;
+use crate::header::ChamberHeader;
+use crate::keys::{data_key, delete_data_key};
+
+use chamber_core::core::Database;
+use chamber_core::traits::AppState;
+
+#[utoipa::path(
+    post,
+    path = "/v1/namespaces",
+    tag = "namespaces",
+    request_body = Namespace,
+    responses((status = 201, description = "The namespace was created")),
+    security(("root_key" = [])),
+)]
+pub async fn create_namespace<S: AppState>(
+    State(state): State<Arc<S>>,
+    TypedHeader(auth): TypedHeader<ChamberHeader>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(namespace): Json<Namespace>,
+) -> Result<StatusCode, ApiError> {
+    check_root_key(state.as_ref(), auth, &client_ip(&headers, connect_info))?;
+
+    let name = namespace.name.clone();
+    state.db().create_namespace(namespace).await?;
+
+    data_key(state.as_ref(), &name).await?;
+
+    Ok(StatusCode::CREATED)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/namespaces",
+    tag = "namespaces",
+    responses((status = 200, body = [Namespace])),
+    security(("root_key" = [])),
+)]
+pub async fn view_namespaces<S: AppState>(
+    State(state): State<Arc<S>>,
+    TypedHeader(auth): TypedHeader<ChamberHeader>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<Namespace>>, ApiError> {
+    check_root_key(state.as_ref(), auth, &client_ip(&headers, connect_info))?;
+
+    let res = state.db().view_namespaces().await?;
+
+    Ok(Json(res))
+}
+
+/// Deletes a namespace along with everything in it. Its data key, which is kept with the keyfile
+/// rather than in the database, is destroyed too, so copies of its secrets, such as those in
+/// database backups, can't be decrypted afterwards either. The default namespace can't be deleted.
+#[utoipa::path(
+    delete,
+    path = "/v1/namespaces/{name}",
+    tag = "namespaces",
+    params(("name" = String, Path, description = "The namespace's name")),
+    responses((status = 200, description = "The namespace was deleted")),
+    security(("root_key" = [])),
+)]
+pub async fn delete_namespace<S: AppState>(
+    State(state): State<Arc<S>>,
+    TypedHeader(auth): TypedHeader<ChamberHeader>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    check_root_key(state.as_ref(), auth, &client_ip(&headers, connect_info))?;
+
+    state.db().delete_namespace(name.clone()).await?;
+
+    delete_data_key(state.as_ref(), &name).await?;
+
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,216 @@
+use axum::Json;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{ContentBuilder, Ref, ResponseBuilder};
+use utoipa::{Modify, OpenApi};
+
+use crate::{
+    access, auth, environments, groups, namespaces, policies, roles, router, secrets, ssh, unseal,
+    users,
+};
+
+/// The `/v1` API. The unversioned routes are deprecated aliases, so they're left out.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Chamber", description = "A self-hostable secrets manager."),
+    paths(
+        router::health_check,
+        openapi_json,
+        secrets::create_secret,
+        secrets::view_all_secrets_v1,
+        secrets::view_secret_v1,
+        secrets::update_secret_v1,
+        secrets::delete_secret_v1,
+        secrets::view_decrypted_secrets_by_tag_v1,
+        secrets::view_folder,
+        secrets::move_secrets,
+        environments::promote_secret,
+        environments::view_audit_log,
+        secrets::upload_binfile,
+        secrets::unlock,
+        users::init,
+        users::create_user,
+        users::view_users,
+        users::view_user_v1,
+        users::update_user_v1,
+        users::delete_user_v1,
+        users::disable_user_v1,
+        users::enable_user_v1,
+        users::set_user_expiry_v1,
+        users::reset_two_factor_v1,
+        users::whoami,
+        users::change_password,
+        users::enroll_two_factor,
+        users::confirm_two_factor,
+        users::disable_two_factor,
+        users::view_two_factor_settings,
+        users::update_two_factor_settings,
+        roles::create_role,
+        roles::view_roles,
+        roles::view_role_v1,
+        roles::update_role_v1,
+        roles::delete_role_v1,
+        groups::create_group,
+        groups::view_groups,
+        groups::view_group_v1,
+        groups::update_group_v1,
+        groups::delete_group_v1,
+        groups::add_group_members_v1,
+        groups::remove_group_members_v1,
+        policies::create_policy,
+        policies::view_policies,
+        policies::view_policy_v1,
+        policies::update_policy_v1,
+        policies::delete_policy_v1,
+        policies::attach_policy_v1,
+        policies::detach_policy_v1,
+        namespaces::create_namespace,
+        namespaces::view_namespaces,
+        namespaces::delete_namespace,
+        access::explain_secret_access,
+        access::explain_user_access,
+        access::simulate_access,
+        ssh::view_ssh_keys,
+        ssh::add_ssh_key,
+        ssh::delete_ssh_key,
+        ssh::ssh_challenge,
+        ssh::ssh_login,
+        unseal::add_unseal_key,
+        unseal::view_unseal_keys,
+        unseal::delete_unseal_key,
+        unseal::unseal_challenge,
+        auth::login,
+        auth::cert_login,
+        auth::view_failed_attempts,
+    ),
+    components(schemas(
+        chamber_shared::AuthBody,
+        chamber_shared::attempts::FailedAttempt,
+        chamber_shared::attempts::FailedAttemptsParams,
+        chamber_shared::audit::AuditEntry,
+        chamber_shared::environments::PromoteSecretParams,
+        chamber_shared::errors::ErrorBody,
+        chamber_shared::errors::ErrorCode,
+        chamber_shared::groups::Group,
+        chamber_shared::groups::GroupChanges,
+        chamber_shared::groups::GroupMembers,
+        chamber_shared::namespaces::Namespace,
+        chamber_shared::policy::Capability,
+        chamber_shared::policy::PolicyRule,
+        chamber_shared::policy::Policy,
+        chamber_shared::policy::PolicyAttachments,
+        chamber_shared::policy::Decision,
+        chamber_shared::policy::AccessGrant,
+        chamber_shared::policy::SecretAccess,
+        chamber_shared::policy::SecretAccessParams,
+        chamber_shared::policy::ReachableSecret,
+        chamber_shared::policy::UserAccess,
+        chamber_shared::policy::UserAccessParams,
+        chamber_shared::policy::SimulateParams,
+        chamber_shared::roles::Role,
+        chamber_shared::roles::RoleUsage,
+        chamber_shared::secrets::SecretInfo,
+        chamber_shared::secrets::SecretPublic,
+        chamber_shared::secrets::CreateSecretParams,
+        chamber_shared::secrets::SecretTags,
+        chamber_shared::secrets::TagMatch,
+        chamber_shared::secrets::SecretSort,
+        chamber_shared::secrets::SortOrder,
+        chamber_shared::secrets::FolderListing,
+        chamber_shared::secrets::MoveSecretsParams,
+        chamber_shared::secrets::MovedSecret,
+        chamber_shared::ssh::SshKey,
+        chamber_shared::ssh::AddSshKeyParams,
+        chamber_shared::ssh::RemoveSshKeyParams,
+        chamber_shared::ssh::SshChallengeParams,
+        chamber_shared::ssh::SshChallenge,
+        chamber_shared::ssh::SshLoginParams,
+        chamber_shared::ssh::UnsealKey,
+        chamber_shared::ssh::AddUnsealKeyParams,
+        chamber_shared::ssh::RemoveUnsealKeyParams,
+        chamber_shared::ssh::UnsealParams,
+        chamber_shared::users::UserProfile,
+        chamber_shared::users::ChangePasswordParams,
+        chamber_shared::users::UserSummary,
+        chamber_shared::users::InitParams,
+        chamber_shared::users::InitResponse,
+        chamber_shared::users::CreateUserParams,
+        chamber_shared::users::UserChanges,
+        chamber_shared::users::UserExpiry,
+        chamber_shared::users::UserLoginParams,
+        chamber_shared::users::CertLoginParams,
+        chamber_shared::users::TwoFactorEnrollment,
+        chamber_shared::users::TwoFactorPasswordParams,
+        chamber_shared::users::TwoFactorCodeParams,
+        chamber_shared::users::DisableTwoFactorParams,
+        chamber_shared::users::RecoveryCodes,
+        chamber_shared::users::TwoFactorSettings,
+        secrets::KeyfileUpload,
+    )),
+    modifiers(&Security, &Errors),
+)]
+pub struct ApiDoc;
+
+/// Tokens from `/v1/login` go in the `Authorization` header, while the root key from the
+/// keyfile goes in `x-chamber-key`. Root key requests pick a namespace with
+/// `x-chamber-namespace`, while tokens always act in their user's namespace. Secrets requests
+/// pick an environment with `x-chamber-environment`.
+struct Security;
+
+impl Modify for Security {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "root_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("x-chamber-key"))),
+        );
+    }
+}
+
+/// Every route can fail with an [`ErrorBody`](chamber_shared::errors::ErrorBody),
+/// so it's the default response of every operation.
+struct Errors;
+
+impl Modify for Errors {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let response = ResponseBuilder::new()
+            .description("An error, with a stable code")
+            .content(
+                "application/json",
+                ContentBuilder::new()
+                    .schema(Ref::from_schema_name("ErrorBody"))
+                    .build(),
+            )
+            .build();
+
+        for item in openapi.paths.paths.values_mut() {
+            for operation in item.operations.values_mut() {
+                operation
+                    .responses
+                    .responses
+                    .entry("default".to_string())
+                    .or_insert_with(|| response.clone().into());
+            }
+        }
+    }
+}
+
+/// Serves this document, so that clients can be generated from a running server.
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    tag = "meta",
+    responses((status = 200, description = "The OpenAPI document for the `/v1` API")),
+)]
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
@@ -0,0 +1,230 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chamber_shared::policy::{AttachPolicyParams, Policy, PolicyAttachments, PolicyParams};
+use std::sync::Arc;
+
+use crate::auth::NamespaceAdmin;
+use crate::errors::ApiError;
+
+use chamber_core::core::Database;
+use chamber_core::traits::AppState;
+
+#[utoipa::path(
+    post,
+    path = "/v1/policies",
+    tag = "policies",
+    request_body = Policy,
+    responses((status = 201, description = "The policy was created")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn create_policy<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(policy): Json<Policy>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    db.create_policy(policy).await?;
+
+    Ok(StatusCode::CREATED)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/policies",
+    tag = "policies",
+    responses((status = 200, body = [Policy])),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn view_policies<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+) -> Result<Json<Vec<Policy>>, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let res = db.view_policies().await?;
+
+    Ok(Json(res))
+}
+
+pub async fn view_policy<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(PolicyParams { name }): Json<PolicyParams>,
+) -> Result<Json<Policy>, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let res = db.get_policy_from_name(name).await?;
+
+    Ok(Json(res))
+}
+
+pub async fn update_policy<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(policy): Json<Policy>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    db.update_policy(policy).await?;
+
+    Ok(StatusCode::OK)
+}
+
+pub async fn delete_policy<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(PolicyParams { name }): Json<PolicyParams>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    db.delete_policy(name).await?;
+
+    Ok(StatusCode::OK)
+}
+
+pub async fn attach_policy<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(AttachPolicyParams { name, users, roles }): Json<AttachPolicyParams>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let mut policy = db.get_policy_from_name(name).await?;
+
+    for user in users.unwrap_or_default() {
+        if !policy.users.contains(&user) {
+            policy.users.push(user);
+        }
+    }
+
+    for role in roles.unwrap_or_default() {
+        if !policy.roles.contains(&role) {
+            policy.roles.push(role);
+        }
+    }
+
+    db.update_policy(policy).await?;
+
+    Ok(StatusCode::OK)
+}
+
+pub async fn detach_policy<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(AttachPolicyParams { name, users, roles }): Json<AttachPolicyParams>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let mut policy = db.get_policy_from_name(name).await?;
+
+    let users = users.unwrap_or_default();
+    let roles = roles.unwrap_or_default();
+
+    policy.users.retain(|x| !users.contains(x));
+    policy.roles.retain(|x| !roles.contains(x));
+
+    db.update_policy(policy).await?;
+
+    Ok(StatusCode::OK)
+}
+
+/// Shows a policy and what it is attached to.
+#[utoipa::path(
+    get,
+    path = "/v1/policies/{name}",
+    tag = "policies",
+    params(("name" = String, Path, description = "The policy's name")),
+    responses((status = 200, body = Policy)),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn view_policy_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+) -> Result<Json<Policy>, ApiError> {
+    view_policy(state, admin, Json(PolicyParams { name })).await
+}
+
+/// Replaces a policy's statements.
+#[utoipa::path(
+    put,
+    path = "/v1/policies/{name}",
+    tag = "policies",
+    params(("name" = String, Path, description = "The policy's name")),
+    request_body = Policy,
+    responses((status = 200, description = "The policy was replaced")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn update_policy_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+    Json(policy): Json<Policy>,
+) -> Result<StatusCode, ApiError> {
+    if policy.name != name {
+        return Err(ApiError::BadRequest(
+            "The policy's name doesn't match the path".to_string(),
+        ));
+    }
+
+    update_policy(state, admin, Json(policy)).await
+}
+
+/// Deletes a policy.
+#[utoipa::path(
+    delete,
+    path = "/v1/policies/{name}",
+    tag = "policies",
+    params(("name" = String, Path, description = "The policy's name")),
+    responses((status = 200, description = "The policy was deleted")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn delete_policy_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    delete_policy(state, admin, Json(PolicyParams { name })).await
+}
+
+/// Attaches a policy to users and roles.
+#[utoipa::path(
+    post,
+    path = "/v1/policies/{name}/attachments",
+    tag = "policies",
+    params(("name" = String, Path, description = "The policy's name")),
+    request_body = PolicyAttachments,
+    responses((status = 200, description = "The policy was attached")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn attach_policy_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+    Json(PolicyAttachments { users, roles }): Json<PolicyAttachments>,
+) -> Result<StatusCode, ApiError> {
+    attach_policy(state, admin, Json(AttachPolicyParams { name, users, roles })).await
+}
+
+/// Detaches a policy from users and roles.
+#[utoipa::path(
+    delete,
+    path = "/v1/policies/{name}/attachments",
+    tag = "policies",
+    params(("name" = String, Path, description = "The policy's name")),
+    request_body = PolicyAttachments,
+    responses((status = 200, description = "The policy was detached")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn detach_policy_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+    Json(PolicyAttachments { users, roles }): Json<PolicyAttachments>,
+) -> Result<StatusCode, ApiError> {
+    detach_policy(state, admin, Json(AttachPolicyParams { name, users, roles })).await
+}
@@ -0,0 +1,92 @@
+use axum::http::HeaderMap;
+use once_cell::sync::OnceCell;
+use std::net::{AddrParseError, IpAddr};
+
+/// A comma-separated list of the IPs of the proxies in front of the server, or `*` when every
+/// request comes through one.
+pub static TRUSTED_PROXIES_VAR: &str = "CHAMBER_TRUSTED_PROXIES";
+
+static TRUSTED_PROXIES: OnceCell<TrustedProxies> = OnceCell::new();
+
+/// Whose `X-Forwarded-For` headers are believed. Anyone else could send the header with a new IP
+/// for every request, and get around the limits that are kept per IP.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum TrustedProxies {
+    /// Clients connect to the server directly, so the header is ignored.
+    #[default]
+    None,
+    /// Every request comes through a proxy that the server can't see the address of, as on
+    /// Shuttle. The entry that the proxy added to the header is believed.
+    All,
+    /// Only requests from these addresses come through a proxy.
+    Only(Vec<IpAddr>),
+}
+
+impl TrustedProxies {
+    /// Reads the proxies from `CHAMBER_TRUSTED_PROXIES`. Without it, no proxy is trusted.
+    pub fn from_env() -> Result<Self, AddrParseError> {
+        let Ok(proxies) = std::env::var(TRUSTED_PROXIES_VAR) else {
+            return Ok(Self::None);
+        };
+
+        if proxies.trim() == "*" {
+            return Ok(Self::All);
+        }
+
+        let proxies = proxies
+            .split(',')
+            .map(|x| x.trim().parse())
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self::Only(proxies))
+    }
+
+    /// Finds the client's IP. Proxies add the address that they were connected from to the end
+    /// of `X-Forwarded-For`, so the header is read from the right and only as far as the trusted
+    /// proxies go, which leaves out anything that the client put there itself.
+    pub fn client_ip(&self, headers: &HeaderMap, peer: Option<IpAddr>) -> String {
+        let fallback = || peer.map_or_else(|| "unknown".to_string(), |x| x.to_string());
+
+        let trusted = match (self, peer) {
+            (Self::None, _) => false,
+            (Self::All, _) => true,
+            (Self::Only(proxies), Some(peer)) => proxies.contains(&peer),
+            (Self::Only(_), None) => false,
+        };
+
+        if !trusted {
+            return fallback();
+        }
+
+        let hops = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|x| x.to_str().ok())
+            .flat_map(|x| x.split(','))
+            .map(str::trim)
+            .collect::<Vec<_>>();
+
+        for hop in hops.into_iter().rev() {
+            let Ok(ip) = hop.parse::<IpAddr>() else {
+                break;
+            };
+
+            match self {
+                Self::Only(proxies) if proxies.contains(&ip) => continue,
+                _ => return ip.to_string(),
+            }
+        }
+
+        fallback()
+    }
+}
+
+/// Sets the proxies that [`crate::auth::client_ip`] trusts. Only the first call has an effect.
+pub fn init(proxies: TrustedProxies) {
+    let _ = TRUSTED_PROXIES.set(proxies);
+}
+
+/// The trusted proxies, which are none until [`init`] is called.
+pub fn get() -> &'static TrustedProxies {
+    TRUSTED_PROXIES.get_or_init(TrustedProxies::default)
+}
@@ -0,0 +1,146 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chamber_shared::roles::{Role, RoleParams, RoleUsage};
+use std::sync::Arc;
+
+use crate::auth::NamespaceAdmin;
+use crate::errors::ApiError;
+
+use chamber_core::core::Database;
+use chamber_core::traits::AppState;
+
+#[utoipa::path(
+    post,
+    path = "/v1/roles",
+    tag = "roles",
+    request_body = Role,
+    responses((status = 201, description = "The role was created")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn create_role<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(role): Json<Role>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    db.create_role(role).await?;
+
+    Ok(StatusCode::CREATED)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/roles",
+    tag = "roles",
+    responses((status = 200, body = [RoleUsage])),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn view_roles<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+) -> Result<Json<Vec<RoleUsage>>, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let res = db.view_roles().await?;
+
+    Ok(Json(res))
+}
+
+pub async fn view_role<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(RoleParams { name }): Json<RoleParams>,
+) -> Result<Json<RoleUsage>, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let res = db.get_role_from_name(name).await?;
+
+    Ok(Json(res))
+}
+
+pub async fn update_role<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(role): Json<Role>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    db.update_role(role).await?;
+
+    Ok(StatusCode::OK)
+}
+
+pub async fn delete_role<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(RoleParams { name }): Json<RoleParams>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    db.delete_role(name).await?;
+
+    Ok(StatusCode::OK)
+}
+
+/// Shows a role and what uses it.
+#[utoipa::path(
+    get,
+    path = "/v1/roles/{name}",
+    tag = "roles",
+    params(("name" = String, Path, description = "The role's name")),
+    responses((status = 200, body = RoleUsage)),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn view_role_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+) -> Result<Json<RoleUsage>, ApiError> {
+    view_role(state, admin, Json(RoleParams { name })).await
+}
+
+/// Replaces a role.
+#[utoipa::path(
+    put,
+    path = "/v1/roles/{name}",
+    tag = "roles",
+    params(("name" = String, Path, description = "The role's name")),
+    request_body = Role,
+    responses((status = 200, description = "The role was replaced")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn update_role_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+    Json(role): Json<Role>,
+) -> Result<StatusCode, ApiError> {
+    if role.name != name {
+        return Err(ApiError::BadRequest(
+            "The role's name doesn't match the path".to_string(),
+        ));
+    }
+
+    update_role(state, admin, Json(role)).await
+}
+
+/// Deletes a role that nothing uses.
+#[utoipa::path(
+    delete,
+    path = "/v1/roles/{name}",
+    tag = "roles",
+    params(("name" = String, Path, description = "The role's name")),
+    responses((status = 200, description = "The role was deleted")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn delete_role_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    delete_role(state, admin, Json(RoleParams { name })).await
+}
@@ -1,7 +1,12 @@
-use crate::{auth, secrets, users};
+use crate::{
+    access, auth, environments, errors, groups, namespaces, openapi, policies, roles, secrets, ssh,
+    unseal, users,
+};
 use axum::{
-    http::StatusCode,
-    middleware,
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::Response,
     routing::{delete, get, post, put},
     Router,
 };
@@ -11,16 +16,156 @@ use std::sync::Arc;
 pub fn init_router<S: AppState>(state: S) -> Router {
     let state = Arc::new(state);
 
+    let unlocked = Router::new()
+        .route("/unseal", post(secrets::unlock))
+        .route("/unseal/challenge", post(unseal::unseal_challenge));
+
+    let v1 = v1_routes()
+        .merge(account_routes())
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            secrets::check_locked,
+        ))
+        .merge(unlocked.clone());
+
+    let legacy = legacy_routes()
+        .merge(account_routes())
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            secrets::check_locked,
+        ))
+        .merge(unlocked)
+        .layer(middleware::from_fn(deprecated));
+
+    Router::new()
+        .route("/health", get(health_check))
+        .route("/openapi.json", get(openapi::openapi_json))
+        .nest("/v1", v1)
+        .merge(legacy)
+        .layer(middleware::from_fn(errors::request_id))
+        .with_state(state)
+}
+
+/// Resource-style routes for secrets and the IAM entities.
+fn v1_routes<S: AppState>() -> Router<Arc<S>> {
+    Router::new()
+        .route(
+            "/secrets",
+            get(secrets::view_all_secrets_v1).post(secrets::create_secret),
+        )
+        .route(
+            "/secrets/*key",
+            get(secrets::view_secret_v1)
+                .put(secrets::update_secret_v1)
+                .delete(secrets::delete_secret_v1),
+        )
+        .route(
+            "/tags/:tag/secrets",
+            get(secrets::view_decrypted_secrets_by_tag_v1),
+        )
+        .route("/folders", get(secrets::view_folder))
+        .route("/folders/move", post(secrets::move_secrets))
+        .route("/environments/promote", post(environments::promote_secret))
+        .route("/audit", get(environments::view_audit_log))
+        .route("/users", get(users::view_users).post(users::create_user))
+        .route(
+            "/users/:name",
+            get(users::view_user_v1)
+                .patch(users::update_user_v1)
+                .delete(users::delete_user_v1),
+        )
+        .route("/users/:name/disable", post(users::disable_user_v1))
+        .route("/users/:name/enable", post(users::enable_user_v1))
+        .route("/users/:name/expiry", put(users::set_user_expiry_v1))
+        .route("/users/:name/reset_2fa", post(users::reset_two_factor_v1))
+        .route("/roles", get(roles::view_roles).post(roles::create_role))
+        .route(
+            "/roles/:name",
+            get(roles::view_role_v1)
+                .put(roles::update_role_v1)
+                .delete(roles::delete_role_v1),
+        )
+        .route(
+            "/groups",
+            get(groups::view_groups).post(groups::create_group),
+        )
+        .route(
+            "/groups/:name",
+            get(groups::view_group_v1)
+                .patch(groups::update_group_v1)
+                .delete(groups::delete_group_v1),
+        )
+        .route(
+            "/groups/:name/members",
+            post(groups::add_group_members_v1).delete(groups::remove_group_members_v1),
+        )
+        .route(
+            "/policies",
+            get(policies::view_policies).post(policies::create_policy),
+        )
+        .route(
+            "/policies/:name",
+            get(policies::view_policy_v1)
+                .put(policies::update_policy_v1)
+                .delete(policies::delete_policy_v1),
+        )
+        .route(
+            "/policies/:name/attachments",
+            post(policies::attach_policy_v1).delete(policies::detach_policy_v1),
+        )
+        .route(
+            "/namespaces",
+            get(namespaces::view_namespaces).post(namespaces::create_namespace),
+        )
+        .route("/namespaces/:name", delete(namespaces::delete_namespace))
+}
+
+/// The original routes, which take the names of what they act on in JSON bodies.
+/// These are kept as deprecated aliases of the `/v1` routes while clients migrate.
+fn legacy_routes<S: AppState>() -> Router<Arc<S>> {
     let user_router = Router::new()
         .route("/create", post(users::create_user))
         .route("/delete", delete(users::delete_user))
         .route("/update", put(users::update_user))
-        .route("/roles", post(users::view_user_roles));
+        .route("/roles", post(users::view_user_roles))
+        .route("/list", post(users::view_users))
+        .route("/disable", post(users::disable_user))
+        .route("/enable", post(users::enable_user))
+        .route("/expiry", put(users::set_user_expiry))
+        .route("/reset_2fa", post(users::reset_two_factor));
+
+    let policy_router = Router::new()
+        .route("/create", post(policies::create_policy))
+        .route("/list", post(policies::view_policies))
+        .route("/get", post(policies::view_policy))
+        .route("/update", put(policies::update_policy))
+        .route("/delete", delete(policies::delete_policy))
+        .route("/attach", post(policies::attach_policy))
+        .route("/detach", post(policies::detach_policy));
+
+    let role_router = Router::new()
+        .route("/create", post(roles::create_role))
+        .route("/list", post(roles::view_roles))
+        .route("/get", post(roles::view_role))
+        .route("/update", put(roles::update_role))
+        .route("/delete", delete(roles::delete_role));
+
+    let group_router = Router::new()
+        .route("/create", post(groups::create_group))
+        .route("/list", post(groups::view_groups))
+        .route("/get", post(groups::view_group))
+        .route("/update", put(groups::update_group))
+        .route("/delete", delete(groups::delete_group))
+        .route("/add_members", post(groups::add_group_members))
+        .route("/remove_members", post(groups::remove_group_members));
 
-    let router = Router::new()
+    Router::new()
         .route("/secrets/set", post(secrets::create_secret))
         .route("/secrets/get", post(secrets::view_secret))
-        .route("/secrets/by_tag", post(secrets::view_decrypted_secrets_by_tag))
+        .route(
+            "/secrets/by_tag",
+            post(secrets::view_decrypted_secrets_by_tag),
+        )
         .route(
             "/secrets",
             post(secrets::view_all_secrets)
@@ -28,20 +173,73 @@ pub fn init_router<S: AppState>(state: S) -> Router {
                 .delete(secrets::delete_secret),
         )
         .nest("/users", user_router)
+        .nest("/policies", policy_router)
+        .nest("/roles", role_router)
+        .nest("/groups", group_router)
+}
+
+/// Routes for logging in, the current user and instance administration,
+/// which are the same in every version of the API.
+fn account_routes<S: AppState>() -> Router<Arc<S>> {
+    let unseal_key_router = Router::new()
+        .route("/create", post(unseal::add_unseal_key))
+        .route("/list", post(unseal::view_unseal_keys))
+        .route("/delete", delete(unseal::delete_unseal_key));
+
+    let settings_router = Router::new().route(
+        "/two_factor",
+        post(users::view_two_factor_settings).put(users::update_two_factor_settings),
+    );
+
+    let access_router = Router::new()
+        .route("/secret", post(access::explain_secret_access))
+        .route("/user", post(access::explain_user_access))
+        .route("/simulate", post(access::simulate_access));
+
+    Router::new()
+        .route("/me", get(users::whoami))
+        .route("/me/password", put(users::change_password))
+        .route("/me/2fa/enroll", post(users::enroll_two_factor))
+        .route("/me/2fa/confirm", post(users::confirm_two_factor))
+        .route("/me/2fa/disable", post(users::disable_two_factor))
+        .route(
+            "/me/ssh_keys",
+            get(ssh::view_ssh_keys)
+                .post(ssh::add_ssh_key)
+                .delete(ssh::delete_ssh_key),
+        )
+        .nest("/access", access_router)
+        .nest("/settings", settings_router)
+        .nest("/unseal/keys", unseal_key_router)
+        .route("/init", post(users::init))
         .route("/login", post(auth::login))
+        .route("/login/ssh/challenge", post(ssh::ssh_challenge))
+        .route("/login/ssh", post(ssh::ssh_login))
+        .route("/login/cert", post(auth::cert_login))
+        .route("/failed_attempts", post(auth::view_failed_attempts))
         .route("/binfile", post(secrets::upload_binfile))
-        .layer(middleware::from_fn_with_state(
-            state.clone(),
-            secrets::check_locked,
-        ));
+}
 
-    Router::new()
-        .route("/unseal", post(secrets::unlock))
-        .route("/health", get(health_check))
-        .merge(router)
-        .with_state(state)
+/// Marks responses from the unversioned routes as deprecated, pointing clients at `/v1`.
+async fn deprecated(req: Request, next: Next) -> Response {
+    let mut res = next.run(req).await;
+
+    let headers = res.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    headers.insert(
+        header::LINK,
+        HeaderValue::from_static("</v1>; rel=\"successor-version\""),
+    );
+
+    res
 }
 
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "meta",
+    responses((status = 200, description = "The server is up")),
+)]
 pub async fn health_check() -> StatusCode {
     StatusCode::OK
 }
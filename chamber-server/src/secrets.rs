@@ -1,21 +1,22 @@
 use axum::{
+    extract::multipart::MultipartError,
     extract::Multipart,
-    extract::State,
-    http::{Request, StatusCode},
+    extract::{ConnectInfo, Path, Query, State},
+    http::{HeaderMap, HeaderValue, Request, StatusCode},
     middleware::Next,
     response::IntoResponse,
     Json,
 };
 use axum_extra::TypedHeader;
-use chamber_core::consts::KEYFILE_PATH;
-use chamber_crypto::secrets::EncryptedSecret;
-use ring::aead::{BoundKey, OpeningKey, SealingKey};
 
-use chamber_crypto::secrets::{KeyFile, NonceCounter};
-use serde::{Deserialize, Serialize};
+use chamber_crypto::secrets::KeyFile;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use utoipa::ToSchema;
 
+use crate::environments::Environment;
 use crate::errors::ApiError;
+use crate::keys::{data_key, replace_keyfile};
 
 use chamber_core::core::Database;
 use chamber_crypto::secrets::EncryptedSecretBuilder;
@@ -23,29 +24,51 @@ use chamber_crypto::signing::check_signing_key_exists;
 use chamber_core::traits::AppState;
 
 use crate::header::ChamberHeader;
-use chamber_core::core::CreateSecretParams;
-
-use crate::auth::Claims;
+use chamber_shared::secrets::{
+    CreateSecretParams, FolderQuery, ListSecretsArgs, ListSecretsQuery, MoveSecretsParams,
+    SecretFilter, SecretKey, SecretPublic, SecretTags, UpdateSecret, NEXT_CURSOR_HEADER,
+};
 
+use crate::auth::{check_root_key, client_ip, record_failed_attempt, unseal_limit_key, AuthUser};
+use crate::unseal::verify_unseal_signature;
+use chamber_core::errors::DatabaseError;
+use chamber_shared::ssh::UnsealParams;
+
+/// Stores a secret, encrypted with the instance's crypto key.
+#[utoipa::path(
+    post,
+    path = "/v1/secrets",
+    tag = "secrets",
+    params(Environment),
+    request_body = CreateSecretParams,
+    responses((status = 201, description = "The secret was stored")),
+    security(("bearer" = [])),
+)]
 #[tracing::instrument(skip_all, fields(key = secret.key))]
 pub async fn create_secret<S: AppState>(
     State(state): State<Arc<S>>,
-    _claim: Claims,
+    auth: AuthUser,
+    Environment(environment): Environment,
     Json(secret): Json<CreateSecretParams>,
 ) -> Result<impl IntoResponse, ApiError> {
+    let db = auth.db(state.as_ref()).in_environment(&environment);
+    let user = auth.0;
     let mut keyfile = state.get_keyfile()?;
 
     check_signing_key_exists()?;
 
+    let data_key = data_key(state.as_ref(), db.namespace()).await?;
+    let nonce = keyfile.next_nonce();
+
     let new_secret = EncryptedSecretBuilder::new(secret.key, secret.value)
         .with_access_level(secret.access_level)
         .with_tags(secret.tags)
         .with_whitelist(secret.role_whitelist)
-        .build(keyfile.get_crypto_seal_key(), keyfile.nonce_number);
+        .build(data_key.seal_key(nonce)?, nonce)?;
 
-    state.db().create_secret(new_secret).await?;
+    db.create_secret(user, new_secret).await?;
 
-    state.save_keyfile(keyfile);
+    state.save_keyfile(keyfile)?;
     tracing::info!("Secret created!");
 
     Ok(StatusCode::CREATED)
@@ -54,36 +77,33 @@ pub async fn create_secret<S: AppState>(
 #[tracing::instrument]
 pub async fn delete_secret<S: AppState>(
     State(state): State<Arc<S>>,
-    _claim: Claims,
+    auth: AuthUser,
+    Environment(environment): Environment,
     Json(SecretKey { key }): Json<SecretKey>,
 ) -> Result<impl IntoResponse, ApiError> {
-    state.db().delete_secret(key).await?;
+    let db = auth.db(state.as_ref()).in_environment(&environment);
+    let user = auth.0;
+    db.delete_secret(user, key).await?;
 
     Ok(StatusCode::OK)
 }
 
-#[derive(Deserialize, Debug)]
-pub struct SecretKey {
-    key: String,
-}
-
-#[derive(Deserialize, Debug)]
-pub struct ListSecretsArgs {
-    pub tag_filter: Option<String>,
-}
-
 #[tracing::instrument(fields(secret_key = secret.key))]
 pub async fn view_secret<S: AppState>(
     State(state): State<Arc<S>>,
-    claim: Claims,
+    auth: AuthUser,
+    Environment(environment): Environment,
     Json(secret): Json<SecretKey>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let user = state.db().get_user_from_name(claim.sub).await?;
-    let secret = state.db().view_secret_decrypted(user, secret.key).await?;
+    let db = auth.db(state.as_ref()).in_environment(&environment);
+    let user = auth.0;
+    let secret = db.view_secret_decrypted(user, secret.key).await?;
 
-    let unsealer = state.get_keyfile()?.get_crypto_open_key(secret.nonce.0);
+    let unsealer = data_key(state.as_ref(), db.namespace())
+        .await?
+        .open_key(secret.nonce.0)?;
 
-    let decrypted_secret = secret.decrypt(unsealer);
+    let decrypted_secret = secret.decrypt(unsealer)?;
 
     Ok(decrypted_secret)
 }
@@ -91,64 +111,249 @@ pub async fn view_secret<S: AppState>(
 #[tracing::instrument(skip_all)]
 pub async fn view_decrypted_secrets_by_tag<'a, S: AppState>(
     State(state): State<Arc<S>>,
-    claim: Claims,
+    auth: AuthUser,
+    Environment(environment): Environment,
     Json(secret): Json<SecretKey>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let user = state.db().get_user_from_name(claim.sub).await?;
-    let secrets = state.db().view_secrets_decrypted_by_tag(user, secret.key).await?;
-
-    let secrets = secrets.into_iter().map(|x| {
-    let unsealer = state.get_keyfile().unwrap().get_crypto_open_key(x.nonce.0);
+    let db = auth.db(state.as_ref()).in_environment(&environment);
+    let user = auth.0;
+    let secrets = db.view_secrets_decrypted_by_tag(user, secret.key).await?;
 
-        SecretPublic { value: x.decrypt(unsealer), key: x.key }
+    let data_key = data_key(state.as_ref(), db.namespace()).await?;
 
-    }).collect::<Vec<SecretPublic>>();
+    let secrets = secrets
+        .into_iter()
+        .map(|x| {
+            let unsealer = data_key.open_key(x.nonce.0)?;
 
+            Ok(SecretPublic {
+                value: x.decrypt(unsealer)?,
+                key: x.key,
+            })
+        })
+        .collect::<Result<Vec<SecretPublic>, ApiError>>()?;
 
     Ok(Json(secrets))
 }
 
-#[derive(Serialize, Debug)]
-pub struct SecretPublic {
-    key: String,
-    value: String
-}
-
 #[tracing::instrument]
 pub async fn view_all_secrets<S: AppState>(
     State(state): State<Arc<S>>,
-    claim: Claims,
+    auth: AuthUser,
+    Environment(environment): Environment,
     Json(secret): Json<ListSecretsArgs>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let user = state.db().get_user_from_name(claim.sub).await?;
+    let db = auth.db(state.as_ref()).in_environment(&environment);
+    let user = auth.0;
 
-    let secrets_info = state.db().view_all_secrets(user, secret.tag_filter).await?;
+    let page = db
+        .view_all_secrets(user, SecretFilter::tagged(secret.tag_filter))
+        .await?;
 
-    Ok(Json(secrets_info))
-}
-
-#[derive(Deserialize, Clone, Debug)]
-pub struct UpdateSecret {
-    key: String,
-    update_data: Vec<String>,
+    Ok(Json(page.secrets))
 }
 
 #[tracing::instrument]
 pub async fn update_secret<S: AppState>(
     State(state): State<Arc<S>>,
-    claim: Claims,
+    auth: AuthUser,
+    Environment(environment): Environment,
     Json(secret): Json<UpdateSecret>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let user = state.db().get_user_from_name(claim.sub).await?;
-    let mut secret_key = state.db().view_secret(user, secret.clone().key).await?;
+    let db = auth.db(state.as_ref()).in_environment(&environment);
+    let user = auth.0;
+    let mut secret_key = db.view_secret(user.clone(), secret.clone().key).await?;
 
     secret_key.replace_tags(secret.update_data);
 
-    state.db().update_secret(secret.key, secret_key).await?;
+    db.update_secret(user, secret.key, secret_key).await?;
 
     Ok(StatusCode::OK)
 }
 
+/// The form that `/binfile` takes. It's only used to document the endpoint.
+#[derive(ToSchema)]
+pub struct KeyfileUpload {
+    /// A keyfile from `chamber keygen`.
+    #[schema(value_type = String, format = Binary)]
+    pub file: Vec<u8>,
+}
+
+/// Lists the secrets that the user can read, without their values. Pages are requested with
+/// `limit`, and carried on with the `x-next-cursor` header of the previous page.
+#[utoipa::path(
+    get,
+    path = "/v1/secrets",
+    tag = "secrets",
+    params(ListSecretsQuery, Environment),
+    responses((
+        status = 200,
+        body = [SecretInfo],
+        headers(("x-next-cursor" = String, description = "The cursor for the next page")),
+    )),
+    security(("bearer" = [])),
+)]
+#[tracing::instrument]
+pub async fn view_all_secrets_v1<S: AppState>(
+    State(state): State<Arc<S>>,
+    auth: AuthUser,
+    Environment(environment): Environment,
+    Query(query): Query<ListSecretsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let page = auth
+        .db(state.as_ref())
+        .in_environment(&environment)
+        .view_all_secrets(auth.0, query.into())
+        .await?;
+
+    let mut headers = HeaderMap::new();
+
+    if let Some(cursor) = page.next_cursor {
+        if let Ok(value) = HeaderValue::from_str(&cursor) {
+            headers.insert(NEXT_CURSOR_HEADER, value);
+        }
+    }
+
+    Ok((headers, Json(page.secrets)))
+}
+
+/// Lists the folders and secrets directly inside a folder, out of the secrets that the user
+/// can read.
+#[utoipa::path(
+    get,
+    path = "/v1/folders",
+    tag = "secrets",
+    params(FolderQuery, Environment),
+    responses((status = 200, body = FolderListing)),
+    security(("bearer" = [])),
+)]
+#[tracing::instrument]
+pub async fn view_folder<S: AppState>(
+    State(state): State<Arc<S>>,
+    auth: AuthUser,
+    Environment(environment): Environment,
+    Query(query): Query<FolderQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let folder = auth
+        .db(state.as_ref())
+        .in_environment(&environment)
+        .view_folder(auth.0, query.prefix.unwrap_or_default())
+        .await?;
+
+    Ok(Json(folder))
+}
+
+/// Moves or renames a secret, or a folder along with everything in it. Either everything is
+/// moved or nothing is. Only the request's environment is moved, so the same keys in other
+/// environments keep their old names.
+#[utoipa::path(
+    post,
+    path = "/v1/folders/move",
+    tag = "secrets",
+    params(Environment),
+    request_body = MoveSecretsParams,
+    responses((status = 200, body = [MovedSecret])),
+    security(("bearer" = [])),
+)]
+#[tracing::instrument]
+pub async fn move_secrets<S: AppState>(
+    State(state): State<Arc<S>>,
+    auth: AuthUser,
+    Environment(environment): Environment,
+    Json(params): Json<MoveSecretsParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let moved = auth
+        .db(state.as_ref())
+        .in_environment(&environment)
+        .move_secrets(auth.0, params.from, params.to)
+        .await?;
+
+    tracing::info!("Moved {} secrets", moved.len());
+
+    Ok(Json(moved))
+}
+
+/// Decrypts a secret.
+#[utoipa::path(
+    get,
+    path = "/v1/secrets/{key}",
+    tag = "secrets",
+    params(("key" = String, Path, description = "The secret's key"), Environment),
+    responses(
+        (status = 200, body = String, content_type = "text/plain", description = "The value"),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn view_secret_v1<S: AppState>(
+    state: State<Arc<S>>,
+    auth: AuthUser,
+    environment: Environment,
+    Path(key): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    view_secret(state, auth, environment, Json(SecretKey { key })).await
+}
+
+/// Replaces a secret's tags.
+#[utoipa::path(
+    put,
+    path = "/v1/secrets/{key}",
+    tag = "secrets",
+    params(("key" = String, Path, description = "The secret's key"), Environment),
+    request_body = SecretTags,
+    responses((status = 200, description = "The tags were replaced")),
+    security(("bearer" = [])),
+)]
+pub async fn update_secret_v1<S: AppState>(
+    state: State<Arc<S>>,
+    auth: AuthUser,
+    environment: Environment,
+    Path(key): Path<String>,
+    Json(SecretTags { tags }): Json<SecretTags>,
+) -> Result<impl IntoResponse, ApiError> {
+    let secret = UpdateSecret {
+        key,
+        update_data: tags,
+    };
+
+    update_secret(state, auth, environment, Json(secret)).await
+}
+
+/// Deletes a secret.
+#[utoipa::path(
+    delete,
+    path = "/v1/secrets/{key}",
+    tag = "secrets",
+    params(("key" = String, Path, description = "The secret's key"), Environment),
+    responses((status = 200, description = "The secret was deleted")),
+    security(("bearer" = [])),
+)]
+pub async fn delete_secret_v1<S: AppState>(
+    state: State<Arc<S>>,
+    auth: AuthUser,
+    environment: Environment,
+    Path(key): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    delete_secret(state, auth, environment, Json(SecretKey { key })).await
+}
+
+/// Decrypts every secret with a tag that the user can read.
+#[utoipa::path(
+    get,
+    path = "/v1/tags/{tag}/secrets",
+    tag = "secrets",
+    params(("tag" = String, Path, description = "The tag to match"), Environment),
+    responses((status = 200, body = [SecretPublic])),
+    security(("bearer" = [])),
+)]
+pub async fn view_decrypted_secrets_by_tag_v1<S: AppState>(
+    state: State<Arc<S>>,
+    auth: AuthUser,
+    environment: Environment,
+    Path(tag): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    view_decrypted_secrets_by_tag(state, auth, environment, Json(SecretKey { key: tag })).await
+}
+
 pub async fn check_locked<S: AppState>(
     State(state): State<Arc<S>>,
     req: Request<axum::body::Body>,
@@ -160,61 +365,122 @@ pub async fn check_locked<S: AppState>(
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/binfile",
+    tag = "admin",
+    request_body(content = KeyfileUpload, content_type = "multipart/form-data"),
+    responses((status = 200, description = "Every data key was wrapped with the new keyfile")),
+    security(("root_key" = [])),
+)]
 #[tracing::instrument]
 pub async fn upload_binfile<S: AppState>(
     State(state): State<Arc<S>>,
+    TypedHeader(auth): TypedHeader<ChamberHeader>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse, ApiError> {
+    // The new keyfile replaces the root key along with the crypto key
+    check_root_key(state.as_ref(), auth, &client_ip(&headers, connect_info))?;
+
     let mut data: Option<Vec<u8>> = None;
 
-    while let Some(field) = multipart.next_field().await.unwrap() {
-        data = Some(field.bytes().await.unwrap().to_vec());
+    while let Some(field) = multipart.next_field().await.map_err(bad_multipart)? {
+        data = Some(field.bytes().await.map_err(bad_multipart)?.to_vec());
     }
 
-    let data: Vec<u8> = data.unwrap();
+    let data: Vec<u8> =
+        data.ok_or_else(|| ApiError::BadRequest("No keyfile was uploaded".to_string()))?;
 
-    let decoded: KeyFile = bincode::deserialize(&data.clone()).unwrap();
+    let decoded: KeyFile = bincode::deserialize(&data)
+        .map_err(|_| ApiError::BadRequest("The keyfile couldn't be decoded".to_string()))?;
 
-    let secrets = state.db().view_all_secrets_admin().await?;
+    // Checked before anything is re-encrypted, so that a bad keyfile can't lose secrets
+    decoded
+        .crypto_key()
+        .make_key()
+        .map_err(|_| ApiError::BadRequest("The keyfile's crypto key is invalid".to_string()))?;
 
-    let secrets: Vec<EncryptedSecret> = secrets
-        .into_iter()
-        .map(|mut secret| {
-            let unbound_key_old = state.get_keyfile().unwrap().crypto_key().make_key();
-            let unbound_key_new = decoded.crypto_key().make_key();
-
-            let nonce_sequence_open = NonceCounter::from_num(secret.nonce.inner());
-            let nonce_sequence_seal = NonceCounter::from_num(secret.nonce.inner());
-            let opening_key = OpeningKey::new(unbound_key_old, nonce_sequence_open);
-            let sealing_key = SealingKey::new(unbound_key_new, nonce_sequence_seal);
-
-            secret.reencrypt(opening_key, sealing_key);
-
-            secret
-        })
-        .collect();
-
-    state.db().rekey_all_secrets(secrets).await?;
-
-    std::fs::write(KEYFILE_PATH, data)?;
-
-    state.save_keyfile(decoded)?;
+    replace_keyfile(state.as_ref(), decoded).await?;
 
     tracing::warn!("New chamberfile uploaded");
 
     Ok(StatusCode::OK)
 }
 
-#[tracing::instrument(skip(state))]
+fn bad_multipart(e: MultipartError) -> ApiError {
+    ApiError::BadRequest(e.body_text())
+}
+
+/// Unseals with a signed challenge from `/unseal/challenge`. Until an unseal key has been
+/// registered, the root key in the `x-chamber-key` header is accepted instead.
+#[utoipa::path(
+    post,
+    path = "/v1/unseal",
+    tag = "admin",
+    request_body(
+        content = Option<UnsealParams>,
+        description = "A signed challenge. It can be left out until unseal keys are registered",
+    ),
+    responses((status = 200, description = "The vault was unsealed")),
+    security((), ("root_key" = [])),
+)]
+#[tracing::instrument(skip_all)]
 pub async fn unlock<S: AppState>(
     State(state): State<Arc<S>>,
-    TypedHeader(auth): TypedHeader<ChamberHeader>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    auth: Option<TypedHeader<ChamberHeader>>,
+    params: Option<Json<UnsealParams>>,
 ) -> Result<impl IntoResponse, ApiError> {
-    if auth.key() != state.get_keyfile()?.unseal_key() {
-        tracing::warn!("Unseal didn't match {}", state.get_keyfile()?.unseal_key());
-        return Err(ApiError::Forbidden);
+    let ip = client_ip(&headers, connect_info);
+    let limit_key = unseal_limit_key(&ip);
+
+    let limits = state.auth_limits();
+    if let Some(wait) = limits.unseal.check(&limit_key) {
+        return Err(ApiError::TooManyRequests(wait));
     }
 
+    let keys_registered = !state.get_unseal_keys()?.is_empty();
+
+    let verified = match (params, auth) {
+        (Some(Json(params)), _) => verify_unseal_signature(state.as_ref(), &params),
+        (None, Some(TypedHeader(auth))) if !keys_registered => {
+            match auth.key() == state.get_keyfile()?.unseal_key() {
+                true => Ok("root key".to_string()),
+                false => Err(DatabaseError::Forbidden),
+            }
+        }
+        (None, Some(_)) => {
+            return Err(ApiError::BadRequest(
+                "Unseal keys have been registered, so the root key can no longer unseal"
+                    .to_string(),
+            ))
+        }
+        (None, None) => {
+            return Err(ApiError::BadRequest(
+                "Send a signed challenge or the root key".to_string(),
+            ))
+        }
+    };
+
+    let operator = match verified {
+        Ok(res) => res,
+        Err(e) => {
+            limits.unseal.record_failure(&limit_key);
+
+            tracing::warn!("Failed unseal attempt from {ip}");
+            record_failed_attempt(state.as_ref(), "unseal", None, ip, &e).await;
+
+            return Err(ApiError::Forbidden);
+        }
+    };
+
+    tracing::info!("Unseal authorised by {operator}");
+
+    limits.unseal.reset(&limit_key);
+
     match state.locked_status().unlock().await {
         Ok(true) => {
             tracing::info!("Vault has been unlocked!");
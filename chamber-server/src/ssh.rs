@@ -0,0 +1,207 @@
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chamber_core::errors::DatabaseError;
+use chamber_core::ssh;
+use chamber_shared::namespaces::DEFAULT_NAMESPACE;
+use chamber_shared::ssh::{
+    AddSshKeyParams, RemoveSshKeyParams, SshChallenge, SshChallengeParams, SshKey, SshLoginParams,
+    SSH_LOGIN_NAMESPACE,
+};
+use chamber_shared::AuthBody;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::auth::{
+    check_second_factor, client_ip, fail_login, issue_token, qualified_name, AuthError, Claims,
+};
+use crate::errors::ApiError;
+
+use chamber_core::core::Database;
+use chamber_core::traits::AppState;
+
+#[utoipa::path(
+    get,
+    path = "/v1/me/ssh_keys",
+    tag = "account",
+    responses((status = 200, body = [SshKey])),
+    security(("bearer" = [])),
+)]
+pub async fn view_ssh_keys<S: AppState>(
+    State(state): State<Arc<S>>,
+    claim: Claims,
+) -> Result<Json<Vec<SshKey>>, ApiError> {
+    let res = state
+        .db()
+        .in_namespace(&claim.namespace)
+        .view_ssh_keys(claim.sub)
+        .await?;
+
+    Ok(Json(res))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/me/ssh_keys",
+    tag = "account",
+    request_body = AddSshKeyParams,
+    responses((status = 201, body = SshKey)),
+    security(("bearer" = [])),
+)]
+pub async fn add_ssh_key<S: AppState>(
+    State(state): State<Arc<S>>,
+    claim: Claims,
+    Json(params): Json<AddSshKeyParams>,
+) -> Result<(StatusCode, Json<SshKey>), ApiError> {
+    let key = ssh::parse_public_key(&params.public_key)?;
+
+    state
+        .db()
+        .in_namespace(&claim.namespace)
+        .add_ssh_key(claim.sub, key.clone())
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(key)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/me/ssh_keys",
+    tag = "account",
+    request_body = RemoveSshKeyParams,
+    responses((status = 200, description = "The key was removed")),
+    security(("bearer" = [])),
+)]
+pub async fn delete_ssh_key<S: AppState>(
+    State(state): State<Arc<S>>,
+    claim: Claims,
+    Json(params): Json<RemoveSshKeyParams>,
+) -> Result<StatusCode, ApiError> {
+    state
+        .db()
+        .in_namespace(&claim.namespace)
+        .delete_ssh_key(claim.sub, params.fingerprint)
+        .await?;
+
+    Ok(StatusCode::OK)
+}
+
+/// Hands out a nonce to sign. This doesn't reveal whether the user exists or has any keys.
+#[utoipa::path(
+    post,
+    path = "/v1/login/ssh/challenge",
+    tag = "auth",
+    request_body = SshChallengeParams,
+    responses((status = 200, body = SshChallenge)),
+)]
+pub async fn ssh_challenge<S: AppState>(
+    State(state): State<Arc<S>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(params): Json<SshChallengeParams>,
+) -> Result<Json<SshChallenge>, AuthError> {
+    if params.username.is_empty() {
+        return Err(AuthError::MissingCredentials);
+    }
+
+    let ip = client_ip(&headers, connect_info);
+    if let Some(wait) = state.auth_limits().challenges.throttle(&format!("ip:{ip}")) {
+        return Err(AuthError::TooManyAttempts(wait));
+    }
+
+    let namespace = params
+        .namespace
+        .unwrap_or_else(|| DEFAULT_NAMESPACE.to_string());
+
+    let challenges = state.challenges();
+    let nonce = challenges
+        .issue(&format!(
+            "user:{}",
+            qualified_name(&namespace, &params.username)
+        ))
+        .map_err(|_| AuthError::TooManyAttempts(challenges.ttl()))?;
+
+    Ok(Json(SshChallenge {
+        nonce,
+        namespace: SSH_LOGIN_NAMESPACE.to_string(),
+        expires_in_secs: challenges.ttl().as_secs(),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/login/ssh",
+    tag = "auth",
+    request_body = SshLoginParams,
+    responses((status = 200, body = AuthBody)),
+)]
+pub async fn ssh_login<S: AppState>(
+    State(state): State<Arc<S>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(params): Json<SshLoginParams>,
+) -> Result<(StatusCode, Json<AuthBody>), AuthError> {
+    if params.username.is_empty() | params.signature.is_empty() {
+        return Err(AuthError::MissingCredentials);
+    }
+
+    let namespace = params
+        .namespace
+        .clone()
+        .unwrap_or_else(|| DEFAULT_NAMESPACE.to_string());
+
+    let ip = client_ip(&headers, connect_info);
+    let username = qualified_name(&namespace, &params.username);
+    let user_key = format!("user:{username}");
+
+    let limits = state.auth_limits();
+    let ip_wait = limits.login.check(&format!("ip:{ip}"));
+    if let Some(wait) = ip_wait.or(limits.login.check(&user_key)) {
+        return Err(AuthError::TooManyAttempts(wait));
+    }
+
+    let verified = verify_ssh_login(state.as_ref(), &namespace, &params).await;
+
+    let mut user = match verified {
+        Ok(res) => res,
+        Err(e) => return Err(fail_login(state.as_ref(), "ssh_login", username, ip, e).await),
+    };
+
+    let mfa = check_second_factor(state.as_ref(), &mut user, params.code, "ssh_login", ip).await?;
+
+    limits.login.reset(&user_key);
+
+    user.check_active().map_err(AuthError::InactiveUser)?;
+
+    Ok((StatusCode::OK, Json(issue_token(&user, mfa)?)))
+}
+
+/// Checks that the nonce was issued to this user and that one of their keys signed it.
+async fn verify_ssh_login<S: AppState>(
+    state: &S,
+    namespace: &str,
+    params: &SshLoginParams,
+) -> Result<chamber_core::users::User, DatabaseError> {
+    let subject = format!("user:{}", qualified_name(namespace, &params.username));
+    if !state.challenges().take(&params.nonce, &subject) {
+        return Err(DatabaseError::InvalidChallenge);
+    }
+
+    let db = state.db().in_namespace(namespace);
+    let user = db.get_user_from_name(params.username.clone()).await?;
+
+    let (sig, fingerprint) = ssh::signature_fingerprint(&params.signature)?;
+
+    let key = db
+        .view_ssh_keys(params.username.clone())
+        .await?
+        .into_iter()
+        .find(|x| x.fingerprint == fingerprint)
+        .ok_or(DatabaseError::SshKeyNotFound)?;
+
+    ssh::verify_signature(&key, SSH_LOGIN_NAMESPACE, params.nonce.as_bytes(), &sig)?;
+
+    Ok(user)
+}
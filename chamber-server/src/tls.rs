@@ -0,0 +1,190 @@
+use axum::extract::ConnectInfo;
+use axum::{Extension, Router};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::service::TowerToHyperService;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tower::Layer;
+
+use crate::auth::ClientCertificate;
+use chamber_shared::namespaces::DEFAULT_NAMESPACE;
+
+pub static TLS_CERT_VAR: &str = "CHAMBER_TLS_CERT";
+pub static TLS_KEY_VAR: &str = "CHAMBER_TLS_KEY";
+pub static TLS_CLIENT_CA_VAR: &str = "CHAMBER_TLS_CLIENT_CA";
+
+#[derive(thiserror::Error, Debug)]
+pub enum TlsError {
+    #[error("{0} and {1} need to be set together")]
+    IncompleteConfig(&'static str, &'static str),
+    #[error("Couldn't read {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("No certificates were found in {0}")]
+    NoCertificates(PathBuf),
+    #[error("No private key was found in {0}")]
+    NoPrivateKey(PathBuf),
+    #[error("Invalid client CA: {0}")]
+    ClientVerifier(#[from] rustls::server::VerifierBuilderError),
+    #[error("TLS error: {0}")]
+    Rustls(#[from] rustls::Error),
+}
+
+/// Paths to the PEM files used to terminate TLS, for deployments that aren't behind a proxy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// When set, clients can present a certificate signed by this CA and log in with it.
+    pub client_ca: Option<PathBuf>,
+}
+
+impl TlsConfig {
+    /// Reads the paths from the environment. Returns `None` when TLS hasn't been configured.
+    pub fn from_env() -> Result<Option<Self>, TlsError> {
+        let cert = std::env::var(TLS_CERT_VAR).ok();
+        let key = std::env::var(TLS_KEY_VAR).ok();
+
+        let (cert, key) = match (cert, key) {
+            (Some(cert), Some(key)) => (cert, key),
+            (None, None) => return Ok(None),
+            _ => return Err(TlsError::IncompleteConfig(TLS_CERT_VAR, TLS_KEY_VAR)),
+        };
+
+        Ok(Some(Self {
+            cert: cert.into(),
+            key: key.into(),
+            client_ca: std::env::var(TLS_CLIENT_CA_VAR).ok().map(PathBuf::from),
+        }))
+    }
+
+    /// Builds the rustls config. Client certificates are optional even with a client CA,
+    /// so that users without one can still log in with a password.
+    pub fn server_config(&self) -> Result<ServerConfig, TlsError> {
+        let certs = load_certs(&self.cert)?;
+        let key = load_key(&self.key)?;
+
+        let builder = match &self.client_ca {
+            Some(path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in load_certs(path)? {
+                    roots.add(cert)?;
+                }
+
+                let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+                    .allow_unauthenticated()
+                    .build()?;
+
+                ServerConfig::builder().with_client_cert_verifier(verifier)
+            }
+            None => ServerConfig::builder().with_no_client_auth(),
+        };
+
+        let mut config = builder.with_single_cert(certs, key)?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        Ok(config)
+    }
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let file = File::open(path).map_err(|e| TlsError::Io(path.to_owned(), e))?;
+
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| TlsError::Io(path.to_owned(), e))?;
+
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificates(path.to_owned()));
+    }
+
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>, TlsError> {
+    let file = File::open(path).map_err(|e| TlsError::Io(path.to_owned(), e))?;
+
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| TlsError::Io(path.to_owned(), e))?
+        .ok_or_else(|| TlsError::NoPrivateKey(path.to_owned()))
+}
+
+/// Reads the identity from a client certificate that rustls has already verified.
+/// The subject's common name is the username that the certificate logs in as, and its
+/// organisational unit is the user's namespace. Certificates without one are for the default
+/// namespace.
+pub fn client_certificate(der: &[u8]) -> Option<ClientCertificate> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der).ok()?;
+
+    let username = cert
+        .subject()
+        .iter_common_name()
+        .next()
+        .and_then(|x| x.as_str().ok())?
+        .to_string();
+
+    let namespace = match cert.subject().iter_organizational_unit().next() {
+        Some(x) => x.as_str().ok()?.to_string(),
+        None => DEFAULT_NAMESPACE.to_string(),
+    };
+
+    Some(ClientCertificate {
+        username,
+        namespace,
+        subject: cert.subject().to_string(),
+    })
+}
+
+/// Accepts TLS connections and serves the router over them. The client's address and
+/// certificate, if it sent one, are added to each request's extensions.
+pub async fn serve(listener: TcpListener, config: ServerConfig, router: Router) {
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+
+    loop {
+        let (stream, addr) = match listener.accept().await {
+            Ok(res) => res,
+            Err(e) => {
+                tracing::warn!("Couldn't accept a connection: {e}");
+                continue;
+            }
+        };
+
+        let acceptor = acceptor.clone();
+        let router = router.clone();
+
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(res) => res,
+                Err(e) => {
+                    tracing::debug!("TLS handshake with {addr} failed: {e}");
+                    return;
+                }
+            };
+
+            let cert = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|x| x.first())
+                .and_then(|x| client_certificate(x));
+
+            let service =
+                Extension(ConnectInfo::<SocketAddr>(addr)).layer(Extension(cert).layer(router));
+
+            if let Err(e) = Builder::new(TokioExecutor::new())
+                .serve_connection(TokioIo::new(stream), TowerToHyperService::new(service))
+                .await
+            {
+                tracing::debug!("Connection with {addr} closed: {e}");
+            }
+        });
+    }
+}
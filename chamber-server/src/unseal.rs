@@ -0,0 +1,221 @@
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use axum_extra::TypedHeader;
+use chamber_core::errors::DatabaseError;
+use chamber_core::ssh;
+use chamber_shared::ssh::{
+    AddUnsealKeyParams, RemoveUnsealKeyParams, SshChallenge, UnsealKey, UnsealParams,
+    SSH_UNSEAL_NAMESPACE,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::auth::{check_root_key, client_ip, record_failed_attempt, unseal_limit_key};
+use crate::errors::ApiError;
+use crate::header::ChamberHeader;
+
+use chamber_core::traits::AppState;
+
+/// Every unseal challenge is issued to the same subject, as operators aren't users.
+pub static UNSEAL_CHALLENGE_SUBJECT: &str = "unseal";
+
+#[utoipa::path(
+    post,
+    path = "/v1/unseal/keys/list",
+    tag = "admin",
+    responses((status = 200, body = [UnsealKey])),
+    security(("root_key" = [])),
+)]
+pub async fn view_unseal_keys<S: AppState>(
+    State(state): State<Arc<S>>,
+    TypedHeader(auth): TypedHeader<ChamberHeader>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<UnsealKey>>, ApiError> {
+    check_root_key(state.as_ref(), auth, &client_ip(&headers, connect_info))?;
+
+    let res = state.get_unseal_keys()?;
+
+    Ok(Json(res))
+}
+
+/// Registers an operator's unseal key. After the first one, this needs a challenge signed by a
+/// key that's already registered as well as the root key.
+#[utoipa::path(
+    post,
+    path = "/v1/unseal/keys/create",
+    tag = "admin",
+    request_body = AddUnsealKeyParams,
+    responses((status = 201, body = UnsealKey)),
+    security(("root_key" = [])),
+)]
+pub async fn add_unseal_key<S: AppState>(
+    State(state): State<Arc<S>>,
+    TypedHeader(auth): TypedHeader<ChamberHeader>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(params): Json<AddUnsealKeyParams>,
+) -> Result<(StatusCode, Json<UnsealKey>), ApiError> {
+    let ip = client_ip(&headers, connect_info);
+    check_root_key(state.as_ref(), auth, &ip)?;
+    check_approval(state.as_ref(), params.approval.as_ref(), ip).await?;
+
+    if params.name.is_empty() {
+        return Err(ApiError::BadRequest("Unseal keys need a name".to_string()));
+    }
+
+    let key = UnsealKey {
+        name: params.name,
+        key: ssh::parse_public_key(&params.public_key)?,
+    };
+
+    let mut keys = state.get_unseal_keys()?;
+
+    if keys
+        .iter()
+        .any(|x| x.key.fingerprint == key.key.fingerprint)
+    {
+        return Err(DatabaseError::SshKeyAlreadyExists.into());
+    }
+
+    keys.push(key.clone());
+    state.save_unseal_keys(keys)?;
+
+    tracing::info!("Added unseal key {} for {}", key.key.fingerprint, key.name);
+
+    Ok((StatusCode::CREATED, Json(key)))
+}
+
+/// Removes an unseal key, with the approval of one of the registered keys.
+#[utoipa::path(
+    delete,
+    path = "/v1/unseal/keys/delete",
+    tag = "admin",
+    request_body = RemoveUnsealKeyParams,
+    responses((status = 200, description = "The key was removed")),
+    security(("root_key" = [])),
+)]
+pub async fn delete_unseal_key<S: AppState>(
+    State(state): State<Arc<S>>,
+    TypedHeader(auth): TypedHeader<ChamberHeader>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(params): Json<RemoveUnsealKeyParams>,
+) -> Result<StatusCode, ApiError> {
+    let ip = client_ip(&headers, connect_info);
+    check_root_key(state.as_ref(), auth, &ip)?;
+    check_approval(state.as_ref(), Some(&params.approval), ip).await?;
+
+    let mut keys = state.get_unseal_keys()?;
+    let count = keys.len();
+
+    keys.retain(|x| x.key.fingerprint != params.fingerprint);
+
+    if keys.len() == count {
+        return Err(DatabaseError::SshKeyNotFound.into());
+    }
+
+    state.save_unseal_keys(keys)?;
+
+    tracing::info!("Removed unseal key {}", params.fingerprint);
+
+    Ok(StatusCode::OK)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/unseal/challenge",
+    tag = "admin",
+    responses((status = 200, body = SshChallenge)),
+)]
+pub async fn unseal_challenge<S: AppState>(
+    State(state): State<Arc<S>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> Result<Json<SshChallenge>, ApiError> {
+    let ip = client_ip(&headers, connect_info);
+    if let Some(wait) = state.auth_limits().challenges.throttle(&format!("ip:{ip}")) {
+        return Err(ApiError::TooManyRequests(wait));
+    }
+
+    let challenges = state.unseal_challenges();
+    let nonce = challenges.issue(UNSEAL_CHALLENGE_SUBJECT)?;
+
+    Ok(Json(SshChallenge {
+        nonce,
+        namespace: SSH_UNSEAL_NAMESPACE.to_string(),
+        expires_in_secs: challenges.ttl().as_secs(),
+    }))
+}
+
+/// Once unseal keys are registered, changing them also needs a challenge signed by one of them.
+/// Otherwise whoever had the root key could register their own key and unseal without the
+/// operators.
+async fn check_approval<S: AppState>(
+    state: &S,
+    approval: Option<&UnsealParams>,
+    ip: String,
+) -> Result<(), ApiError> {
+    if state.get_unseal_keys()?.is_empty() {
+        return Ok(());
+    }
+
+    let Some(approval) = approval else {
+        return Err(ApiError::BadRequest(
+            "Unseal keys have been registered, so changing them needs a challenge signed by one"
+                .to_string(),
+        ));
+    };
+
+    match verify_unseal_signature(state, approval) {
+        Ok(operator) => {
+            tracing::info!("Change to the unseal keys approved by {operator}");
+            Ok(())
+        }
+        Err(e) => {
+            state
+                .auth_limits()
+                .unseal
+                .record_failure(&unseal_limit_key(&ip));
+
+            tracing::warn!("Unapproved change to the unseal keys from {ip}");
+            record_failed_attempt(state, "unseal", None, ip, &e).await;
+
+            Err(ApiError::Forbidden)
+        }
+    }
+}
+
+/// Checks that the nonce is one of ours and that a registered unseal key signed it.
+/// Returns the name of the operator who signed it.
+pub fn verify_unseal_signature<S: AppState>(
+    state: &S,
+    params: &UnsealParams,
+) -> Result<String, DatabaseError> {
+    if !state
+        .unseal_challenges()
+        .take(&params.nonce, UNSEAL_CHALLENGE_SUBJECT)
+    {
+        return Err(DatabaseError::InvalidChallenge);
+    }
+
+    let (sig, fingerprint) = ssh::signature_fingerprint(&params.signature)?;
+
+    let key = state
+        .get_unseal_keys()?
+        .into_iter()
+        .find(|x| x.key.fingerprint == fingerprint)
+        .ok_or(DatabaseError::SshKeyNotFound)?;
+
+    ssh::verify_signature(
+        &key.key,
+        SSH_UNSEAL_NAMESPACE,
+        params.nonce.as_bytes(),
+        &sig,
+    )?;
+
+    Ok(key.name)
+}
@@ -1,73 +1,159 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
 use axum_extra::TypedHeader;
-use serde::Deserialize;
 
+use crate::auth::{check_root_key, client_ip, two_factor_required, Claims, EnrollmentClaims, NamespaceAdmin};
 use crate::errors::ApiError;
 use crate::header::ChamberHeader;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use chamber_core::users::User;
+use chamber_core::consts::ROOT_ACCESS_LEVEL;
+use chamber_core::totp;
+use chamber_shared::users::{
+    ChangePasswordParams, CreateUserParams, DisableTwoFactorParams, InitParams, InitResponse,
+    RecoveryCodes, TwoFactorCodeParams, TwoFactorEnrollment, TwoFactorPasswordParams,
+    TwoFactorSettings, UpdateUserParams, UserChanges, UserExpiry, UserExpiryParams, UserParams,
+    UserProfile, UserSummary,
+};
 
 use chamber_core::core::Database;
 use chamber_core::traits::AppState;
-#[derive(Deserialize)]
-pub struct UserParams {
-    name: String,
-}
+/// Colons are kept for externally authenticated users, who are named `<provider>:<sub>`.
+fn check_username(username: &str) -> Result<(), ApiError> {
+    if username.contains(':') {
+        return Err(ApiError::BadRequest(
+            "Usernames can't contain ':'".to_string(),
+        ));
+    }
 
-#[derive(Deserialize)]
-pub struct CreateUserParams {
-    pub username: String,
-    pub password: String,
-    pub access_level: Option<i32>,
-    pub roles: Option<Vec<String>>,
+    Ok(())
 }
 
-#[derive(Deserialize)]
-pub struct UpdateUserParams {
-    pub username: String,
-    pub access_level: Option<i32>,
-    pub roles: Option<Vec<String>>,
+/// Creates the first admin of a fresh instance, who also administers the default namespace.
+/// Fails once the default namespace has any users.
+#[utoipa::path(
+    post,
+    path = "/v1/init",
+    tag = "users",
+    request_body = InitParams,
+    responses((status = 201, body = InitResponse)),
+    security(("root_key" = [])),
+)]
+pub async fn init<S: AppState>(
+    State(state): State<Arc<S>>,
+    TypedHeader(auth): TypedHeader<ChamberHeader>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(params): Json<InitParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    check_root_key(state.as_ref(), auth, &client_ip(&headers, connect_info))?;
+
+    let username = params.username.unwrap_or_else(|| "root".to_string());
+    check_username(&username)?;
+
+    let (password, generated) = match params.password {
+        Some(password) if password.is_empty() => {
+            return Err(ApiError::BadRequest(
+                "The password can't be empty".to_string(),
+            ))
+        }
+        Some(password) => (password, None),
+        None => {
+            let password = User::generate_password();
+            (password.clone(), Some(password))
+        }
+    };
+
+    let mut user = User::new(username.clone());
+    user.set_password(&state.hash_config(), &password).await?;
+    user.set_access_level(ROOT_ACCESS_LEVEL);
+    user.set_namespace_admin(true);
+
+    state.db().bootstrap_user(user).await?;
+
+    tracing::info!("Chamber has been initialised with admin user {username}");
+
+    Ok((
+        StatusCode::CREATED,
+        Json(InitResponse {
+            username,
+            password: generated,
+        }),
+    ))
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/users",
+    tag = "users",
+    request_body = CreateUserParams,
+    responses(
+        (status = 201, body = String, content_type = "text/plain", description = "The user's name"),
+    ),
+    security(("root_key" = []), ("bearer" = [])),
+)]
 pub async fn create_user<S: AppState>(
     State(state): State<Arc<S>>,
-    TypedHeader(_auth): TypedHeader<ChamberHeader>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
     Json(params): Json<CreateUserParams>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let user = User::new(params.username, params.password);
+    check_username(&params.username)?;
+
+    let db = state.db().in_namespace(&namespace);
+
+    let mut user = User::new(params.username);
+    user.set_password(&state.hash_config(), &params.password)
+        .await?;
+    user.set_namespace_admin(params.namespace_admin.unwrap_or_default());
 
-    let res = state.db().create_user(user).await?;
+    let res = db.create_user(user).await?;
 
     Ok((StatusCode::CREATED, res))
 }
 
 pub async fn delete_user<S: AppState>(
     State(state): State<Arc<S>>,
-    TypedHeader(_auth): TypedHeader<ChamberHeader>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
     Json(UserParams { name }): Json<UserParams>,
 ) -> Result<StatusCode, ApiError> {
-    state.db().delete_user(name).await?;
+    let db = state.db().in_namespace(&namespace);
+
+    db.delete_user(name).await?;
 
     Ok(StatusCode::OK)
 }
 
 pub async fn view_user_roles<S: AppState>(
     State(state): State<Arc<S>>,
-    TypedHeader(_auth): TypedHeader<ChamberHeader>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
     Json(UserParams { name }): Json<UserParams>,
-) -> Result<Json<User>, ApiError> {
-    let res = state.db().get_user_from_name(name).await?;
+) -> Result<Json<UserSummary>, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let res = db.get_user_from_name(name).await?;
 
-    Ok(Json(res))
+    Ok(Json(res.summary()))
 }
 
 pub async fn update_user<S: AppState>(
     State(state): State<Arc<S>>,
-    TypedHeader(_auth): TypedHeader<ChamberHeader>,
-    Json(UpdateUserParams { username, access_level, roles }): Json<UpdateUserParams>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(UpdateUserParams {
+        username,
+        access_level,
+        roles,
+        namespace_admin,
+    }): Json<UpdateUserParams>,
 ) -> Result<StatusCode, ApiError> {
-    let mut user = state.db().get_user_from_name(username).await?;
+    let db = state.db().in_namespace(&namespace);
+
+    let mut user = db.get_user_from_name(username).await?;
 
     if let Some(roles) = roles {
         user.set_roles(roles);
@@ -77,7 +163,431 @@ pub async fn update_user<S: AppState>(
         user.set_access_level(access_level);
     }
 
-    state.db().update_user(user).await?;
+    if let Some(namespace_admin) = namespace_admin {
+        user.set_namespace_admin(namespace_admin);
+    }
+
+    db.update_user(user).await?;
+
+    Ok(StatusCode::OK)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/users",
+    tag = "users",
+    responses((status = 200, body = [UserSummary])),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn view_users<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+) -> Result<Json<Vec<UserSummary>>, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let res = db.view_users().await?;
+
+    Ok(Json(res.iter().map(User::summary).collect()))
+}
+
+pub async fn disable_user<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(UserParams { name }): Json<UserParams>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let mut user = db.get_user_from_name(name).await?;
+    user.set_disabled(true);
+
+    db.update_user(user).await?;
+
+    Ok(StatusCode::OK)
+}
+
+pub async fn enable_user<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(UserParams { name }): Json<UserParams>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let mut user = db.get_user_from_name(name).await?;
+    user.set_disabled(false);
+
+    db.update_user(user).await?;
+
+    Ok(StatusCode::OK)
+}
+
+pub async fn set_user_expiry<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(UserExpiryParams { name, expires_at }): Json<UserExpiryParams>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let mut user = db.get_user_from_name(name).await?;
+    user.set_expires_at(expires_at);
+
+    db.update_user(user).await?;
+
+    Ok(StatusCode::OK)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/me",
+    tag = "account",
+    responses((status = 200, body = UserProfile)),
+    security(("bearer" = [])),
+)]
+pub async fn whoami<S: AppState>(
+    State(state): State<Arc<S>>,
+    EnrollmentClaims(claim): EnrollmentClaims,
+) -> Result<Json<UserProfile>, ApiError> {
+    let db = state.db().in_namespace(&claim.namespace);
+    let user = db.get_user_from_name(claim.sub).await?;
+    let two_factor_required = two_factor_required(state.as_ref(), &user).await?;
+    let groups = db.view_groups_for_user(user.username.clone()).await?;
+
+    let effective = user.clone().with_groups(&groups);
+
+    Ok(Json(UserProfile {
+        username: user.username.clone(),
+        namespace: claim.namespace,
+        namespace_admin: user.is_namespace_admin(),
+        access_level: user.access_level(),
+        roles: user.roles().to_vec(),
+        groups: groups.into_iter().map(|x| x.name).collect(),
+        effective_access_level: effective.access_level(),
+        effective_roles: effective.roles().to_vec(),
+        two_factor_enabled: user.two_factor_enabled(),
+        two_factor_required,
+    }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/me/password",
+    tag = "account",
+    request_body = ChangePasswordParams,
+    responses((status = 200, description = "The password was changed")),
+    security(("bearer" = [])),
+)]
+pub async fn change_password<S: AppState>(
+    State(state): State<Arc<S>>,
+    claim: Claims,
+    Json(params): Json<ChangePasswordParams>,
+) -> Result<StatusCode, ApiError> {
+    if params.new_password.is_empty() {
+        return Err(ApiError::BadRequest(
+            "The new password can't be empty".to_string(),
+        ));
+    }
+
+    let db = state.db().in_namespace(&claim.namespace);
+    let mut user = db.get_user_from_name(claim.sub).await?;
+
+    if user.verify(&params.old_password).await.is_err() {
+        return Err(ApiError::Unauthorised);
+    }
+
+    user.set_password(&state.hash_config(), &params.new_password)
+        .await?;
+
+    db.update_user_password(user).await?;
 
     Ok(StatusCode::OK)
 }
+
+#[utoipa::path(
+    post,
+    path = "/v1/me/2fa/enroll",
+    tag = "account",
+    request_body = TwoFactorPasswordParams,
+    responses((status = 200, body = TwoFactorEnrollment)),
+    security(("bearer" = [])),
+)]
+pub async fn enroll_two_factor<S: AppState>(
+    State(state): State<Arc<S>>,
+    EnrollmentClaims(claim): EnrollmentClaims,
+    Json(params): Json<TwoFactorPasswordParams>,
+) -> Result<Json<TwoFactorEnrollment>, ApiError> {
+    let db = state.db().in_namespace(&claim.namespace);
+    let mut user = db.get_user_from_name(claim.sub).await?;
+
+    if user.verify(&params.password).await.is_err() {
+        return Err(ApiError::Unauthorised);
+    }
+
+    if user.two_factor_enabled() {
+        return Err(ApiError::Conflict(
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+
+    let secret = user.start_two_factor_enrollment()?;
+    let otpauth_uri = totp::otpauth_uri("Chamber", &user.username, &secret);
+
+    db.update_user_two_factor(user).await?;
+
+    Ok(Json(TwoFactorEnrollment {
+        secret,
+        otpauth_uri,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/me/2fa/confirm",
+    tag = "account",
+    request_body = TwoFactorCodeParams,
+    responses((status = 200, body = RecoveryCodes)),
+    security(("bearer" = [])),
+)]
+pub async fn confirm_two_factor<S: AppState>(
+    State(state): State<Arc<S>>,
+    EnrollmentClaims(claim): EnrollmentClaims,
+    Json(params): Json<TwoFactorCodeParams>,
+) -> Result<Json<RecoveryCodes>, ApiError> {
+    let db = state.db().in_namespace(&claim.namespace);
+    let mut user = db.get_user_from_name(claim.sub).await?;
+
+    let recovery_codes = user.confirm_two_factor(&params.code)?;
+
+    db.update_user_two_factor(user).await?;
+
+    Ok(Json(RecoveryCodes { recovery_codes }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/me/2fa/disable",
+    tag = "account",
+    request_body = DisableTwoFactorParams,
+    responses((status = 200, description = "Two-factor authentication was disabled")),
+    security(("bearer" = [])),
+)]
+pub async fn disable_two_factor<S: AppState>(
+    State(state): State<Arc<S>>,
+    claim: Claims,
+    Json(params): Json<DisableTwoFactorParams>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&claim.namespace);
+    let mut user = db.get_user_from_name(claim.sub).await?;
+
+    if user.verify(&params.password).await.is_err() {
+        return Err(ApiError::Unauthorised);
+    }
+
+    user.verify_second_factor(&params.code)?;
+
+    if two_factor_required(state.as_ref(), &user).await? {
+        return Err(ApiError::Forbidden);
+    }
+
+    user.disable_two_factor();
+
+    db.update_user_two_factor(user).await?;
+
+    Ok(StatusCode::OK)
+}
+
+pub async fn reset_two_factor<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Json(UserParams { name }): Json<UserParams>,
+) -> Result<StatusCode, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let mut user = db.get_user_from_name(name).await?;
+    user.disable_two_factor();
+
+    db.update_user_two_factor(user).await?;
+
+    Ok(StatusCode::OK)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/settings/two_factor",
+    tag = "admin",
+    responses((status = 200, body = TwoFactorSettings)),
+    security(("root_key" = [])),
+)]
+pub async fn view_two_factor_settings<S: AppState>(
+    State(state): State<Arc<S>>,
+    TypedHeader(auth): TypedHeader<ChamberHeader>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> Result<Json<TwoFactorSettings>, ApiError> {
+    check_root_key(state.as_ref(), auth, &client_ip(&headers, connect_info))?;
+
+    let required_above = state.db().view_two_factor_level().await?;
+
+    Ok(Json(TwoFactorSettings { required_above }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/settings/two_factor",
+    tag = "admin",
+    request_body = TwoFactorSettings,
+    responses((status = 200, description = "The settings were saved")),
+    security(("root_key" = [])),
+)]
+pub async fn update_two_factor_settings<S: AppState>(
+    State(state): State<Arc<S>>,
+    TypedHeader(auth): TypedHeader<ChamberHeader>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(settings): Json<TwoFactorSettings>,
+) -> Result<StatusCode, ApiError> {
+    check_root_key(state.as_ref(), auth, &client_ip(&headers, connect_info))?;
+
+    state
+        .db()
+        .set_two_factor_level(settings.required_above)
+        .await?;
+
+    Ok(StatusCode::OK)
+}
+
+/// Shows a user's access level, roles and status.
+#[utoipa::path(
+    get,
+    path = "/v1/users/{name}",
+    tag = "users",
+    params(("name" = String, Path, description = "The user's name")),
+    responses((status = 200, body = UserSummary)),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn view_user_v1<S: AppState>(
+    State(state): State<Arc<S>>,
+    NamespaceAdmin(namespace): NamespaceAdmin,
+    Path(name): Path<String>,
+) -> Result<Json<UserSummary>, ApiError> {
+    let db = state.db().in_namespace(&namespace);
+
+    let user = db.get_user_from_name(name).await?;
+
+    Ok(Json(user.summary()))
+}
+
+/// Changes a user's access level or roles. Anything left out is kept.
+#[utoipa::path(
+    patch,
+    path = "/v1/users/{name}",
+    tag = "users",
+    params(("name" = String, Path, description = "The user's name")),
+    request_body = UserChanges,
+    responses((status = 200, description = "The user was updated")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn update_user_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(username): Path<String>,
+    Json(UserChanges {
+        access_level,
+        roles,
+        namespace_admin,
+    }): Json<UserChanges>,
+) -> Result<StatusCode, ApiError> {
+    let params = UpdateUserParams {
+        username,
+        access_level,
+        roles,
+        namespace_admin,
+    };
+
+    update_user(state, admin, Json(params)).await
+}
+
+/// Deletes a user.
+#[utoipa::path(
+    delete,
+    path = "/v1/users/{name}",
+    tag = "users",
+    params(("name" = String, Path, description = "The user's name")),
+    responses((status = 200, description = "The user was deleted")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn delete_user_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    delete_user(state, admin, Json(UserParams { name })).await
+}
+
+/// Stops a user from logging in, and invalidates their tokens.
+#[utoipa::path(
+    post,
+    path = "/v1/users/{name}/disable",
+    tag = "users",
+    params(("name" = String, Path, description = "The user's name")),
+    responses((status = 200, description = "The user was disabled")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn disable_user_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    disable_user(state, admin, Json(UserParams { name })).await
+}
+
+/// Lets a disabled user log in again.
+#[utoipa::path(
+    post,
+    path = "/v1/users/{name}/enable",
+    tag = "users",
+    params(("name" = String, Path, description = "The user's name")),
+    responses((status = 200, description = "The user was enabled")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn enable_user_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    enable_user(state, admin, Json(UserParams { name })).await
+}
+
+/// Sets or clears the time after which a user can no longer log in.
+#[utoipa::path(
+    put,
+    path = "/v1/users/{name}/expiry",
+    tag = "users",
+    params(("name" = String, Path, description = "The user's name")),
+    request_body = UserExpiry,
+    responses((status = 200, description = "The expiry was set")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn set_user_expiry_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+    Json(UserExpiry { expires_at }): Json<UserExpiry>,
+) -> Result<StatusCode, ApiError> {
+    set_user_expiry(state, admin, Json(UserExpiryParams { name, expires_at })).await
+}
+
+/// Removes a user's second factor, for when they've lost it.
+#[utoipa::path(
+    post,
+    path = "/v1/users/{name}/reset_2fa",
+    tag = "users",
+    params(("name" = String, Path, description = "The user's name")),
+    responses((status = 200, description = "Two-factor authentication was reset")),
+    security(("root_key" = []), ("bearer" = [])),
+)]
+pub async fn reset_two_factor_v1<S: AppState>(
+    state: State<Arc<S>>,
+    admin: NamespaceAdmin,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    reset_two_factor(state, admin, Json(UserParams { name })).await
+}
@@ -1,39 +1,90 @@
 use axum::{
-    body::{Body, HttpBody},
+    body::{Body, Bytes},
     http::{self, Request, StatusCode},
+    response::Response,
+    Router,
 };
+use chamber_core::hashing::HashConfig;
+use chamber_core::traits::{AppState, ShuttleAppState};
+use chamber_server::router::init_router;
 use serde_json::Value;
-use std::net::SocketAddr;
+use shuttle_persist::PersistInstance;
+use tokio::sync::{Mutex, MutexGuard};
+use tower::ServiceExt;
 
 pub mod postgres;
 
-pub async fn create_user_and_log_in(addr: SocketAddr, key: &str) -> String {
-    let client = hyper::Client::new();
+/// Tests share a database, a keyfile and its persisted copy, and some of them upload a new
+/// keyfile, so they run one at a time.
+static SERIAL: Mutex<()> = Mutex::const_new(());
 
-    let response = client
+/// A router with its own state, which requests are sent to without a server.
+pub struct TestApp {
+    pub state: ShuttleAppState,
+    router: Router,
+    _serial: MutexGuard<'static, ()>,
+}
+
+impl TestApp {
+    pub async fn new() -> Self {
+        let serial = SERIAL.lock().await;
+
+        let pool = postgres::get_test_db_connection().await;
+        let persist = PersistInstance::new("data/persist".into()).unwrap();
+        let state = ShuttleAppState::new(pool, persist, HashConfig::default());
+        state.check_keyfile_exists().unwrap();
+
+        Self {
+            router: init_router(state.clone()),
+            state,
+            _serial: serial,
+        }
+    }
+
+    pub fn root_key(&self) -> String {
+        self.state.get_keyfile().unwrap().unseal_key().to_string()
+    }
+
+    pub async fn request(&self, request: Request<Body>) -> Response {
+        self.router.clone().oneshot(request).await.unwrap()
+    }
+}
+
+pub async fn body_bytes(response: Response) -> Bytes {
+    axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap()
+}
+
+pub async fn body_string(response: Response) -> String {
+    String::from_utf8(body_bytes(response).await.to_vec()).unwrap()
+}
+
+pub async fn create_user_and_log_in(app: &TestApp) -> String {
+    let key = app.root_key();
+
+    let response = app
         .request(
             Request::builder()
                 .method(http::Method::POST)
-                .uri(format!("http://{}/unseal", addr))
-                .header("x-chamber-key", key)
+                .uri("/unseal")
+                .header("x-chamber-key", &key)
                 .header("Content-Type", "application/json")
                 .body(Body::empty())
                 .unwrap(),
         )
-        .await
-        .unwrap();
+        .await;
 
-    //        let body = response.into_body().collect().await.unwrap().to_bytes();
-    //        let string = std::str::from_utf8(&body).unwrap();
-    //        assert_eq!(string, "The vault is locked!");
     assert_eq!(response.status(), StatusCode::OK);
 
-    let response = client
+    // Tests share a database, so only the first one to run gets to create the admin
+    let response = app
         .request(
             Request::builder()
                 .method(http::Method::POST)
                 .header("Content-Type", "application/json")
-                .uri(format!("http://{}/login", addr))
+                .header("x-chamber-key", &key)
+                .uri("/init")
                 .body(Body::from(
                     serde_json::to_vec(&serde_json::json!(
                     {
@@ -45,13 +96,69 @@ pub async fn create_user_and_log_in(addr: SocketAddr, key: &str) -> String {
                 ))
                 .unwrap(),
         )
-        .await
-        .unwrap();
+        .await;
+
+    assert!([StatusCode::CREATED, StatusCode::CONFLICT].contains(&response.status()));
+
+    log_in(app, "root", "this").await
+}
+
+pub async fn create_user_and_log_in_as(app: &TestApp, username: &str, password: &str) -> String {
+    let response = app
+        .request(
+            Request::builder()
+                .method(http::Method::POST)
+                .header("Content-Type", "application/json")
+                .header("x-chamber-key", app.root_key())
+                .uri("/users/create")
+                .body(Body::from(
+                    serde_json::to_vec(&serde_json::json!(
+                    {
+                        "username": username,
+                        "password": password
+                    }
+                    ))
+                    .unwrap(),
+                ))
+                .unwrap(),
+        )
+        .await;
+
+    assert_eq!(response.status(), StatusCode::CREATED);
 
-   assert_eq!(response.status(), StatusCode::OK);
+    log_in(app, username, password).await
+}
 
-    let body = response.into_body().collect().await.unwrap().to_bytes();
-    let body: Value = serde_json::from_slice(&body).unwrap();
+/// Logs in and returns the `Authorization` header for the token.
+pub async fn log_in(app: &TestApp, username: &str, password: &str) -> String {
+    log_in_to(app, "default", username, password).await
+}
+
+/// Logs in to a namespace and returns the `Authorization` header for the token.
+pub async fn log_in_to(app: &TestApp, namespace: &str, username: &str, password: &str) -> String {
+    let response = app
+        .request(
+            Request::builder()
+                .method(http::Method::POST)
+                .header("Content-Type", "application/json")
+                .uri("/login")
+                .body(Body::from(
+                    serde_json::to_vec(&serde_json::json!(
+                    {
+                        "namespace": namespace,
+                        "username": username,
+                        "password": password
+                    }
+                    ))
+                    .unwrap(),
+                ))
+                .unwrap(),
+        )
+        .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
 
     assert!(body.get("access_token").is_some());
 
@@ -61,7 +168,5 @@ pub async fn create_user_and_log_in(addr: SocketAddr, key: &str) -> String {
         body.get("access_token").unwrap()
     );
 
-    let new_str = &res.replace('\"', "");
-
-    new_str.to_owned()
+    res.replace('\"', "")
 }
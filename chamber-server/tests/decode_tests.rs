@@ -0,0 +1,138 @@
+#[cfg(test)]
+mod tests {
+    use axum_extra::headers::{Header, HeaderValue};
+    use chamber_crypto::errors::DatabaseError;
+    use chamber_crypto::secrets::{KeyFile, NonceCounter, Secret, SerializeKey, U64Wrapper};
+    use chamber_crypto::signing::SigWrapper;
+    use chamber_server::header::ChamberHeader;
+    use ring::aead::{BoundKey, OpeningKey};
+    use sqlx::types::BigDecimal;
+
+    #[test]
+    fn malformed_root_key_headers_are_rejected() {
+        let value = HeaderValue::from_bytes(b"caf\xc3\xa9").unwrap();
+        assert!(ChamberHeader::decode(&mut std::iter::once(&value)).is_err());
+
+        let value = HeaderValue::from_static("root-key");
+        let header = ChamberHeader::decode(&mut std::iter::once(&value)).unwrap();
+        assert_eq!(header.key(), "root-key");
+    }
+
+    #[test]
+    fn corrupted_rows_are_errors() {
+        let nonce: BigDecimal = "-1".parse().unwrap();
+        assert!(matches!(
+            U64Wrapper::try_from(nonce),
+            Err(DatabaseError::InvalidNonce)
+        ));
+
+        let nonce: BigDecimal = "42".parse().unwrap();
+        assert_eq!(U64Wrapper::try_from(nonce).unwrap().inner(), 42);
+
+        assert!(matches!(
+            SigWrapper::try_from(vec![0u8; 12]),
+            Err(DatabaseError::InvalidSignature)
+        ));
+
+        let secret = Secret {
+            key: "hello_world".to_string(),
+            nonce: U64Wrapper(1),
+            ciphertext: b"not a ciphertext".to_vec(),
+            sig: vec![0u8; 64],
+        };
+        let keyfile = KeyFile::new();
+        assert!(matches!(
+            secret.decrypt(keyfile.get_crypto_open_key(1).unwrap()),
+            Err(DatabaseError::DecryptionError)
+        ));
+
+        let secret = Secret {
+            sig: vec![0u8; 3],
+            ..secret
+        };
+        assert!(matches!(
+            secret.decrypt(keyfile.get_crypto_open_key(1).unwrap()),
+            Err(DatabaseError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn keys_from_another_keyfile_fail_to_decrypt() {
+        let mut keyfile = KeyFile::new();
+        let ciphertext = {
+            let mut sealing_key = keyfile.get_crypto_seal_key().unwrap();
+            let mut data = b"meme".to_vec();
+            sealing_key
+                .seal_in_place_append_tag(ring::aead::Aad::empty(), &mut data)
+                .unwrap();
+            data
+        };
+
+        let other = KeyFile::new();
+        let opening_key = OpeningKey::new(
+            other.crypto_key().make_key().unwrap(),
+            NonceCounter::from_num(1),
+        );
+
+        let secret = Secret {
+            key: "hello_world".to_string(),
+            nonce: U64Wrapper(1),
+            ciphertext,
+            sig: vec![0u8; 64],
+        };
+        assert!(matches!(
+            secret.decrypt(opening_key),
+            Err(DatabaseError::DecryptionError)
+        ));
+    }
+
+    #[test]
+    fn data_keys_only_unwrap_for_their_namespace_and_keyfile() {
+        let keyfile = KeyFile::new();
+        let data_key = SerializeKey::new();
+        let wrapped = keyfile.wrap_data_key("payments", &data_key).unwrap();
+
+        assert_eq!(
+            keyfile.unwrap_data_key("payments", &wrapped).unwrap().0,
+            data_key.0
+        );
+        assert!(matches!(
+            keyfile.unwrap_data_key("billing", &wrapped),
+            Err(DatabaseError::DecryptionError)
+        ));
+        assert!(matches!(
+            KeyFile::new().unwrap_data_key("payments", &wrapped),
+            Err(DatabaseError::DecryptionError)
+        ));
+        assert!(matches!(
+            keyfile.unwrap_data_key("payments", &wrapped[..4]),
+            Err(DatabaseError::DecryptionError)
+        ));
+
+        // Secrets sealed with a data key can't be opened with the crypto key, or another data key
+        let ciphertext = {
+            let mut data = b"meme".to_vec();
+            data_key
+                .seal_key(7)
+                .unwrap()
+                .seal_in_place_append_tag(ring::aead::Aad::empty(), &mut data)
+                .unwrap();
+            data
+        };
+        let secret = Secret {
+            key: "hello_world".to_string(),
+            nonce: U64Wrapper(7),
+            ciphertext,
+            sig: vec![0u8; 64],
+        };
+        for opening_key in [
+            keyfile.get_crypto_open_key(7).unwrap(),
+            SerializeKey::new().open_key(7).unwrap(),
+        ] {
+            assert!(matches!(
+                secret.decrypt(opening_key),
+                Err(DatabaseError::DecryptionError)
+            ));
+        }
+    }
+}
@@ -0,0 +1,423 @@
+#[cfg(test)]
+mod tests {
+    use chamber_shared::attempts::{FailedAttempt, FailedAttemptsParams};
+    use chamber_shared::audit::{AuditEntry, AuditLogQuery};
+    use chamber_shared::environments::{check_environment_name, PromoteSecretParams};
+    use chamber_shared::errors::ErrorBody;
+    use chamber_shared::groups::{
+        Group, GroupChanges, GroupMembers, GroupMembersParams, GroupParams, UpdateGroupParams,
+    };
+    use chamber_shared::namespaces::{check_namespace_name, Namespace};
+    use chamber_shared::policy::{
+        AttachPolicyParams, Policy, PolicyAttachments, PolicyParams, PolicyRule, SecretAccess,
+        SecretAccessParams, SimulateParams, UserAccess, UserAccessParams,
+    };
+    use chamber_shared::roles::{Role, RoleParams, RoleUsage};
+    use chamber_shared::secrets::{
+        check_key_path, CreateSecretParams, FolderListing, FolderQuery, ListSecretsArgs,
+        ListSecretsQuery, MoveSecretsParams, MovedSecret, SecretFilter, SecretInfo, SecretKey,
+        SecretPublic, SecretSort, SecretTags, SortOrder, TagMatch, UpdateSecret, MAX_PAGE_SIZE,
+    };
+    use chamber_shared::ssh::{
+        AddSshKeyParams, AddUnsealKeyParams, RemoveSshKeyParams, RemoveUnsealKeyParams,
+        SshChallenge, SshChallengeParams, SshKey, SshLoginParams, UnsealKey, UnsealParams,
+    };
+    use chamber_shared::users::{
+        CertLoginParams, ChangePasswordParams, CreateUserParams, DisableTwoFactorParams,
+        InitParams, InitResponse, RecoveryCodes, TwoFactorCodeParams, TwoFactorEnrollment,
+        TwoFactorPasswordParams, TwoFactorSettings, UpdateUserParams, UserChanges, UserExpiry,
+        UserExpiryParams, UserLoginParams, UserParams, UserProfile, UserSummary,
+    };
+    use chamber_shared::AuthBody;
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use serde_json::{json, Value};
+
+    /// Reads a body off the wire and writes it back, which should give the same JSON. This
+    /// pins the field names that the server, the CLI and the SDK all rely on.
+    fn round_trip<T: Serialize + DeserializeOwned>(body: Value) {
+        let parsed: T = serde_json::from_value(body.clone()).unwrap();
+
+        assert_eq!(serde_json::to_value(parsed).unwrap(), body);
+    }
+
+    #[test]
+    fn secret_types_round_trip() {
+        round_trip::<SecretInfo>(json!({
+            "key": "payments/stripe",
+            "tags": ["payments"],
+            "access_level": 100,
+            "role_whitelist": ["billing"]
+        }));
+        round_trip::<SecretPublic>(json!({"key": "payments/stripe", "value": "sk_test"}));
+        round_trip::<CreateSecretParams>(json!({
+            "key": "payments/stripe",
+            "value": "sk_test",
+            "tags": ["payments"],
+            "access_level": 100,
+            "role_whitelist": ["billing"]
+        }));
+        round_trip::<SecretKey>(json!({"key": "payments/stripe"}));
+        round_trip::<ListSecretsArgs>(json!({"tag_filter": "payments"}));
+        round_trip::<ListSecretsQuery>(json!({"tag": "payments"}));
+        round_trip::<ListSecretsQuery>(json!({
+            "tags": "payments,prod",
+            "tag_match": "any",
+            "key": "payments/*",
+            "sort": "created",
+            "order": "desc",
+            "cursor": "7b7d",
+            "limit": 50
+        }));
+        round_trip::<UpdateSecret>(json!({"key": "payments/stripe", "update_data": ["billing"]}));
+        round_trip::<SecretTags>(json!({"tags": ["billing"]}));
+        round_trip::<FolderQuery>(json!({"prefix": "payments/prod"}));
+        round_trip::<FolderListing>(json!({
+            "prefix": "payments",
+            "folders": ["payments/prod"],
+            "secrets": [{
+                "key": "payments/stripe",
+                "tags": [],
+                "access_level": 0,
+                "role_whitelist": []
+            }]
+        }));
+        round_trip::<MoveSecretsParams>(json!({"from": "payments/dev", "to": "payments/test"}));
+        round_trip::<MovedSecret>(json!({
+            "from": "payments/dev/stripe",
+            "to": "payments/test/stripe"
+        }));
+    }
+
+    #[test]
+    fn keys_are_slash_separated_paths() {
+        assert!(check_key_path("payments/prod/db/password").is_ok());
+        assert!(check_key_path("stripe").is_ok());
+
+        for key in ["", "/payments", "payments/", "payments//prod", "/"] {
+            assert!(check_key_path(key).is_err(), "{key}");
+        }
+    }
+
+    #[test]
+    fn user_types_round_trip() {
+        round_trip::<UserProfile>(json!({
+            "username": "alice",
+            "namespace": "default",
+            "namespace_admin": false,
+            "access_level": 100,
+            "roles": ["billing"],
+            "groups": ["finance"],
+            "effective_access_level": 200,
+            "effective_roles": ["billing", "reporting"],
+            "two_factor_enabled": true,
+            "two_factor_required": true
+        }));
+        round_trip::<UserSummary>(json!({
+            "username": "alice",
+            "access_level": 100,
+            "roles": ["billing"],
+            "namespace_admin": true,
+            "disabled": false,
+            "expires_at": "2030-01-01T00:00:00Z",
+            "created_at": "2024-01-01T00:00:00Z",
+            "two_factor_enabled": false
+        }));
+        round_trip::<ChangePasswordParams>(json!({"old_password": "a", "new_password": "b"}));
+        round_trip::<InitParams>(json!({"username": "root", "password": null}));
+        round_trip::<InitResponse>(json!({"username": "root", "password": "generated"}));
+        round_trip::<UserParams>(json!({"name": "alice"}));
+        round_trip::<CreateUserParams>(json!({
+            "username": "alice",
+            "password": "hunter2",
+            "access_level": 100,
+            "roles": ["billing"],
+            "namespace_admin": null
+        }));
+        round_trip::<UpdateUserParams>(json!({
+            "username": "alice",
+            "access_level": null,
+            "roles": ["billing"],
+            "namespace_admin": true
+        }));
+        round_trip::<UserChanges>(json!({
+            "access_level": 100,
+            "roles": null,
+            "namespace_admin": false
+        }));
+        round_trip::<UserExpiry>(json!({"expires_at": "2030-01-01T00:00:00Z"}));
+        round_trip::<UserExpiryParams>(json!({"name": "alice", "expires_at": null}));
+        round_trip::<UserLoginParams>(json!({
+            "namespace": "payments",
+            "username": "alice",
+            "password": "hunter2",
+            "code": "123456"
+        }));
+        round_trip::<CertLoginParams>(json!({"namespace": null, "code": null}));
+        round_trip::<AuthBody>(json!({"access_token": "token", "token_type": "Bearer"}));
+    }
+
+    #[test]
+    fn namespace_types_round_trip() {
+        round_trip::<Namespace>(json!({"name": "payments", "description": "The payments team"}));
+    }
+
+    #[test]
+    fn namespace_names_are_lowercase_slugs() {
+        assert!(check_namespace_name("payments").is_ok());
+        assert!(check_namespace_name("team-42").is_ok());
+
+        for name in ["", "-payments", "Payments", "pay ments", "pay/ments"] {
+            assert!(check_namespace_name(name).is_err(), "{name}");
+        }
+        assert!(check_namespace_name(&"a".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn environment_types_round_trip() {
+        round_trip::<PromoteSecretParams>(json!({
+            "key": "payments/stripe",
+            "from": "staging",
+            "to": "prod"
+        }));
+        round_trip::<AuditEntry>(json!({
+            "action": "promote",
+            "username": "alice",
+            "key": "payments/stripe",
+            "from_environment": "staging",
+            "environment": "prod",
+            "created_at": "2024-01-01T00:00:00Z"
+        }));
+        round_trip::<AuditLogQuery>(json!({"limit": 50}));
+    }
+
+    #[test]
+    fn environment_names_are_lowercase_slugs() {
+        assert!(check_environment_name("prod").is_ok());
+        assert!(check_environment_name("eu-staging-2").is_ok());
+
+        for name in ["", "-prod", "Prod", "pr od", "prod/eu"] {
+            assert!(check_environment_name(name).is_err(), "{name}");
+        }
+        assert!(check_environment_name(&"a".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn two_factor_types_round_trip() {
+        round_trip::<TwoFactorEnrollment>(json!({
+            "secret": "JBSWY3DPEHPK3PXP",
+            "otpauth_uri": "otpauth://totp/Chamber:alice?secret=JBSWY3DPEHPK3PXP"
+        }));
+        round_trip::<RecoveryCodes>(json!({"recovery_codes": ["abcd-efgh"]}));
+        round_trip::<TwoFactorSettings>(json!({"required_above": 100}));
+        round_trip::<TwoFactorPasswordParams>(json!({"password": "hunter2"}));
+        round_trip::<TwoFactorCodeParams>(json!({"code": "123456"}));
+        round_trip::<DisableTwoFactorParams>(json!({"password": "hunter2", "code": "123456"}));
+    }
+
+    #[test]
+    fn role_and_group_types_round_trip() {
+        round_trip::<Role>(json!({"name": "billing", "description": "Pays the bills"}));
+        round_trip::<RoleUsage>(json!({
+            "name": "billing",
+            "description": "Pays the bills",
+            "users": ["alice"],
+            "groups": ["finance"],
+            "secrets": ["payments/stripe"]
+        }));
+        round_trip::<RoleParams>(json!({"name": "billing"}));
+        round_trip::<Group>(json!({
+            "name": "finance",
+            "description": "",
+            "members": ["alice"],
+            "subgroups": ["auditors"],
+            "roles": ["billing"],
+            "access_level": 100
+        }));
+        round_trip::<GroupParams>(json!({"name": "finance"}));
+        round_trip::<UpdateGroupParams>(json!({
+            "name": "finance",
+            "description": null,
+            "roles": ["billing"],
+            "access_level": 100
+        }));
+        round_trip::<GroupChanges>(json!({
+            "description": "Pays the bills",
+            "roles": null,
+            "access_level": null
+        }));
+        round_trip::<GroupMembersParams>(json!({
+            "name": "finance",
+            "users": ["alice"],
+            "groups": null
+        }));
+        round_trip::<GroupMembers>(json!({"users": null, "groups": ["auditors"]}));
+    }
+
+    #[test]
+    fn policy_types_round_trip() {
+        let rule = json!({
+            "path": "payments/*",
+            "tags": ["payments"],
+            "max_access_level": 100,
+            "check_whitelist": true,
+            "environments": ["staging", "prod"],
+            "capabilities": ["read", "list"]
+        });
+        let decision = json!({"allowed": true, "policy": "billing", "rule": rule});
+
+        round_trip::<Policy>(json!({
+            "name": "billing",
+            "description": "",
+            "rules": [rule],
+            "users": ["alice"],
+            "roles": []
+        }));
+        round_trip::<PolicyParams>(json!({"name": "billing"}));
+        round_trip::<AttachPolicyParams>(json!({
+            "name": "billing",
+            "users": ["alice"],
+            "roles": null
+        }));
+        round_trip::<PolicyAttachments>(json!({"users": null, "roles": ["billing"]}));
+        round_trip::<SecretAccessParams>(json!({"key": "payments/stripe"}));
+        round_trip::<UserAccessParams>(json!({"username": "alice"}));
+        round_trip::<SimulateParams>(json!({
+            "username": "alice",
+            "key": "payments/stripe",
+            "capability": "delete"
+        }));
+        round_trip::<SecretAccess>(json!({
+            "key": "payments/stripe",
+            "users": [{
+                "username": "alice",
+                "groups": ["finance"],
+                "roles": ["billing"],
+                "decision": decision
+            }],
+            "roles": ["billing"]
+        }));
+        round_trip::<UserAccess>(json!({
+            "username": "alice",
+            "groups": ["finance"],
+            "secrets": [{
+                "key": "payments/stripe",
+                "capabilities": ["read"],
+                "decision": decision
+            }]
+        }));
+    }
+
+    #[test]
+    fn ssh_types_round_trip() {
+        let key = json!({
+            "fingerprint": "SHA256:abc",
+            "public_key": "ssh-ed25519 AAAA alice@laptop",
+            "comment": "alice@laptop",
+            "created_at": "2024-01-01T00:00:00Z"
+        });
+
+        round_trip::<SshKey>(key.clone());
+        round_trip::<AddSshKeyParams>(json!({"public_key": "ssh-ed25519 AAAA"}));
+        round_trip::<RemoveSshKeyParams>(json!({"fingerprint": "SHA256:abc"}));
+        round_trip::<SshChallengeParams>(json!({"namespace": "payments", "username": "alice"}));
+        round_trip::<SshChallenge>(json!({
+            "nonce": "nonce",
+            "namespace": "chamber-login",
+            "expires_in_secs": 60
+        }));
+        round_trip::<SshLoginParams>(json!({
+            "namespace": "payments",
+            "username": "alice",
+            "nonce": "nonce",
+            "signature": "-----BEGIN SSH SIGNATURE-----",
+            "code": null
+        }));
+        round_trip::<UnsealKey>(json!({"name": "alice", "key": key}));
+        round_trip::<AddUnsealKeyParams>(json!({
+            "name": "alice",
+            "public_key": "ssh-ed25519 AAAA"
+        }));
+        round_trip::<AddUnsealKeyParams>(json!({
+            "name": "bob",
+            "public_key": "ssh-ed25519 AAAA",
+            "approval": {"nonce": "nonce", "signature": "-----BEGIN SSH SIGNATURE-----"}
+        }));
+        round_trip::<RemoveUnsealKeyParams>(json!({
+            "fingerprint": "SHA256:abc",
+            "approval": {"nonce": "nonce", "signature": "-----BEGIN SSH SIGNATURE-----"}
+        }));
+        round_trip::<UnsealParams>(json!({
+            "nonce": "nonce",
+            "signature": "-----BEGIN SSH SIGNATURE-----"
+        }));
+    }
+
+    #[test]
+    fn attempt_and_error_types_round_trip() {
+        round_trip::<FailedAttempt>(json!({
+            "kind": "login",
+            "username": "alice",
+            "ip": null,
+            "reason": "wrong password",
+            "created_at": "2024-01-01T00:00:00Z"
+        }));
+        round_trip::<FailedAttemptsParams>(json!({"limit": 50}));
+        round_trip::<ErrorBody>(json!({
+            "code": "not_found",
+            "message": "Secret not found",
+            "request_id": "abc"
+        }));
+    }
+
+    #[test]
+    fn listing_queries_become_filters() {
+        let query: ListSecretsQuery = serde_json::from_value(json!({
+            "tag": "payments",
+            "tags": "prod, eu,,",
+            "key": "",
+            "limit": 5000
+        }))
+        .unwrap();
+        let filter = SecretFilter::from(query);
+
+        assert_eq!(filter.tags, vec!["payments", "prod", "eu"]);
+        assert_eq!(filter.tag_match, TagMatch::All);
+        assert_eq!(filter.key, None);
+        assert_eq!(filter.sort, SecretSort::Key);
+        assert_eq!(filter.order, SortOrder::Asc);
+        assert_eq!(filter.limit, Some(MAX_PAGE_SIZE));
+
+        let filter = SecretFilter::from(ListSecretsQuery::default());
+
+        assert!(filter.tags.is_empty());
+        assert_eq!(filter.limit, None);
+    }
+
+    #[test]
+    fn optional_request_fields_can_be_left_out() {
+        let secret: CreateSecretParams =
+            serde_json::from_value(json!({"key": "payments/stripe", "value": "sk_test"})).unwrap();
+        assert!(secret.tags.is_none());
+        assert!(secret.role_whitelist.is_none());
+
+        let login: UserLoginParams =
+            serde_json::from_value(json!({"username": "alice", "password": "hunter2"})).unwrap();
+        assert!(login.code.is_none());
+        assert!(login.namespace.is_none());
+
+        let namespace: Namespace = serde_json::from_value(json!({"name": "payments"})).unwrap();
+        assert!(namespace.description.is_empty());
+
+        let simulate: SimulateParams =
+            serde_json::from_value(json!({"username": "alice", "key": "payments/stripe"})).unwrap();
+        assert!(simulate.capability.is_none());
+
+        let init: InitParams = serde_json::from_value(json!({})).unwrap();
+        assert!(init.username.is_none());
+
+        let rule: PolicyRule =
+            serde_json::from_value(json!({"path": "*", "capabilities": ["read"]})).unwrap();
+        assert!(rule.environments.is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        middleware,
+        response::{IntoResponse, Response},
+        routing::{get, post},
+        Json, Router,
+    };
+    use chamber_core::errors::DatabaseError;
+    use chamber_server::auth::AuthError;
+    use chamber_server::errors::{self, ApiError};
+    use chamber_shared::errors::{ErrorBody, ErrorCode, REQUEST_ID_HEADER};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    async fn missing() -> Result<String, ApiError> {
+        Err(ApiError::NotFound("Key wasn't found".to_string()))
+    }
+
+    async fn echo(Json(body): Json<serde_json::Value>) -> Json<serde_json::Value> {
+        Json(body)
+    }
+
+    fn router() -> Router {
+        Router::new()
+            .route("/missing", get(missing))
+            .route("/echo", post(echo))
+            .route("/ok", get(|| async { "ok" }))
+            .layer(middleware::from_fn(errors::request_id))
+    }
+
+    async fn error_body(res: Response) -> ErrorBody {
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn errors_share_an_envelope_with_the_request_id() {
+        let res = router()
+            .oneshot(Request::get("/missing").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let id = res.headers()[REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let body = error_body(res).await;
+        assert_eq!(body.code, ErrorCode::NotFound);
+        assert_eq!(body.message, "Key wasn't found");
+        assert_eq!(body.request_id, Some(id));
+
+        // Including axum's own rejections
+        let res = router()
+            .oneshot(
+                Request::post("/echo")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from("{not json"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(error_body(res).await.code, ErrorCode::BadRequest);
+
+        let res = router()
+            .oneshot(Request::get("/nowhere").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(error_body(res).await.code, ErrorCode::NotFound);
+    }
+
+    #[tokio::test]
+    async fn request_ids_from_clients_are_kept_if_well_formed() {
+        let res = router()
+            .oneshot(
+                Request::get("/ok")
+                    .header(REQUEST_ID_HEADER, "deploy-1234")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[REQUEST_ID_HEADER], "deploy-1234");
+
+        let res = router()
+            .oneshot(
+                Request::get("/ok")
+                    .header(REQUEST_ID_HEADER, "not a valid id")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_ne!(res.headers()[REQUEST_ID_HEADER], "not a valid id");
+    }
+
+    #[tokio::test]
+    async fn running_out_of_challenges_is_rate_limited() {
+        let res = ApiError::from(DatabaseError::TooManyChallenges(Duration::from_secs(30)))
+            .into_response();
+
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()["retry-after"], "30");
+        assert_eq!(error_body(res).await.code, ErrorCode::TooManyRequests);
+    }
+
+    #[tokio::test]
+    async fn wrong_credentials_dont_say_what_was_wrong() {
+        let missing_user =
+            AuthError::WrongCredentials(DatabaseError::SQLError(sqlx::Error::RowNotFound));
+        let wrong_password = AuthError::WrongCredentials(DatabaseError::Forbidden);
+
+        for err in [missing_user, wrong_password] {
+            let res = err.into_response();
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+            let body = error_body(res).await;
+            assert_eq!(body.code, ErrorCode::Unauthorised);
+            assert_eq!(body.message, "Wrong credentials");
+        }
+    }
+
+    #[test]
+    fn error_codes_are_stable() {
+        let body = ErrorBody::new(
+            ErrorCode::TwoFactorRequired,
+            "A two-factor code is required",
+        );
+        let json = serde_json::to_value(&body).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "code": "two_factor_required",
+                "message": "A two-factor code is required"
+            })
+        );
+        assert_eq!(ErrorCode::TwoFactorRequired.status(), 401);
+
+        // Codes from newer servers don't break older clients
+        let body: ErrorBody =
+            serde_json::from_str(r#"{"code": "something_new", "message": "?"}"#).unwrap();
+        assert_eq!(body.code, ErrorCode::Unknown);
+    }
+}
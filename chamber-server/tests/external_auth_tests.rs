@@ -0,0 +1,174 @@
+#[cfg(test)]
+mod tests {
+    use chamber_server::external::{
+        is_external, ClaimRule, ExternalAuth, ExternalAuthConfig, ExternalAuthError,
+    };
+    use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+    use serde_json::{json, Value};
+    use std::path::PathBuf;
+
+    static ISSUER: &str = "https://ci.example.com";
+
+    // rsa.key and ec.key are in jwks.json as rsa-1 and ec-1, other.key isn't
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/jwks")
+            .join(name)
+    }
+
+    async fn auth() -> ExternalAuth {
+        let config: ExternalAuthConfig = serde_json::from_value(json!({
+            "providers": [{
+                "name": "ci",
+                "issuer": ISSUER,
+                "audience": "chamber",
+                "jwks_file": fixture("jwks.json"),
+                "rules": [
+                    {
+                        "claims": {"repository": "org/*", "ref": "refs/heads/main"},
+                        "roles": ["deploy"],
+                        "access_level": 10
+                    },
+                    {
+                        "claims": {"repository": "org/app"},
+                        "roles": ["app"],
+                        "access_level": 5
+                    }
+                ]
+            }]
+        }))
+        .unwrap();
+
+        ExternalAuth::new(config).await.unwrap()
+    }
+
+    fn token(alg: Algorithm, kid: &str, key_file: &str, claims: Value) -> String {
+        let pem = std::fs::read(fixture(key_file)).unwrap();
+        let key = match alg {
+            Algorithm::ES256 => EncodingKey::from_ec_pem(&pem).unwrap(),
+            _ => EncodingKey::from_rsa_pem(&pem).unwrap(),
+        };
+
+        let mut header = Header::new(alg);
+        header.kid = Some(kid.to_string());
+
+        encode(&header, &claims, &key).unwrap()
+    }
+
+    fn claims(repository: &str, git_ref: &str) -> Value {
+        json!({
+            "iss": ISSUER,
+            "aud": "chamber",
+            "sub": format!("repo:{repository}:ref:{git_ref}"),
+            "exp": chrono::Utc::now().timestamp() + 300,
+            "repository": repository,
+            "ref": git_ref,
+        })
+    }
+
+    #[tokio::test]
+    async fn tokens_get_the_grants_of_every_matching_rule() {
+        let auth = auth().await;
+
+        let rs256 = token(
+            Algorithm::RS256,
+            "rsa-1",
+            "rsa.key",
+            claims("org/app", "refs/heads/main"),
+        );
+        let user = auth.authenticate(&rs256).await.unwrap();
+
+        assert_eq!(user.username, "ci:repo:org/app:ref:refs/heads/main");
+        assert_eq!(user.roles(), ["deploy", "app"]);
+        assert_eq!(user.access_level(), 10);
+
+        let es256 = token(
+            Algorithm::ES256,
+            "ec-1",
+            "ec.key",
+            claims("org/app", "refs/heads/feature"),
+        );
+        let user = auth.authenticate(&es256).await.unwrap();
+
+        assert_eq!(user.roles(), ["app"]);
+        assert_eq!(user.access_level(), 5);
+
+        let unmatched = token(
+            Algorithm::RS256,
+            "rsa-1",
+            "rsa.key",
+            claims("someone/else", "refs/heads/main"),
+        );
+        assert!(matches!(
+            auth.authenticate(&unmatched).await,
+            Err(ExternalAuthError::NoMatchingRule)
+        ));
+    }
+
+    #[tokio::test]
+    async fn invalid_tokens_are_rejected() {
+        let auth = auth().await;
+        let valid = claims("org/app", "refs/heads/main");
+
+        let forged = token(Algorithm::RS256, "rsa-1", "other.key", valid.clone());
+        assert!(matches!(
+            auth.authenticate(&forged).await,
+            Err(ExternalAuthError::InvalidToken(_))
+        ));
+
+        let unknown_key = token(Algorithm::RS256, "rsa-2", "rsa.key", valid.clone());
+        assert!(matches!(
+            auth.authenticate(&unknown_key).await,
+            Err(ExternalAuthError::UnknownKey)
+        ));
+
+        let mut wrong_audience = valid.clone();
+        wrong_audience["aud"] = json!("someone-else");
+        let wrong_audience = token(Algorithm::RS256, "rsa-1", "rsa.key", wrong_audience);
+        assert!(auth.authenticate(&wrong_audience).await.is_err());
+
+        let mut expired = valid.clone();
+        expired["exp"] = json!(chrono::Utc::now().timestamp() - 3600);
+        let expired = token(Algorithm::RS256, "rsa-1", "rsa.key", expired);
+        assert!(auth.authenticate(&expired).await.is_err());
+
+        let mut other_issuer = valid.clone();
+        other_issuer["iss"] = json!("https://elsewhere.example.com");
+        let other_issuer = token(Algorithm::RS256, "rsa-1", "rsa.key", other_issuer);
+        assert!(matches!(
+            auth.authenticate(&other_issuer).await,
+            Err(ExternalAuthError::UnknownIssuer(_))
+        ));
+
+        // Chamber's own tokens are HS256, and never get checked against a provider
+        let hs256 = encode(
+            &Header::new(Algorithm::HS256),
+            &valid,
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+        assert!(matches!(
+            auth.authenticate(&hs256).await,
+            Err(ExternalAuthError::UnsupportedAlgorithm(Algorithm::HS256))
+        ));
+        assert!(!is_external(&hs256));
+        assert!(is_external(&forged));
+    }
+
+    #[test]
+    fn array_claims_match_if_any_item_does() {
+        let rule: ClaimRule = serde_json::from_value(json!({
+            "claims": {"groups": "platform-*", "email_verified": "true"}
+        }))
+        .unwrap();
+
+        let claims = json!({"groups": ["dev", "platform-admins"], "email_verified": true});
+        assert!(rule.matches(claims.as_object().unwrap()));
+
+        let claims = json!({"groups": ["dev"], "email_verified": true});
+        assert!(!rule.matches(claims.as_object().unwrap()));
+
+        let claims = json!({"groups": ["platform-admins"]});
+        assert!(!rule.matches(claims.as_object().unwrap()));
+    }
+}
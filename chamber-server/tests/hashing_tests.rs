@@ -0,0 +1,40 @@
+#[cfg(test)]
+mod tests {
+    use chamber_core::hashing::{verify, HashConfig};
+
+    // Small parameters so that the tests stay quick
+    fn config(iterations: u32) -> HashConfig {
+        HashConfig {
+            memory_kib: 1024,
+            iterations,
+            parallelism: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn hashes_verify_against_the_right_password() {
+        let hash = config(1).hash("hunter2").await.unwrap();
+
+        assert!(verify(&hash, "hunter2").await.is_ok());
+        assert!(verify(&hash, "hunter3").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn outdated_hashes_still_verify_but_need_rehashing() {
+        let old = config(1).hash("hunter2").await.unwrap();
+        let new = config(2);
+
+        assert!(!config(1).needs_rehash(&old));
+        assert!(new.needs_rehash(&old));
+        assert!(verify(&old, "hunter2").await.is_ok());
+
+        let rehashed = new.hash("hunter2").await.unwrap();
+        assert!(!new.needs_rehash(&rehashed));
+    }
+
+    #[test]
+    fn unparseable_hashes_need_rehashing() {
+        assert!(HashConfig::default().needs_rehash(""));
+        assert!(HashConfig::default().needs_rehash("plaintext"));
+    }
+}
@@ -0,0 +1,70 @@
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Method, Request, StatusCode};
+    use chamber_core::hashing::HashConfig;
+    use chamber_core::traits::{AppState, ShuttleAppState};
+    use chamber_server::router::init_router;
+    use shuttle_persist::PersistInstance;
+    use sqlx::PgPool;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use tower::ServiceExt;
+    use tracing::Level;
+
+    /// Collects everything that's logged, so that it can be searched.
+    #[derive(Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn the_root_key_is_never_logged() {
+        let dir = std::env::temp_dir().join(format!("chamber-keyfile-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+
+        let logs = Logs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        // Nothing here needs the database
+        let pool = PgPool::connect_lazy("postgres://localhost/chamber").unwrap();
+        let persist = PersistInstance::new(dir.join("persist")).unwrap();
+        let state = ShuttleAppState::new(pool, persist, HashConfig::default());
+
+        state.check_keyfile_exists().unwrap();
+        let key = state.get_keyfile().unwrap().unseal_key().to_string();
+
+        let response = init_router(state)
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/unseal")
+                    .header("x-chamber-key", &key)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("Unseal authorised"));
+        assert!(!logs.contains(&key));
+    }
+}
@@ -0,0 +1,126 @@
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderMap;
+    use chamber_core::limits::{AttemptLimiter, LimiterConfig};
+    use chamber_server::proxies::TrustedProxies;
+    use std::net::IpAddr;
+    use std::time::Duration;
+
+    fn limiter() -> AttemptLimiter {
+        AttemptLimiter::new(LimiterConfig {
+            max_failures: 3,
+            window: Duration::from_secs(60),
+            base_lockout: Duration::from_secs(10),
+            max_lockout: Duration::from_secs(25),
+        })
+    }
+
+    #[test]
+    fn keys_are_locked_out_after_too_many_failures() {
+        let limiter = limiter();
+
+        assert!(limiter.record_failure("user:root").is_none());
+        assert!(limiter.record_failure("user:root").is_none());
+        assert!(limiter.check("user:root").is_none());
+
+        assert_eq!(
+            limiter.record_failure("user:root"),
+            Some(Duration::from_secs(10))
+        );
+        assert!(limiter.check("user:root").is_some());
+
+        // Other keys aren't affected
+        assert!(limiter.check("user:someone_else").is_none());
+    }
+
+    #[test]
+    fn lockouts_double_up_to_the_maximum() {
+        let limiter = limiter();
+
+        let lockouts: Vec<Option<Duration>> = (0..9)
+            .map(|_| limiter.record_failure("ip:127.0.0.1"))
+            .filter(Option::is_some)
+            .collect();
+
+        assert_eq!(
+            lockouts,
+            vec![
+                Some(Duration::from_secs(10)),
+                Some(Duration::from_secs(20)),
+                Some(Duration::from_secs(25)),
+            ]
+        );
+    }
+
+    #[test]
+    fn resetting_clears_the_lockout() {
+        let limiter = limiter();
+
+        for _ in 0..3 {
+            limiter.record_failure("user:root");
+        }
+        assert!(limiter.check("user:root").is_some());
+
+        limiter.reset("user:root");
+        assert!(limiter.check("user:root").is_none());
+    }
+
+    #[test]
+    fn throttling_counts_every_attempt() {
+        let limiter = limiter();
+
+        for _ in 0..3 {
+            assert!(limiter.throttle("ip:127.0.0.1").is_none());
+        }
+
+        // Until the lockout is over
+        assert!(limiter.throttle("ip:127.0.0.1").is_some());
+        assert!(limiter.throttle("ip:127.0.0.1").is_some());
+        assert!(limiter.throttle("ip:127.0.0.2").is_none());
+    }
+
+    fn forwarded(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", value.parse().unwrap());
+
+        headers
+    }
+
+    #[test]
+    fn forwarded_ips_are_only_believed_from_trusted_proxies() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let client: IpAddr = "198.51.100.7".parse().unwrap();
+        let headers = forwarded("203.0.113.9, 198.51.100.7");
+
+        // Without a proxy, whoever connected is the client
+        assert_eq!(
+            TrustedProxies::None.client_ip(&headers, Some(client)),
+            "198.51.100.7"
+        );
+        assert_eq!(
+            TrustedProxies::Only(vec![proxy]).client_ip(&headers, Some(client)),
+            "198.51.100.7"
+        );
+
+        // The proxy added the last entry, and the client could have sent the ones before it
+        assert_eq!(
+            TrustedProxies::Only(vec![proxy]).client_ip(&headers, Some(proxy)),
+            "198.51.100.7"
+        );
+        assert_eq!(
+            TrustedProxies::All.client_ip(&headers, None),
+            "198.51.100.7"
+        );
+
+        // Chained proxies are skipped
+        let headers = forwarded("203.0.113.9, 198.51.100.7, 10.0.0.2");
+        let proxies = TrustedProxies::Only(vec![proxy, "10.0.0.2".parse().unwrap()]);
+        assert_eq!(proxies.client_ip(&headers, Some(proxy)), "198.51.100.7");
+
+        // A proxy that didn't add anything leaves the proxy as the client
+        assert_eq!(
+            TrustedProxies::Only(vec![proxy]).client_ip(&HeaderMap::new(), Some(proxy)),
+            "10.0.0.1"
+        );
+    }
+}
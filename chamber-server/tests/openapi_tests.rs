@@ -0,0 +1,171 @@
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Method, Request, StatusCode},
+        Router,
+    };
+    use chamber_core::hashing::HashConfig;
+    use chamber_core::traits::{AppState, ShuttleAppState};
+    use chamber_server::openapi::ApiDoc;
+    use chamber_server::router::init_router;
+    use shuttle_persist::PersistInstance;
+    use sqlx::postgres::PgPoolOptions;
+    use std::collections::BTreeSet;
+    use tower::ServiceExt;
+    use utoipa::openapi::PathItemType;
+    use utoipa::OpenApi;
+
+    const METHODS: [(PathItemType, Method); 5] = [
+        (PathItemType::Get, Method::GET),
+        (PathItemType::Post, Method::POST),
+        (PathItemType::Put, Method::PUT),
+        (PathItemType::Patch, Method::PATCH),
+        (PathItemType::Delete, Method::DELETE),
+    ];
+
+    /// An unsealed router whose database is never connected to, since none of the requests
+    /// here get past authentication.
+    async fn router() -> Router {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/chamber")
+            .unwrap();
+        let persist =
+            PersistInstance::new(std::env::temp_dir().join("chamber-openapi-tests")).unwrap();
+
+        let state = ShuttleAppState::new(pool, persist, HashConfig::default());
+        state.locked_status().unlock().await.unwrap();
+
+        init_router(state)
+    }
+
+    /// Fills in a path's parameters, such as `{name}`.
+    fn concrete(path: &str) -> String {
+        path.split('/')
+            .map(|x| match x.starts_with('{') {
+                true => "example",
+                false => x,
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// The paths that `router.rs` serves under `/v1`, read from its source so that routes
+    /// which were never documented are caught too.
+    fn routed_paths() -> BTreeSet<String> {
+        let source = include_str!("../src/router.rs");
+        let section = |start: &str, end: &str| {
+            let from = source.find(start).unwrap();
+            let to = from + source[from..].find(end).unwrap();
+
+            &source[from..to]
+        };
+
+        let sections = [
+            section("let unlocked", "let v1"),
+            section("fn v1_routes", "fn legacy_routes"),
+            section("fn account_routes", "async fn deprecated"),
+        ];
+
+        let mut paths = BTreeSet::new();
+
+        for section in sections {
+            for statement in section.split(';') {
+                // Routers that are built up front get nested under a prefix later on
+                let prefix = statement
+                    .split_once("let ")
+                    .and_then(|(_, x)| x.split_whitespace().next())
+                    .and_then(|name| {
+                        let nest = section.find(&format!(", {name})"))?;
+                        let start = section[..nest].rfind(".nest(\"")? + ".nest(\"".len();
+
+                        Some(section[start..nest].trim_end_matches('"').to_string())
+                    })
+                    .unwrap_or_default();
+
+                for route in statement.split(".route(").skip(1) {
+                    let path = route.trim_start().trim_start_matches('"');
+                    let path = &path[..path.find('"').unwrap()];
+
+                    let path: Vec<String> = path
+                        .split('/')
+                        .map(|x| match x.strip_prefix(':').or(x.strip_prefix('*')) {
+                            Some(param) => format!("{{{param}}}"),
+                            None => x.to_string(),
+                        })
+                        .collect();
+
+                    paths.insert(format!("/v1{prefix}{}", path.join("/")));
+                }
+            }
+        }
+
+        paths
+    }
+
+    #[test]
+    fn every_v1_route_is_documented() {
+        let spec = ApiDoc::openapi();
+        let documented: BTreeSet<String> = spec
+            .paths
+            .paths
+            .keys()
+            .filter(|x| x.starts_with("/v1/"))
+            .cloned()
+            .collect();
+
+        assert_eq!(documented, routed_paths());
+    }
+
+    #[tokio::test]
+    async fn documented_operations_are_routed() {
+        let spec = ApiDoc::openapi();
+        let router = router().await;
+
+        for (path, item) in &spec.paths.paths {
+            let uri = concrete(path);
+
+            for (kind, method) in METHODS {
+                let req = Request::builder()
+                    .method(method.clone())
+                    .uri(&uri)
+                    .body(Body::empty())
+                    .unwrap();
+
+                let status = router.clone().oneshot(req).await.unwrap().status();
+
+                match item.operations.contains_key(&kind) {
+                    true => assert!(
+                        status != StatusCode::NOT_FOUND && status != StatusCode::METHOD_NOT_ALLOWED,
+                        "{method} {path} is documented but isn't routed ({status})"
+                    ),
+                    false => assert_eq!(
+                        status,
+                        StatusCode::METHOD_NOT_ALLOWED,
+                        "{method} {path} is routed but isn't documented"
+                    ),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn errors_are_documented_on_every_operation() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            for (method, operation) in item.as_object().unwrap() {
+                assert_eq!(
+                    operation["responses"]["default"]["content"]["application/json"]["schema"]
+                        ["$ref"],
+                    "#/components/schemas/ErrorBody",
+                    "{method} {path}"
+                );
+            }
+        }
+
+        assert!(spec["components"]["schemas"]["ErrorBody"].is_object());
+        assert!(spec["components"]["securitySchemes"]["bearer"].is_object());
+        assert!(spec["components"]["securitySchemes"]["root_key"].is_object());
+    }
+}
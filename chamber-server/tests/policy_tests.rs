@@ -0,0 +1,166 @@
+#[cfg(test)]
+mod tests {
+    use chamber_core::policy::{glob_match, PolicyEvaluator, LEGACY_POLICY_NAME};
+    use chamber_core::users::User;
+    use chamber_shared::groups::Group;
+    use chamber_shared::policy::{Capability, Policy, PolicyRule};
+    use chamber_shared::secrets::SecretInfo;
+
+    fn secret(key: &str, access_level: i32, role_whitelist: &[&str]) -> SecretInfo {
+        SecretInfo {
+            key: key.to_string(),
+            tags: vec!["payments".to_string()],
+            access_level,
+            role_whitelist: role_whitelist.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+
+    fn user(access_level: i32, roles: &[&str]) -> User {
+        let mut user = User::new("test_user".to_string());
+        user.set_access_level(access_level);
+        user.set_roles(roles.iter().map(|x| x.to_string()).collect());
+        user
+    }
+
+    fn policy(name: &str, path: &str, capabilities: Vec<Capability>) -> Policy {
+        Policy {
+            name: name.to_string(),
+            description: String::new(),
+            rules: vec![PolicyRule {
+                path: path.to_string(),
+                tags: Vec::new(),
+                max_access_level: None,
+                check_whitelist: false,
+                environments: Vec::new(),
+                capabilities,
+            }],
+            users: vec!["test_user".to_string()],
+            roles: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn glob_matching_works() {
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("payments/*", "payments/prod/db"));
+        assert!(glob_match("payments/?rod", "payments/prod"));
+        assert!(glob_match("*/db/*", "payments/db/password"));
+        assert!(!glob_match("payments/*", "billing/prod"));
+        assert!(!glob_match("payments", "payments/prod"));
+    }
+
+    #[test]
+    fn legacy_policy_matches_access_level_and_whitelist() {
+        let evaluator = PolicyEvaluator::new(user(100, &["Engineer"]), Vec::new());
+
+        let decision = evaluator.evaluate(Capability::Read, &secret("low", 50, &[]));
+        assert!(decision.allowed);
+        assert_eq!(decision.policy.as_deref(), Some(LEGACY_POLICY_NAME));
+
+        assert!(!evaluator.is_allowed(Capability::Read, &secret("high", 500, &[])));
+        assert!(evaluator.is_allowed(Capability::Read, &secret("eng", 50, &["Engineer"])));
+        assert!(!evaluator.is_allowed(Capability::Read, &secret("ops", 50, &["Ops"])));
+        // Listing has never checked the role whitelist.
+        assert!(evaluator.is_allowed(Capability::List, &secret("ops", 50, &["Ops"])));
+    }
+
+    #[test]
+    fn named_policies_grant_capabilities() {
+        let evaluator = PolicyEvaluator::new(
+            user(0, &[]),
+            vec![policy(
+                "payments-read",
+                "payments/*",
+                vec![Capability::Read],
+            )],
+        );
+
+        let decision = evaluator.evaluate(Capability::Read, &secret("payments/prod", 500, &[]));
+        assert!(decision.allowed);
+        assert_eq!(decision.policy.as_deref(), Some("payments-read"));
+
+        assert!(!evaluator.is_allowed(Capability::Delete, &secret("payments/prod", 500, &[])));
+        assert!(!evaluator.is_allowed(Capability::Read, &secret("billing/prod", 500, &[])));
+    }
+
+    #[test]
+    fn explicit_deny_takes_precedence() {
+        let evaluator = PolicyEvaluator::new(
+            user(9001, &[]),
+            vec![policy("no-prod", "*/prod", vec![Capability::Deny])],
+        );
+
+        let decision = evaluator.evaluate(Capability::Read, &secret("payments/prod", 0, &[]));
+        assert!(!decision.allowed);
+        assert_eq!(decision.policy.as_deref(), Some("no-prod"));
+
+        assert!(evaluator.is_allowed(Capability::Read, &secret("payments/dev", 0, &[])));
+    }
+
+    #[test]
+    fn rules_can_be_limited_to_environments() {
+        let mut prod_deny = policy("no-prod", "*", vec![Capability::Deny]);
+        prod_deny.rules[0].environments = vec!["prod".to_string()];
+
+        let staging =
+            PolicyEvaluator::new(user(100, &[]), vec![prod_deny.clone()]).in_environment("staging");
+        assert!(staging.is_allowed(Capability::Read, &secret("payments/db", 0, &[])));
+
+        let prod = PolicyEvaluator::new(user(100, &[]), vec![prod_deny]).in_environment("prod");
+        let decision = prod.evaluate(Capability::Read, &secret("payments/db", 0, &[]));
+        assert!(!decision.allowed);
+        assert_eq!(decision.policy.as_deref(), Some("no-prod"));
+    }
+
+    #[test]
+    fn group_grants_are_merged_into_the_user() {
+        let group = Group {
+            name: "payments-team".to_string(),
+            description: String::new(),
+            members: vec!["test_user".to_string()],
+            subgroups: Vec::new(),
+            roles: vec!["Payments".to_string()],
+            access_level: 300,
+        };
+
+        let evaluator =
+            PolicyEvaluator::new(user(100, &["Engineer"]).with_groups(&[group]), Vec::new());
+
+        assert!(evaluator.is_allowed(Capability::Read, &secret("high", 300, &[])));
+        assert!(evaluator.is_allowed(Capability::Read, &secret("pay", 50, &["Payments"])));
+        assert!(evaluator.is_allowed(Capability::Read, &secret("eng", 50, &["Engineer"])));
+    }
+
+    #[test]
+    fn explaining_a_decision_reports_granting_roles() {
+        let mut ops_policy = policy(
+            "ops-read",
+            "ops/*",
+            vec![Capability::Read, Capability::List],
+        );
+        ops_policy.users = Vec::new();
+        ops_policy.roles = vec!["Ops".to_string()];
+
+        let evaluator = PolicyEvaluator::new(user(0, &["Engineer", "Ops"]), vec![ops_policy]);
+
+        let eng_secret = secret("eng", 0, &["Engineer", "Finance"]);
+        let decision = evaluator.evaluate(Capability::Read, &eng_secret);
+        assert_eq!(decision.policy.as_deref(), Some(LEGACY_POLICY_NAME));
+        assert_eq!(
+            evaluator.granting_roles(&decision, &eng_secret),
+            vec!["Engineer"]
+        );
+
+        let ops_secret = secret("ops/db", 50, &[]);
+        let decision = evaluator.evaluate(Capability::Read, &ops_secret);
+        assert_eq!(decision.policy.as_deref(), Some("ops-read"));
+        assert_eq!(
+            evaluator.granting_roles(&decision, &ops_secret),
+            vec!["Ops"]
+        );
+        assert_eq!(
+            evaluator.allowed_capabilities(&ops_secret),
+            vec![Capability::Read, Capability::List]
+        );
+    }
+}
@@ -1,7 +1,3 @@
-use chamber_core::traits::AppState;
-use chamber_core::traits::RegularAppState;
-use chamber_server::router::init_router;
-
 mod common;
 const BOUNDARY: &str = "------------------------ea3bbcf87c101592";
 
@@ -9,111 +5,87 @@ const BOUNDARY: &str = "------------------------ea3bbcf87c101592";
 mod tests {
     use super::*;
     use axum::{
-        body::{Body, HttpBody},
+        body::Body,
+        extract::ConnectInfo,
         http::{self, Request, StatusCode},
     };
-    use std::net::SocketAddr;
-    use std::net::TcpListener;
-
-    use chamber_core::secrets::KeyFile;
+    use chamber_core::core::Database;
+    use chamber_core::totp;
+    use chamber_core::traits::AppState;
+    use chamber_crypto::errors::DatabaseError as CryptoError;
+    use chamber_crypto::secrets::{EncryptedSecretBuilder, KeyFile, SerializeKey};
+    use chamber_crypto::signing::check_signing_key_exists;
+    use chamber_server::auth::ClientCertificate;
+    use chamber_server::keys;
+    use chamber_shared::attempts::FailedAttempt;
+    use chamber_shared::audit::AuditEntry;
+    use chamber_shared::environments::ENVIRONMENT_HEADER;
+    use chamber_shared::errors::{ErrorBody, ErrorCode};
+    use chamber_shared::namespaces::{Namespace, NAMESPACE_HEADER};
+    use chamber_shared::secrets::{SecretInfo, NEXT_CURSOR_HEADER};
+    use chamber_shared::users::{RecoveryCodes, TwoFactorEnrollment, UserSummary};
     use std::io::Write;
-    use tower::ServiceExt;
+    use std::net::SocketAddr;
 
     #[tokio::test]
     async fn hello_world() {
-        let pool = common::postgres::get_test_db_connection().await;
-        let state = RegularAppState::new(pool);
-        state.check_keyfile_exists();
-
-        let app = init_router(state);
+        let app = common::TestApp::new().await;
 
         let response = app
-            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
-            .await
-            .unwrap();
+            .request(
+                Request::builder()
+                    .uri("/v1/secrets")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
 
         assert_eq!(response.status(), StatusCode::LOCKED);
 
-        let body = response.into_body().collect().await.unwrap().to_bytes();
-        assert_eq!(&body[..], b"The vault is locked!");
+        let body: ErrorBody = serde_json::from_slice(&common::body_bytes(response).await).unwrap();
+        assert_eq!(body.code, ErrorCode::Locked);
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
     async fn create_user() {
-        let pool = common::postgres::get_test_db_connection().await;
-        let state = RegularAppState::new(pool);
-
-        let app = init_router(state.clone());
-
-        let listener = TcpListener::bind("0.0.0.0:0".parse::<SocketAddr>().unwrap()).unwrap();
-        let addr = listener.local_addr().unwrap();
-
-        tokio::spawn(async move {
-            axum::Server::from_tcp(listener)
-                .unwrap()
-                .serve(app.into_make_service())
-                .await
-                .unwrap();
-        });
+        let app = common::TestApp::new().await;
 
-        let _ =
-            common::create_user_and_log_in(addr, state.get_keyfile().unwrap().unseal_key()).await;
+        let _ = common::create_user_and_log_in(&app).await;
 
         let test_user = "test_user";
 
-        let client = hyper::Client::new();
-
-        let response = client
+        let response = app
             .request(
                 Request::builder()
                     .method(http::Method::POST)
                     .header("Content-Type", "application/json")
-                    .header("x-chamber-key", state.get_keyfile().unwrap().unseal_key())
-                    .uri(format!("http://{}/users/create", addr))
+                    .header("x-chamber-key", app.root_key())
+                    .uri("/users/create")
                     .body(Body::from(
                         serde_json::to_vec(&serde_json::json!({"name": test_user})).unwrap(),
                     ))
                     .unwrap(),
             )
-            .await
-            .unwrap();
+            .await;
 
-        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-        let password = std::str::from_utf8(&body).unwrap();
+        let password = common::body_string(response).await;
         println!("{password}");
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
     async fn creating_a_secret_works() {
-        let pool = common::postgres::get_test_db_connection().await;
-        let state = RegularAppState::new(pool);
-
-        let app = init_router(state.clone());
-
-        let listener = TcpListener::bind("0.0.0.0:0".parse::<SocketAddr>().unwrap()).unwrap();
-        let addr = listener.local_addr().unwrap();
-
-        tokio::spawn(async move {
-            axum::Server::from_tcp(listener)
-                .unwrap()
-                .serve(app.into_make_service())
-                .await
-                .unwrap();
-        });
+        let app = common::TestApp::new().await;
 
-        let jwt_key =
-            common::create_user_and_log_in(addr, state.get_keyfile().unwrap().unseal_key()).await;
+        let jwt_key = common::create_user_and_log_in(&app).await;
 
         println!("{jwt_key}");
 
-        let client = hyper::Client::new();
-
-        let response = client
+        let response = app
             .request(
                 Request::builder()
                     .header("Authorization", &jwt_key)
                     .header("Content-Type", "application/json")
-                    .uri(format!("http://{}/secrets/set", addr))
+                    .uri("/secrets/set")
                     .method(http::Method::POST)
                     .body(Body::from(
                         serde_json::to_vec(
@@ -123,64 +95,44 @@ mod tests {
                     ))
                     .unwrap(),
             )
-            .await
-            .unwrap();
+            .await;
 
         assert_eq!(response.status(), StatusCode::CREATED);
 
-        let response = client
+        let response = app
             .request(
                 Request::builder()
                     .header("Authorization", &jwt_key)
                     .header("Content-Type", "application/json")
-                    .uri(format!("http://{}/secrets/get", addr))
+                    .uri("/secrets/get")
                     .method(http::Method::POST)
                     .body(Body::from(
                         serde_json::to_vec(&serde_json::json!({"key": "hello_world"})).unwrap(),
                     ))
                     .unwrap(),
             )
-            .await
-            .unwrap();
+            .await;
 
         // assert_eq!(response.status(), StatusCode::OK);
 
-        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-        let body = std::str::from_utf8(&body).unwrap();
+        let body = common::body_string(response).await;
         assert_eq!(body, "meme");
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
     async fn create_secret_with_access_level() {
-        let pool = common::postgres::get_test_db_connection().await;
-        let state = RegularAppState::new(pool);
-
-        let app = init_router(state.clone());
+        let app = common::TestApp::new().await;
 
-        let listener = TcpListener::bind("0.0.0.0:0".parse::<SocketAddr>().unwrap()).unwrap();
-        let addr = listener.local_addr().unwrap();
-
-        tokio::spawn(async move {
-            axum::Server::from_tcp(listener)
-                .unwrap()
-                .serve(app.into_make_service())
-                .await
-                .unwrap();
-        });
-
-        let jwt_key =
-            common::create_user_and_log_in(addr, state.get_keyfile().unwrap().unseal_key()).await;
+        let jwt_key = common::create_user_and_log_in(&app).await;
 
         println!("{jwt_key}");
 
-        let client = hyper::Client::new();
-
-        let response = client
+        let response = app
             .request(
                 Request::builder()
                     .header("Authorization", &jwt_key)
                     .header("Content-Type", "application/json")
-                    .uri(format!("http://{}/secrets/set", addr))
+                    .uri("/secrets/set")
                     .method(http::Method::POST)
                     .body(Body::from(
                         serde_json::to_vec(&serde_json::json!({
@@ -193,64 +145,60 @@ mod tests {
                     ))
                     .unwrap(),
             )
-            .await
-            .unwrap();
+            .await;
 
         assert_eq!(response.status(), StatusCode::CREATED);
 
-        let response = client
+        let response = app
             .request(
                 Request::builder()
                     .header("Authorization", &jwt_key)
                     .header("Content-Type", "application/json")
-                    .uri(format!("http://{}/secrets/get", addr))
+                    .uri("/secrets/get")
                     .method(http::Method::POST)
                     .body(Body::from(
                         serde_json::to_vec(&serde_json::json!({"key": "test key"})).unwrap(),
                     ))
                     .unwrap(),
             )
-            .await
-            .unwrap();
+            .await;
 
         assert_eq!(response.status(), StatusCode::OK);
 
-        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-        let body = std::str::from_utf8(&body).unwrap();
+        let body = common::body_string(response).await;
         assert_eq!(body, "test key");
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
     async fn create_secret_with_access_level_and_role() {
-        let pool = common::postgres::get_test_db_connection().await;
-        let state = RegularAppState::new(pool);
-
-        let app = init_router(state.clone());
-
-        let listener = TcpListener::bind("0.0.0.0:0".parse::<SocketAddr>().unwrap()).unwrap();
-        let addr = listener.local_addr().unwrap();
-
-        tokio::spawn(async move {
-            axum::Server::from_tcp(listener)
-                .unwrap()
-                .serve(app.into_make_service())
-                .await
-                .unwrap();
-        });
+        let app = common::TestApp::new().await;
 
-        let jwt_key =
-            common::create_user_and_log_in(addr, state.get_keyfile().unwrap().unseal_key()).await;
+        let jwt_key = common::create_user_and_log_in(&app).await;
 
         println!("{jwt_key}");
 
-        let client = hyper::Client::new();
+        let response = app
+            .request(
+                Request::builder()
+                    .header("x-chamber-key", app.root_key())
+                    .header("Content-Type", "application/json")
+                    .uri("/roles/create")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({"name": "Engineer"})).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::CREATED);
 
-        let response = client
+        let response = app
             .request(
                 Request::builder()
                     .header("Authorization", &jwt_key)
                     .header("Content-Type", "application/json")
-                    .uri(format!("http://{}/secrets/set", addr))
+                    .uri("/secrets/set")
                     .method(http::Method::POST)
                     .body(Body::from(
                         serde_json::to_vec(&serde_json::json!({
@@ -264,103 +212,1615 @@ mod tests {
                     ))
                     .unwrap(),
             )
-            .await
-            .unwrap();
+            .await;
 
-        assert_eq!(response.status(), StatusCode::CREATED);
+        // Users can't create secrets that they wouldn't be able to read themselves
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
 
-        let response = client
+        let response = app
             .request(
                 Request::builder()
                     .header("Authorization", &jwt_key)
                     .header("Content-Type", "application/json")
-                    .uri(format!("http://{}/secrets/get", addr))
+                    .uri("/secrets/get")
                     .method(http::Method::POST)
                     .body(Body::from(
                         serde_json::to_vec(&serde_json::json!({"key": "stripe_test_key"})).unwrap(),
                     ))
                     .unwrap(),
             )
-            .await
-            .unwrap();
+            .await;
 
-        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        // Secrets that can't be read are reported as missing, so their existence isn't leaked
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let body = common::body_bytes(response).await;
+        let body: ErrorBody = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.code, ErrorCode::NotFound);
+        assert!(body.request_id.is_some());
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
     async fn rekeying_works() {
-        let pool = common::postgres::get_test_db_connection().await;
-        let state = RegularAppState::new(pool);
+        let app = common::TestApp::new().await;
 
-        let app = init_router(state.clone());
+        let jwt_key = common::create_user_and_log_in(&app).await;
 
-        let listener = TcpListener::bind("0.0.0.0:0".parse::<SocketAddr>().unwrap()).unwrap();
-        let addr = listener.local_addr().unwrap();
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &jwt_key)
+                    .header("Content-Type", "application/json")
+                    .uri("/secrets/set")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({
+                            "key": "rekeyed_key",
+                            "value":"rekeyed_value"
+                        }))
+                        .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
 
-        tokio::spawn(async move {
-            axum::Server::from_tcp(listener)
-                .unwrap()
-                .serve(app.into_make_service())
-                .await
-                .unwrap();
-        });
+        assert_eq!(response.status(), StatusCode::CREATED);
 
-        let jwt_key =
-            common::create_user_and_log_in(addr, state.get_keyfile().unwrap().unseal_key()).await;
+        let keyfile = KeyFile::new();
 
-        println!("{jwt_key}");
+        let encoded = bincode::serialize(&keyfile).unwrap();
 
-        let client = hyper::Client::new();
+        let mut data = Vec::new();
+        write!(data, "--{}\r\n", BOUNDARY).unwrap();
+        write!(
+            data,
+            "Content-Disposition: form-data; name=\"file\"; filename=\"chamber.bin\"\r\n"
+        )
+        .unwrap();
+        write!(data, "Content-Type: \r\n").unwrap();
+        write!(data, "\r\n").unwrap();
 
-    let keyfile = KeyFile::new();
+        data.write_all(encoded.as_slice()).unwrap();
 
-    let encoded = bincode::serialize(&keyfile).unwrap();
+        write!(data, "\r\n").unwrap(); // The key thing you are missing
+        write!(data, "--{}--\r\n", BOUNDARY).unwrap();
 
-    let mut data = Vec::new();
-    write!(data, "--{}\r\n", BOUNDARY).unwrap();
-    write!(data, "Content-Disposition: form-data; name=\"file\"; filename=\"chamber.bin\"\r\n").unwrap();
-    write!(data, "Content-Type: \r\n").unwrap();
-    write!(data, "\r\n").unwrap();
+        let old_root_key = app.root_key();
 
-    data.write(encoded.as_slice()).unwrap();
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &jwt_key)
+                    .header(
+                        "Content-Type",
+                        &*format!("multipart/form-data; boundary={}", BOUNDARY),
+                    )
+                    .uri("/binfile")
+                    .method(http::Method::POST)
+                    .body(data.clone().into())
+                    .unwrap(),
+            )
+            .await;
 
-    write!(data, "\r\n").unwrap(); // The key thing you are missing
-    write!(data, "--{}--\r\n", BOUNDARY).unwrap();
+        // Logging in isn't enough to replace the keyfile
+        assert_ne!(response.status(), StatusCode::OK);
+        assert_eq!(app.root_key(), old_root_key);
 
-        let response = client
+        let response = app
             .request(
                 Request::builder()
                     .header("Authorization", &jwt_key)
-                    .header("Content-Type", &*format!("multipart/form-data; boundary={}", BOUNDARY))
-                    .uri(format!("http://{}/binfile", addr))
+                    .header("x-chamber-key", app.root_key())
+                    .header(
+                        "Content-Type",
+                        &*format!("multipart/form-data; boundary={}", BOUNDARY),
+                    )
+                    .uri("/binfile")
                     .method(http::Method::POST)
                     .body(data.into())
-                    .unwrap()
-                    )
-            .await
-            .unwrap();
+                    .unwrap(),
+            )
+            .await;
 
         assert_eq!(response.status(), StatusCode::OK);
 
-        let response = client
+        let response = app
             .request(
                 Request::builder()
                     .header("Authorization", &jwt_key)
                     .header("Content-Type", "application/json")
-                    .uri(format!("http://{}/secrets/get", addr))
+                    .uri("/secrets/get")
                     .method(http::Method::POST)
                     .body(Body::from(
-                        serde_json::to_vec(&serde_json::json!({"key": "test key"})).unwrap(),
+                        serde_json::to_vec(&serde_json::json!({"key": "rekeyed_key"})).unwrap(),
                     ))
                     .unwrap(),
             )
-            .await
-            .unwrap();
+            .await;
 
         assert_eq!(response.status(), StatusCode::OK);
 
-        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-        let body = std::str::from_utf8(&body).unwrap();
-        assert_eq!(body, "test key");
+        let body = common::body_string(response).await;
+        assert_eq!(body, "rekeyed_value");
+
+        assert_eq!(app.root_key(), keyfile.unseal_key());
+        for (namespace, wrapped) in app.state.get_data_keys().unwrap() {
+            assert_eq!(wrapped.len(), 1);
+            assert!(keyfile.unwrap_data_key(&namespace, &wrapped[0]).is_ok());
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn users_cannot_write_above_their_access_level() {
+        let app = common::TestApp::new().await;
+
+        let root_jwt = common::create_user_and_log_in(&app).await;
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &root_jwt)
+                    .header("Content-Type", "application/json")
+                    .uri("/secrets/set")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({
+                            "key": "root_only_key",
+                            "value":"root_only_value",
+                            "access_level":500
+                        }))
+                        .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let jwt_key =
+            common::create_user_and_log_in_as(&app, "low_level_user", "low_level_password").await;
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &jwt_key)
+                    .header("Content-Type", "application/json")
+                    .uri("/secrets/set")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({
+                            "key": "too_high_key",
+                            "value":"too_high_value",
+                            "access_level":500
+                        }))
+                        .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &jwt_key)
+                    .header("Content-Type", "application/json")
+                    .uri("/secrets")
+                    .method(http::Method::DELETE)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({"key": "root_only_key"})).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_ne!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn whitelisting_an_unknown_role_fails() {
+        let app = common::TestApp::new().await;
+
+        let jwt_key = common::create_user_and_log_in(&app).await;
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &jwt_key)
+                    .header("Content-Type", "application/json")
+                    .uri("/secrets/set")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({
+                            "key": "typo_key",
+                            "value":"typo_value",
+                            "role_whitelist":["Enginer"]
+                        }))
+                        .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_ne!(response.status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn users_can_view_themselves_and_change_their_password() {
+        let app = common::TestApp::new().await;
+
+        let _ = common::create_user_and_log_in(&app).await;
+        let jwt_key = common::create_user_and_log_in_as(&app, "passwd_user", "old_password").await;
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &jwt_key)
+                    .uri("/me")
+                    .method(http::Method::GET)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = common::body_bytes(response).await;
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.get("username").unwrap(), "passwd_user");
+        assert!(body.get("password").is_none());
+
+        let change_password = |old_password: &str| {
+            Request::builder()
+                .header("Authorization", &jwt_key)
+                .header("Content-Type", "application/json")
+                .uri("/me/password")
+                .method(http::Method::PUT)
+                .body(Body::from(
+                    serde_json::to_vec(&serde_json::json!({
+                        "old_password": old_password,
+                        "new_password": "new_password"
+                    }))
+                    .unwrap(),
+                ))
+                .unwrap()
+        };
+
+        let response = app.request(change_password("wrong_password")).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app.request(change_password("old_password")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Content-Type", "application/json")
+                    .uri("/login")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({
+                            "username": "passwd_user",
+                            "password": "new_password"
+                        }))
+                        .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn disabled_users_are_rejected() {
+        let app = common::TestApp::new().await;
+
+        let _ = common::create_user_and_log_in(&app).await;
+        let jwt_key =
+            common::create_user_and_log_in_as(&app, "disabled_user", "disabled_user").await;
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("x-chamber-key", app.root_key())
+                    .header("Content-Type", "application/json")
+                    .uri("/users/disable")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({"name": "disabled_user"})).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &jwt_key)
+                    .uri("/me")
+                    .method(http::Method::GET)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Content-Type", "application/json")
+                    .uri("/login")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({
+                            "username": "disabled_user",
+                            "password": "disabled_user"
+                        }))
+                        .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn v1_routes_take_names_from_the_path() {
+        let app = common::TestApp::new().await;
+
+        let jwt_key = common::create_user_and_log_in(&app).await;
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &jwt_key)
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/secrets")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(
+                            &serde_json::json!({"key": "prod/db_url", "value": "meme"}),
+                        )
+                        .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(response.headers().get("deprecation").is_none());
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &jwt_key)
+                    .uri("/v1/secrets/prod/db_url")
+                    .method(http::Method::GET)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert_eq!(common::body_string(response).await, "meme");
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &jwt_key)
+                    .header("Content-Type", "application/json")
+                    .uri("/secrets/get")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({"key": "prod/db_url"})).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["deprecation"], "true");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn viewing_a_users_roles_never_shows_their_password() {
+        let app = common::TestApp::new().await;
+
+        let _ = common::create_user_and_log_in(&app).await;
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("x-chamber-key", app.root_key())
+                    .header("Content-Type", "application/json")
+                    .uri("/users/roles")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({"name": "root"})).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body: serde_json::Value =
+            serde_json::from_slice(&common::body_bytes(response).await).unwrap();
+        assert_eq!(body.get("username").unwrap(), "root");
+        assert!(body.get("roles").is_some());
+        assert!(body.get("password").is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn moving_onto_an_existing_key_is_a_conflict() {
+        let app = common::TestApp::new().await;
+
+        let jwt_key = common::create_user_and_log_in(&app).await;
+
+        for key in ["moving/db_url", "taken/db_url"] {
+            let response = app
+                .request(
+                    Request::builder()
+                        .header("Authorization", &jwt_key)
+                        .header("Content-Type", "application/json")
+                        .uri("/v1/secrets")
+                        .method(http::Method::POST)
+                        .body(Body::from(
+                            serde_json::to_vec(&serde_json::json!({"key": key, "value": key}))
+                                .unwrap(),
+                        ))
+                        .unwrap(),
+                )
+                .await;
+
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &jwt_key)
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/folders/move")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({"from": "moving", "to": "taken"}))
+                            .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let body: ErrorBody = serde_json::from_slice(&common::body_bytes(response).await).unwrap();
+        assert_eq!(body.code, ErrorCode::Conflict);
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &jwt_key)
+                    .uri("/v1/secrets/moving/db_url")
+                    .method(http::Method::GET)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn moving_a_folder_leaves_secrets_that_cant_be_read() {
+        let app = common::TestApp::new().await;
+
+        let root_jwt = common::create_user_and_log_in(&app).await;
+        let jwt_key = common::create_user_and_log_in_as(&app, "mover", "mover_password").await;
+
+        for (jwt, key, access_level) in [
+            (&root_jwt, "hiding/root_only", 500),
+            (&jwt_key, "hiding/shared", 0),
+        ] {
+            let response = app
+                .request(
+                    Request::builder()
+                        .header("Authorization", jwt)
+                        .header("Content-Type", "application/json")
+                        .uri("/secrets/set")
+                        .method(http::Method::POST)
+                        .body(Body::from(
+                            serde_json::to_vec(&serde_json::json!({
+                                "key": key,
+                                "value": key,
+                                "access_level": access_level
+                            }))
+                            .unwrap(),
+                        ))
+                        .unwrap(),
+                )
+                .await;
+
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &jwt_key)
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/folders/move")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({"from": "hiding", "to": "shown"}))
+                            .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body: serde_json::Value =
+            serde_json::from_slice(&common::body_bytes(response).await).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!([{"from": "hiding/shared", "to": "shown/shared"}])
+        );
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &root_jwt)
+                    .uri("/v1/secrets/hiding/root_only")
+                    .method(http::Method::GET)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn deleting_a_namespace_destroys_its_data_key() {
+        let app = common::TestApp::new().await;
+
+        let _ = common::create_user_and_log_in(&app).await;
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("x-chamber-key", app.root_key())
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/namespaces")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({"name": "shredded"})).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(app.state.get_data_keys().unwrap().contains_key("shredded"));
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("x-chamber-key", app.root_key())
+                    .uri("/v1/namespaces/shredded")
+                    .method(http::Method::DELETE)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!app.state.get_data_keys().unwrap().contains_key("shredded"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn certificates_only_log_in_to_their_own_namespace() {
+        let app = common::TestApp::new().await;
+
+        let _ = common::create_user_and_log_in(&app).await;
+
+        let cert = |namespace: &str| {
+            Some(ClientCertificate {
+                username: "root".to_string(),
+                namespace: namespace.to_string(),
+                subject: "CN=root".to_string(),
+            })
+        };
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/login/cert")
+                    .method(http::Method::POST)
+                    .extension(cert("payments"))
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({"namespace": "default"})).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = app
+            .request(
+                Request::builder()
+                    .uri("/v1/login/cert")
+                    .method(http::Method::POST)
+                    .extension(cert("default"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn local_usernames_cant_look_like_external_ones() {
+        let app = common::TestApp::new().await;
+
+        let _ = common::create_user_and_log_in(&app).await;
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("x-chamber-key", app.root_key())
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/users")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({
+                            "username": "okta:alice",
+                            "password": "alice_password"
+                        }))
+                        .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body: ErrorBody = serde_json::from_slice(&common::body_bytes(response).await).unwrap();
+        assert_eq!(body.code, ErrorCode::BadRequest);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn recreated_users_dont_inherit_policies() {
+        let app = common::TestApp::new().await;
+
+        let root_jwt = common::create_user_and_log_in(&app).await;
+        let _ = common::create_user_and_log_in_as(&app, "departed", "departed_password").await;
+
+        let policy = |users: &[&str]| {
+            serde_json::json!({
+                "name": "departed_reads",
+                "rules": [{"path": "*", "capabilities": ["read"]}],
+                "users": users
+            })
+        };
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &root_jwt)
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/policies")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&policy(&["departed", "never_existed"])).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &root_jwt)
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/policies")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&policy(&["departed"])).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &root_jwt)
+                    .uri("/v1/users/departed")
+                    .method(http::Method::DELETE)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Updating the policy checks the users it names too
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &root_jwt)
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/policies/departed_reads")
+                    .method(http::Method::PUT)
+                    .body(Body::from(
+                        serde_json::to_vec(&policy(&["departed"])).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let _ = common::create_user_and_log_in_as(&app, "departed", "new_password").await;
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &root_jwt)
+                    .uri("/v1/policies/departed_reads")
+                    .method(http::Method::GET)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body: serde_json::Value =
+            serde_json::from_slice(&common::body_bytes(response).await).unwrap();
+        assert_eq!(body.get("users").unwrap(), &serde_json::json!([]));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn namespaces_keep_their_secrets_users_and_policies_apart() {
+        let app = common::TestApp::new().await;
+
+        let root_jwt = common::create_user_and_log_in(&app).await;
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("x-chamber-key", app.root_key())
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/namespaces")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({"name": "tenant"})).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("x-chamber-key", app.root_key())
+                    .header(NAMESPACE_HEADER, "tenant")
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/users")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({
+                            "username": "tenant_admin",
+                            "password": "tenant_password",
+                            "namespace_admin": true
+                        }))
+                        .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let tenant_jwt = common::log_in_to(&app, "tenant", "tenant_admin", "tenant_password").await;
+
+        // The same key can be used in both, and each only sees its own
+        for (jwt, value) in [(&root_jwt, "default_value"), (&tenant_jwt, "tenant_value")] {
+            let response = app
+                .request(
+                    Request::builder()
+                        .header("Authorization", jwt)
+                        .header("Content-Type", "application/json")
+                        .uri("/v1/secrets")
+                        .method(http::Method::POST)
+                        .body(Body::from(
+                            serde_json::to_vec(
+                                &serde_json::json!({"key": "isolated/key", "value": value}),
+                            )
+                            .unwrap(),
+                        ))
+                        .unwrap(),
+                )
+                .await;
+
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+
+        for (jwt, value) in [(&root_jwt, "default_value"), (&tenant_jwt, "tenant_value")] {
+            let response = app
+                .request(
+                    Request::builder()
+                        .header("Authorization", jwt)
+                        .header("Content-Type", "application/json")
+                        .uri("/secrets/get")
+                        .method(http::Method::POST)
+                        .body(Body::from(
+                            serde_json::to_vec(&serde_json::json!({"key": "isolated/key"}))
+                                .unwrap(),
+                        ))
+                        .unwrap(),
+                )
+                .await;
+
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(common::body_string(response).await, value);
+        }
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &tenant_jwt)
+                    .uri("/v1/users")
+                    .method(http::Method::GET)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let users: Vec<UserSummary> =
+            serde_json::from_slice(&common::body_bytes(response).await).unwrap();
+        assert_eq!(
+            users
+                .iter()
+                .map(|x| x.username.as_str())
+                .collect::<Vec<_>>(),
+            ["tenant_admin"]
+        );
+
+        let policy = |users: &[&str]| {
+            Body::from(
+                serde_json::to_vec(&serde_json::json!({
+                    "name": "tenant_reads",
+                    "rules": [{"path": "*", "capabilities": ["read"]}],
+                    "users": users
+                }))
+                .unwrap(),
+            )
+        };
+
+        // Root is a user of the default namespace, not this one
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &tenant_jwt)
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/policies")
+                    .method(http::Method::POST)
+                    .body(policy(&["root"]))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &tenant_jwt)
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/policies")
+                    .method(http::Method::POST)
+                    .body(policy(&["tenant_admin"]))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &root_jwt)
+                    .uri("/v1/policies/tenant_reads")
+                    .method(http::Method::GET)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn data_keys_are_made_when_first_needed_and_reencrypt_older_secrets() {
+        let app = common::TestApp::new().await;
+
+        let _ = common::create_user_and_log_in(&app).await;
+
+        // Namespaces from before data keys have secrets sealed with the crypto key, so these are
+        // made without going through the API, which would give them a data key straight away
+        for name in ["legacy", "legacy-upload"] {
+            app.state
+                .db()
+                .create_namespace(Namespace {
+                    name: name.to_string(),
+                    description: String::new(),
+                })
+                .await
+                .unwrap();
+
+            let response = app
+                .request(
+                    Request::builder()
+                        .header("x-chamber-key", app.root_key())
+                        .header(NAMESPACE_HEADER, name)
+                        .header("Content-Type", "application/json")
+                        .uri("/v1/users")
+                        .method(http::Method::POST)
+                        .body(Body::from(
+                            serde_json::to_vec(&serde_json::json!({
+                                "username": "owner",
+                                "password": "owner_password"
+                            }))
+                            .unwrap(),
+                        ))
+                        .unwrap(),
+                )
+                .await;
+
+            assert_eq!(response.status(), StatusCode::CREATED);
+
+            let db = app.state.db().in_namespace(name);
+            let owner = db.get_user_from_name("owner".to_string()).await.unwrap();
+
+            check_signing_key_exists().unwrap();
+
+            let mut keyfile = app.state.get_keyfile().unwrap();
+            let nonce = keyfile.next_nonce();
+            let secret = EncryptedSecretBuilder::new("old_key".to_string(), name.to_string())
+                .build(keyfile.crypto_key().seal_key(nonce).unwrap(), nonce)
+                .unwrap();
+            app.state.save_keyfile(keyfile).unwrap();
+
+            db.create_secret(owner, secret).await.unwrap();
+            assert!(!db.has_data_key().await.unwrap());
+        }
+
+        async fn open(
+            app: &common::TestApp,
+            name: &str,
+            key: &SerializeKey,
+        ) -> Result<String, CryptoError> {
+            let db = app.state.db().in_namespace(name);
+            let owner = db.get_user_from_name("owner".to_string()).await.unwrap();
+            let secret = db
+                .view_secret_decrypted(owner, "old_key".to_string())
+                .await
+                .unwrap();
+
+            secret.decrypt(key.open_key(secret.nonce.0)?)
+        }
+
+        let Ok(data_key) = keys::data_key(&app.state, "legacy").await else {
+            panic!("legacy should have been given a data key");
+        };
+        assert!(app
+            .state
+            .db()
+            .in_namespace("legacy")
+            .has_data_key()
+            .await
+            .unwrap());
+        assert_eq!(open(&app, "legacy", &data_key).await.unwrap(), "legacy");
+
+        let old_keyfile = app.state.get_keyfile().unwrap();
+        assert!(open(&app, "legacy", old_keyfile.crypto_key())
+            .await
+            .is_err());
+
+        // Uploading a keyfile gives the namespaces that are left a data key before re-wrapping
+        // them all, as their secrets can't be opened with the new crypto key
+        let keyfile = KeyFile::new();
+        let root_key = keyfile.unseal_key().to_string();
+        assert!(keys::replace_keyfile(&app.state, keyfile).await.is_ok());
+
+        let keyfile = app.state.get_keyfile().unwrap();
+        assert_eq!(keyfile.unseal_key(), root_key);
+
+        for name in ["legacy", "legacy-upload"] {
+            let wrapped = &app.state.get_data_keys().unwrap()[name];
+            assert_eq!(wrapped.len(), 1);
+
+            let data_key = keyfile.unwrap_data_key(name, &wrapped[0]).unwrap();
+            assert_eq!(open(&app, name, &data_key).await.unwrap(), name);
+            assert!(open(&app, name, old_keyfile.crypto_key()).await.is_err());
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn promoting_needs_access_to_both_environments_and_is_audited() {
+        let app = common::TestApp::new().await;
+
+        let root_jwt = common::create_user_and_log_in(&app).await;
+        let jwt_key =
+            common::create_user_and_log_in_as(&app, "promoter", "promoter_password").await;
+
+        // The promoter can read the shared secret in staging but can't change it in prod
+        for (key, environment, access_level) in [
+            ("promoted/root_only", "staging", 500),
+            ("promoted/shared", "staging", 0),
+            ("promoted/shared", "prod", 500),
+        ] {
+            let response = app
+                .request(
+                    Request::builder()
+                        .header("Authorization", &root_jwt)
+                        .header(ENVIRONMENT_HEADER, environment)
+                        .header("Content-Type", "application/json")
+                        .uri("/secrets/set")
+                        .method(http::Method::POST)
+                        .body(Body::from(
+                            serde_json::to_vec(&serde_json::json!({
+                                "key": key,
+                                "value": format!("{environment}_value"),
+                                "access_level": access_level
+                            }))
+                            .unwrap(),
+                        ))
+                        .unwrap(),
+                )
+                .await;
+
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+
+        let promote = |jwt: &str, key: &str, from: &str, to: &str| {
+            Request::builder()
+                .header("Authorization", jwt)
+                .header("Content-Type", "application/json")
+                .uri("/v1/environments/promote")
+                .method(http::Method::POST)
+                .body(Body::from(
+                    serde_json::to_vec(&serde_json::json!({
+                        "key": key,
+                        "from": from,
+                        "to": to
+                    }))
+                    .unwrap(),
+                ))
+                .unwrap()
+        };
+
+        // Secrets that can't be read aren't there as far as the promoter can tell, whichever
+        // environment they're in
+        for key in ["promoted/root_only", "promoted/shared"] {
+            let response = app.request(promote(&jwt_key, key, "staging", "prod")).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+
+        let response = app
+            .request(promote(
+                &root_jwt,
+                "promoted/root_only",
+                "staging",
+                "staging",
+            ))
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .request(promote(&root_jwt, "promoted/root_only", "staging", "prod"))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let get = |key: &str| {
+            Request::builder()
+                .header("Authorization", &root_jwt)
+                .header(ENVIRONMENT_HEADER, "prod")
+                .header("Content-Type", "application/json")
+                .uri("/secrets/get")
+                .method(http::Method::POST)
+                .body(Body::from(
+                    serde_json::to_vec(&serde_json::json!({"key": key})).unwrap(),
+                ))
+                .unwrap()
+        };
+
+        let response = app.request(get("promoted/root_only")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(common::body_string(response).await, "staging_value");
+
+        let response = app.request(get("promoted/shared")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(common::body_string(response).await, "prod_value");
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("x-chamber-key", app.root_key())
+                    .uri("/v1/audit")
+                    .method(http::Method::GET)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Only the promotion that went through is recorded
+        let entries: Vec<AuditEntry> =
+            serde_json::from_slice(&common::body_bytes(response).await).unwrap();
+        let entries = entries
+            .into_iter()
+            .filter(|x| x.key.starts_with("promoted/"))
+            .collect::<Vec<_>>();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key, "promoted/root_only");
+        assert_eq!(entries[0].action, "promote");
+        assert_eq!(entries[0].username, "root");
+        assert_eq!(entries[0].from_environment.as_deref(), Some("staging"));
+        assert_eq!(entries[0].environment, "prod");
+
+        // Limits below one still return the newest entry
+        for limit in [0, -1] {
+            let response = app
+                .request(
+                    Request::builder()
+                        .header("x-chamber-key", app.root_key())
+                        .uri(format!("/v1/audit?limit={limit}"))
+                        .method(http::Method::GET)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await;
+
+            assert_eq!(response.status(), StatusCode::OK, "limit {limit}");
+
+            let entries: Vec<AuditEntry> =
+                serde_json::from_slice(&common::body_bytes(response).await).unwrap();
+            assert_eq!(entries.len(), 1, "limit {limit}");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn paging_through_secrets_skips_and_repeats_nothing() {
+        let app = common::TestApp::new().await;
+
+        let root_jwt = common::create_user_and_log_in(&app).await;
+        let jwt_key = common::create_user_and_log_in_as(&app, "pager", "pager_password").await;
+
+        let mut keys = Vec::new();
+        for i in 0..7 {
+            keys.push(format!("paged/{i:02}"));
+        }
+
+        // The pager can't list this one, so pages have to be filled past it
+        for (jwt, key, access_level) in keys.iter().map(|x| (&jwt_key, x.as_str(), 0)).chain([(
+            &root_jwt,
+            "paged/03_root_only",
+            500,
+        )]) {
+            let response = app
+                .request(
+                    Request::builder()
+                        .header("Authorization", jwt)
+                        .header("Content-Type", "application/json")
+                        .uri("/secrets/set")
+                        .method(http::Method::POST)
+                        .body(Body::from(
+                            serde_json::to_vec(&serde_json::json!({
+                                "key": key,
+                                "value": key,
+                                "access_level": access_level
+                            }))
+                            .unwrap(),
+                        ))
+                        .unwrap(),
+                )
+                .await;
+
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+
+        let list = |query: String| {
+            Request::builder()
+                .header("Authorization", &jwt_key)
+                .uri(format!("/v1/secrets?key=paged/&{query}"))
+                .method(http::Method::GET)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        for sort in ["key", "created"] {
+            for order in ["asc", "desc"] {
+                let response = app
+                    .request(list(format!("sort={sort}&order={order}")))
+                    .await;
+                assert_eq!(response.status(), StatusCode::OK);
+
+                let everything: Vec<SecretInfo> =
+                    serde_json::from_slice(&common::body_bytes(response).await).unwrap();
+                let everything = everything.into_iter().map(|x| x.key).collect::<Vec<_>>();
+
+                let mut sorted = everything.clone();
+                sorted.sort();
+                assert_eq!(sorted, keys);
+
+                let mut paged = Vec::new();
+                let mut cursor = None;
+
+                loop {
+                    let mut query = format!("sort={sort}&order={order}&limit=3");
+                    if let Some(cursor) = &cursor {
+                        query.push_str(&format!("&cursor={cursor}"));
+                    }
+
+                    let response = app.request(list(query)).await;
+                    assert_eq!(response.status(), StatusCode::OK);
+
+                    cursor = response
+                        .headers()
+                        .get(NEXT_CURSOR_HEADER)
+                        .map(|x| x.to_str().unwrap().to_string());
+
+                    let page: Vec<SecretInfo> =
+                        serde_json::from_slice(&common::body_bytes(response).await).unwrap();
+                    assert!(page.len() <= 3);
+                    paged.extend(page.into_iter().map(|x| x.key));
+
+                    if cursor.is_none() {
+                        break;
+                    }
+                }
+
+                assert_eq!(paged, everything, "sorted by {sort} {order}");
+            }
+        }
+
+        let mut descending = keys.clone();
+        descending.reverse();
+
+        let response = app.request(list("sort=key&order=desc".to_string())).await;
+        let listed: Vec<SecretInfo> =
+            serde_json::from_slice(&common::body_bytes(response).await).unwrap();
+        assert_eq!(
+            listed.into_iter().map(|x| x.key).collect::<Vec<_>>(),
+            descending
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn two_factor_logins_need_a_code_that_hasnt_been_used() {
+        let app = common::TestApp::new().await;
+
+        let _ = common::create_user_and_log_in(&app).await;
+        let jwt_key =
+            common::create_user_and_log_in_as(&app, "second_factor", "second_password").await;
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &jwt_key)
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/me/2fa/enroll")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({"password": "second_password"}))
+                            .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let enrollment: TwoFactorEnrollment =
+            serde_json::from_slice(&common::body_bytes(response).await).unwrap();
+        let step = totp::time_step(chrono::Utc::now().timestamp() as u64);
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &jwt_key)
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/me/2fa/confirm")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({
+                            "code": totp::code_at(&enrollment.secret, step).unwrap()
+                        }))
+                        .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let codes: RecoveryCodes =
+            serde_json::from_slice(&common::body_bytes(response).await).unwrap();
+
+        // Wrong codes count as failed logins, so they're kept away from the other tests' IP
+        let log_in = |code: Option<String>| {
+            Request::builder()
+                .header("Content-Type", "application/json")
+                .uri("/login")
+                .method(http::Method::POST)
+                .extension(ConnectInfo(SocketAddr::new([198, 51, 100, 35].into(), 443)))
+                .body(Body::from(
+                    serde_json::to_vec(&serde_json::json!({
+                        "username": "second_factor",
+                        "password": "second_password",
+                        "code": code
+                    }))
+                    .unwrap(),
+                ))
+                .unwrap()
+        };
+
+        let response = app.request(log_in(None)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body: ErrorBody = serde_json::from_slice(&common::body_bytes(response).await).unwrap();
+        assert_eq!(body.code, ErrorCode::TwoFactorRequired);
+
+        let response = app.request(log_in(Some("000000".to_string()))).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body: ErrorBody = serde_json::from_slice(&common::body_bytes(response).await).unwrap();
+        assert_eq!(body.code, ErrorCode::Unauthorised);
+
+        // The code that confirmed the enrollment has been used, but the next one hasn't
+        let response = app
+            .request(log_in(totp::code_at(&enrollment.secret, step)))
+            .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app
+            .request(log_in(totp::code_at(&enrollment.secret, step + 1)))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .request(log_in(Some(codes.recovery_codes[0].clone())))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .request(log_in(Some(codes.recovery_codes[0].clone())))
+            .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn users_above_the_two_factor_level_have_to_enroll() {
+        let app = common::TestApp::new().await;
+
+        let _ = common::create_user_and_log_in(&app).await;
+
+        let high_jwt =
+            common::create_user_and_log_in_as(&app, "unenrolled_high", "unenrolled_password").await;
+        let low_jwt =
+            common::create_user_and_log_in_as(&app, "unenrolled_low", "unenrolled_password").await;
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("x-chamber-key", app.root_key())
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/users/unenrolled_high")
+                    .method(http::Method::PATCH)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({"access_level": 200})).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let set_level = |level: Option<i32>| {
+            Request::builder()
+                .header("x-chamber-key", app.root_key())
+                .header("Content-Type", "application/json")
+                .uri("/v1/settings/two_factor")
+                .method(http::Method::PUT)
+                .body(Body::from(
+                    serde_json::to_vec(&serde_json::json!({"required_above": level})).unwrap(),
+                ))
+                .unwrap()
+        };
+
+        let response = app.request(set_level(Some(100))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let list = |jwt: &str| {
+            Request::builder()
+                .header("Authorization", jwt)
+                .uri("/v1/secrets")
+                .method(http::Method::GET)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // Users above the level can't do anything without a second factor...
+        let high = app.request(list(&high_jwt)).await;
+        let low = app.request(list(&low_jwt)).await;
+
+        // ...but they can still enroll
+        let enroll = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &high_jwt)
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/me/2fa/enroll")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({"password": "unenrolled_password"}))
+                            .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        // The level is shared by every test, so it's put back before anything is checked
+        let response = app.request(set_level(None)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert_eq!(high.status(), StatusCode::FORBIDDEN);
+        let body: ErrorBody = serde_json::from_slice(&common::body_bytes(high).await).unwrap();
+        assert_eq!(body.code, ErrorCode::TwoFactorEnrollmentRequired);
+        assert_eq!(enroll.status(), StatusCode::OK);
+
+        assert_eq!(low.status(), StatusCode::OK);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn moving_only_moves_one_environment() {
+        let app = common::TestApp::new().await;
+
+        let jwt_key = common::create_user_and_log_in(&app).await;
+
+        for environment in ["default", "staging"] {
+            let response = app
+                .request(
+                    Request::builder()
+                        .header("Authorization", &jwt_key)
+                        .header(ENVIRONMENT_HEADER, environment)
+                        .header("Content-Type", "application/json")
+                        .uri("/v1/secrets")
+                        .method(http::Method::POST)
+                        .body(Body::from(
+                            serde_json::to_vec(
+                                &serde_json::json!({"key": "staying/key", "value": environment}),
+                            )
+                            .unwrap(),
+                        ))
+                        .unwrap(),
+                )
+                .await;
+
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+
+        let response = app
+            .request(
+                Request::builder()
+                    .header("Authorization", &jwt_key)
+                    .header(ENVIRONMENT_HEADER, "staging")
+                    .header("Content-Type", "application/json")
+                    .uri("/v1/folders/move")
+                    .method(http::Method::POST)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({"from": "staying", "to": "gone"}))
+                            .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        for (environment, key, status) in [
+            ("default", "staying/key", StatusCode::OK),
+            ("default", "gone/key", StatusCode::NOT_FOUND),
+            ("staging", "staying/key", StatusCode::NOT_FOUND),
+            ("staging", "gone/key", StatusCode::OK),
+        ] {
+            let response = app
+                .request(
+                    Request::builder()
+                        .header("Authorization", &jwt_key)
+                        .header(ENVIRONMENT_HEADER, environment)
+                        .uri(format!("/v1/secrets/{key}"))
+                        .method(http::Method::GET)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await;
+
+            assert_eq!(response.status(), status, "{key} in {environment}");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn failed_attempt_limits_are_clamped() {
+        let app = common::TestApp::new().await;
+
+        let _ = common::create_user_and_log_in(&app).await;
+
+        for _ in 0..2 {
+            let response = app
+                .request(
+                    Request::builder()
+                        .header("Content-Type", "application/json")
+                        .uri("/login")
+                        .method(http::Method::POST)
+                        .extension(ConnectInfo(SocketAddr::new([198, 51, 100, 36].into(), 443)))
+                        .body(Body::from(
+                            serde_json::to_vec(&serde_json::json!({
+                                "username": "root",
+                                "password": "not_the_password"
+                            }))
+                            .unwrap(),
+                        ))
+                        .unwrap(),
+                )
+                .await;
+
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        for (limit, expected) in [(0, 1), (-1, 1), (2, 2)] {
+            let response = app
+                .request(
+                    Request::builder()
+                        .header("x-chamber-key", app.root_key())
+                        .header("Content-Type", "application/json")
+                        .uri("/v1/failed_attempts")
+                        .method(http::Method::POST)
+                        .body(Body::from(
+                            serde_json::to_vec(&serde_json::json!({"limit": limit})).unwrap(),
+                        ))
+                        .unwrap(),
+                )
+                .await;
+
+            assert_eq!(response.status(), StatusCode::OK, "limit {limit}");
+
+            let attempts: Vec<FailedAttempt> =
+                serde_json::from_slice(&common::body_bytes(response).await).unwrap();
+            assert_eq!(attempts.len(), expected, "limit {limit}");
+        }
     }
 }
@@ -0,0 +1,312 @@
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::extract::ConnectInfo;
+    use axum::http::{Method, Request, StatusCode};
+    use chamber_core::challenges::ChallengeStore;
+    use chamber_core::errors::DatabaseError;
+    use chamber_core::hashing::HashConfig;
+    use chamber_core::ssh::{parse_public_key, signature_fingerprint, verify_signature};
+    use chamber_core::traits::{AppState, ShuttleAppState};
+    use chamber_server::router::init_router;
+    use chamber_shared::ssh::{SshChallenge, UnsealKey, SSH_LOGIN_NAMESPACE};
+    use serde_json::json;
+    use shuttle_persist::PersistInstance;
+    use sqlx::postgres::PgPoolOptions;
+    use sqlx::PgPool;
+    use ssh_key::{HashAlg, LineEnding, PrivateKey};
+    use std::net::SocketAddr;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    // Made with `ssh-keygen -t ed25519` and `ssh-keygen -Y sign -n chamber-login` over NONCE
+    static PUBLIC_KEY: &str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIE3izRqqYk736gUht1Nk4uFsIfvZverh9OYH4GozlCHm test@chamber";
+    static FINGERPRINT: &str = "SHA256:DhCnjcBhYCZqo+F946KX23ie+HgG3sjPlcXUI15ke+A";
+    static NONCE: &str = "deadbeef";
+    static SIGNATURE: &str = "-----BEGIN SSH SIGNATURE-----\nU1NIU0lHAAAAAQAAADMAAAALc3NoLWVkMjU1MTkAAAAgTeLNGqpiTvfqBSG3U2Ti4Wwh+9\nm96uH05gfgajOUIeYAAAANY2hhbWJlci1sb2dpbgAAAAAAAAAGc2hhNTEyAAAAUwAAAAtz\nc2gtZWQyNTUxOQAAAEBD/bTQhsJaxFRUD4F9P9HVFvCDwdaWCvqLqnSd6mkmnBVKwT7PFD\nguAamPX2FyXZ/N0SuRlw3zKf/wZ7nsRzEH\n-----END SSH SIGNATURE-----";
+
+    #[test]
+    fn ed25519_keys_are_parsed_and_other_keys_are_rejected() {
+        let key = parse_public_key(PUBLIC_KEY).unwrap();
+
+        assert_eq!(key.fingerprint, FINGERPRINT);
+        assert_eq!(key.comment, "test@chamber");
+        assert!(!key.public_key.contains("test@chamber"));
+
+        assert!(parse_public_key("ecdsa-sha2-nistp256 AAAAE2VjZHNhLXNoYTItbmlzdHAyNTYAAAAIbmlzdHAyNTYAAABBBM8D6nqqw+xrRGP0YuRTKBBvsrEIKcrdECCWG1MyQRexbhTr41smshVJ2HyC9d/3ddPMvE/Lrm5SeI9t6OGfn9g=").is_err());
+        assert!(parse_public_key("not a key").is_err());
+    }
+
+    #[test]
+    fn signatures_from_ssh_keygen_verify() {
+        let key = parse_public_key(PUBLIC_KEY).unwrap();
+        let (sig, fingerprint) = signature_fingerprint(SIGNATURE).unwrap();
+
+        assert_eq!(fingerprint, FINGERPRINT);
+        assert!(verify_signature(&key, SSH_LOGIN_NAMESPACE, NONCE.as_bytes(), &sig).is_ok());
+        assert!(verify_signature(&key, SSH_LOGIN_NAMESPACE, b"another nonce", &sig).is_err());
+        assert!(verify_signature(&key, "git", NONCE.as_bytes(), &sig).is_err());
+    }
+
+    #[test]
+    fn challenges_can_only_be_used_once_by_their_subject() {
+        let challenges = ChallengeStore::default();
+
+        let nonce = challenges.issue("alice").unwrap();
+        assert!(!challenges.take(&nonce, "bob"));
+
+        // Taking it as the wrong user still uses it up
+        assert!(!challenges.take(&nonce, "alice"));
+
+        let nonce = challenges.issue("alice").unwrap();
+        assert!(challenges.take(&nonce, "alice"));
+        assert!(!challenges.take(&nonce, "alice"));
+    }
+
+    #[test]
+    fn expired_challenges_are_rejected() {
+        let challenges = ChallengeStore::new(Duration::ZERO);
+
+        let nonce = challenges.issue("alice").unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+
+        assert!(!challenges.take(&nonce, "alice"));
+    }
+
+    #[test]
+    fn full_stores_refuse_challenges_until_there_is_room() {
+        let challenges = ChallengeStore::default().with_max_outstanding(2);
+
+        let nonce = challenges.issue("alice").unwrap();
+        challenges.issue("bob").unwrap();
+
+        match challenges.issue("mallory") {
+            Err(DatabaseError::TooManyChallenges(wait)) => assert!(wait <= challenges.ttl()),
+            _ => panic!("the store should be full"),
+        }
+
+        assert!(challenges.take(&nonce, "alice"));
+        assert!(challenges.issue("alice").is_ok());
+    }
+
+    #[tokio::test]
+    async fn unseal_challenges_are_kept_apart_and_limited_per_ip() {
+        // Asking for a challenge doesn't need the database
+        let pool = PgPool::connect_lazy("postgres://localhost/chamber").unwrap();
+        let persist = PersistInstance::new(std::env::temp_dir().join("chamber-ssh-tests")).unwrap();
+        let state = ShuttleAppState::new(pool, persist, HashConfig::default());
+
+        // Login challenges running out doesn't stop operators from unsealing
+        while state.challenges().issue("user:mallory").is_ok() {}
+
+        let router = init_router(state);
+        let challenge = |ip: &str| {
+            router.clone().oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/v1/unseal/challenge")
+                    .extension(ConnectInfo(SocketAddr::new(ip.parse().unwrap(), 443)))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        for _ in 0..20 {
+            let res = challenge("203.0.113.1").await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+
+        let res = challenge("203.0.113.1").await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let res = challenge("203.0.113.2").await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn wrong_root_keys_only_lock_out_their_own_client() {
+        // Failed unseals are recorded in the database, which isn't there, so give up on it quickly
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(100))
+            .connect_lazy("postgres://localhost/chamber")
+            .unwrap();
+        let persist =
+            PersistInstance::new(std::env::temp_dir().join("chamber-root-key-tests")).unwrap();
+        let state = ShuttleAppState::new(pool, persist, HashConfig::default());
+        let key = state.get_keyfile().unwrap().unseal_key().to_string();
+
+        let router = init_router(state);
+        let unseal = |ip: &str, key: &str| {
+            router.clone().oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/unseal")
+                    .header("x-chamber-key", key)
+                    .extension(ConnectInfo(SocketAddr::new(ip.parse().unwrap(), 443)))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        let res = unseal("203.0.113.1", "wrong").await.unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let res = unseal("203.0.113.1", &key).await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let res = unseal("203.0.113.2", &key).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    // Made with `ssh-keygen -t ed25519`, without passphrases
+    fn private_key(name: &str) -> PrivateKey {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/ssh")
+            .join(name);
+
+        PrivateKey::read_openssh_file(&path).unwrap()
+    }
+
+    #[tokio::test]
+    async fn changing_unseal_keys_needs_the_approval_of_a_registered_one() {
+        // Rejected approvals are recorded in the database, which isn't there, so give up on it
+        // quickly
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(100))
+            .connect_lazy("postgres://localhost/chamber")
+            .unwrap();
+        let dir = std::env::temp_dir().join("chamber-unseal-key-tests");
+        let _ = std::fs::remove_dir_all(&dir);
+        let state = ShuttleAppState::new(
+            pool,
+            PersistInstance::new(dir).unwrap(),
+            HashConfig::default(),
+        );
+        let root_key = state.get_keyfile().unwrap().unseal_key().to_string();
+
+        let router = init_router(state);
+        // Rejected approvals count against the client like wrong root keys, so each one is sent
+        // from somewhere else
+        let send = |ip: u8, method: Method, uri: &str, body: Body| {
+            router.clone().oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("x-chamber-key", &root_key)
+                    .header("Content-Type", "application/json")
+                    .extension(ConnectInfo(SocketAddr::new([203, 0, 113, ip].into(), 443)))
+                    .body(body)
+                    .unwrap(),
+            )
+        };
+        let json_body = |body: serde_json::Value| Body::from(serde_json::to_vec(&body).unwrap());
+        let approval = |signer: &str| {
+            let router = router.clone();
+            let key = private_key(signer);
+
+            async move {
+                let res = router
+                    .oneshot(
+                        Request::builder()
+                            .method(Method::POST)
+                            .uri("/v1/unseal/challenge")
+                            .extension(ConnectInfo(SocketAddr::new([203, 0, 113, 1].into(), 443)))
+                            .body(Body::empty())
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let challenge: SshChallenge = serde_json::from_slice(&body).unwrap();
+
+                let signature = key
+                    .sign(
+                        &challenge.namespace,
+                        HashAlg::Sha512,
+                        challenge.nonce.as_bytes(),
+                    )
+                    .unwrap()
+                    .to_pem(LineEnding::LF)
+                    .unwrap();
+
+                json!({"nonce": challenge.nonce, "signature": signature})
+            }
+        };
+        let add = |name: &str| {
+            json!({
+                "name": name,
+                "public_key": private_key(name).public_key().to_openssh().unwrap(),
+            })
+        };
+
+        let res = send(1, Method::POST, "/unseal", Body::empty())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // The first key only needs the root key
+        let res = send(
+            1,
+            Method::POST,
+            "/v1/unseal/keys/create",
+            json_body(add("alice")),
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let alice: UnsealKey = serde_json::from_slice(&body).unwrap();
+
+        let res = send(
+            1,
+            Method::POST,
+            "/v1/unseal/keys/create",
+            json_body(add("bob")),
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        // Bob's key isn't registered, so he can't approve adding it himself
+        let mut body = add("bob");
+        body["approval"] = approval("bob").await;
+        let res = send(1, Method::POST, "/v1/unseal/keys/create", json_body(body))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let mut body = add("bob");
+        body["approval"] = approval("alice").await;
+        let res = send(2, Method::POST, "/v1/unseal/keys/create", json_body(body))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+
+        // Approvals can't be used twice
+        let approval_by_bob = approval("bob").await;
+        let res = send(
+            2,
+            Method::DELETE,
+            "/v1/unseal/keys/delete",
+            json_body(json!({"fingerprint": alice.key.fingerprint, "approval": approval_by_bob})),
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = send(
+            2,
+            Method::DELETE,
+            "/v1/unseal/keys/delete",
+            json_body(json!({"fingerprint": "SHA256:abc", "approval": approval_by_bob})),
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+}
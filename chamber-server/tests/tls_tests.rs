@@ -0,0 +1,121 @@
+#[cfg(test)]
+mod tests {
+    use axum::{routing::get, Extension, Router};
+    use chamber_server::auth::ClientCertificate;
+    use chamber_server::tls::{self, TlsConfig, TlsError};
+    use std::path::PathBuf;
+    use tokio::net::TcpListener;
+
+    // Made with openssl: a CA, a server certificate for localhost and a client certificate for
+    // deploy-bot signed by it, plus self-signed deploy-bot certificates that the CA didn't sign,
+    // one of them for the payments namespace.
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/tls")
+            .join(name)
+    }
+
+    fn config(client_ca: bool) -> TlsConfig {
+        TlsConfig {
+            cert: fixture("server.pem"),
+            key: fixture("server.key"),
+            client_ca: client_ca.then(|| fixture("ca.pem")),
+        }
+    }
+
+    fn client(identity: Option<&str>) -> reqwest::Client {
+        let ca = std::fs::read(fixture("ca.pem")).unwrap();
+        let mut builder = reqwest::Client::builder()
+            .add_root_certificate(reqwest::Certificate::from_pem(&ca).unwrap());
+
+        if let Some(name) = identity {
+            let cert = std::fs::read(fixture(&format!("{name}.pem"))).unwrap();
+            let key = std::fs::read(fixture(&format!("{name}.key"))).unwrap();
+            builder = builder.identity(reqwest::Identity::from_pkcs8_pem(&cert, &key).unwrap());
+        }
+
+        builder.build().unwrap()
+    }
+
+    async fn whoami(cert: Extension<Option<ClientCertificate>>) -> String {
+        match cert.0 {
+            Some(cert) => cert.username,
+            None => "anonymous".to_string(),
+        }
+    }
+
+    #[test]
+    fn server_config_loads_pem_files() {
+        assert!(config(false).server_config().is_ok());
+        assert!(config(true).server_config().is_ok());
+
+        let missing = TlsConfig {
+            cert: fixture("missing.pem"),
+            ..config(false)
+        };
+        assert!(matches!(missing.server_config(), Err(TlsError::Io(..))));
+
+        let swapped = TlsConfig {
+            cert: fixture("server.key"),
+            ..config(false)
+        };
+        assert!(matches!(
+            swapped.server_config(),
+            Err(TlsError::NoCertificates(_))
+        ));
+    }
+
+    #[test]
+    fn client_certificate_uses_the_common_name() {
+        let pem = std::fs::read(fixture("client.pem")).unwrap();
+        let der = rustls_pemfile::certs(&mut pem.as_slice())
+            .next()
+            .unwrap()
+            .unwrap();
+
+        let cert = tls::client_certificate(&der).unwrap();
+
+        assert_eq!(cert.username, "deploy-bot");
+        assert_eq!(cert.namespace, "default");
+        assert!(cert.subject.contains("O=Chamber"));
+    }
+
+    #[test]
+    fn client_certificates_name_their_namespace() {
+        let pem = std::fs::read(fixture("payments.pem")).unwrap();
+        let der = rustls_pemfile::certs(&mut pem.as_slice())
+            .next()
+            .unwrap()
+            .unwrap();
+
+        let cert = tls::client_certificate(&der).unwrap();
+
+        assert_eq!(cert.username, "deploy-bot");
+        assert_eq!(cert.namespace, "payments");
+    }
+
+    #[tokio::test]
+    async fn client_certificates_are_verified_during_the_handshake() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let router = Router::new().route("/whoami", get(whoami));
+
+        tokio::spawn(tls::serve(
+            listener,
+            config(true).server_config().unwrap(),
+            router,
+        ));
+
+        let url = format!("https://localhost:{port}/whoami");
+
+        let res = client(Some("client")).get(&url).send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "deploy-bot");
+
+        // Certificates are optional, so password logins still work
+        let res = client(None).get(&url).send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "anonymous");
+
+        // But one that the CA didn't sign fails the handshake
+        assert!(client(Some("other")).get(&url).send().await.is_err());
+    }
+}
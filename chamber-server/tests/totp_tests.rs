@@ -0,0 +1,71 @@
+#[cfg(test)]
+mod tests {
+    use chamber_core::totp::{
+        code_at, generate_recovery_codes, generate_secret, hash_recovery_code, otpauth_uri,
+        time_step, verify_code, RECOVERY_CODE_COUNT,
+    };
+
+    // The SHA-1 secret from RFC 6238, base32 encoded
+    static RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn codes_match_the_rfc_test_vectors() {
+        assert_eq!(code_at(RFC_SECRET, time_step(59)).unwrap(), "287082");
+        assert_eq!(
+            code_at(RFC_SECRET, time_step(1111111109)).unwrap(),
+            "081804"
+        );
+        assert_eq!(
+            code_at(RFC_SECRET, time_step(1234567890)).unwrap(),
+            "005924"
+        );
+    }
+
+    #[test]
+    fn codes_are_accepted_within_one_step_of_drift() {
+        let now = 1234567890;
+        let code = code_at(RFC_SECRET, time_step(now)).unwrap();
+
+        assert_eq!(verify_code(RFC_SECRET, &code, now), Some(time_step(now)));
+        assert_eq!(
+            verify_code(RFC_SECRET, &code, now + 30),
+            Some(time_step(now))
+        );
+        assert_eq!(
+            verify_code(RFC_SECRET, &code, now - 30),
+            Some(time_step(now))
+        );
+        assert_eq!(verify_code(RFC_SECRET, &code, now + 90), None);
+        assert_eq!(verify_code(RFC_SECRET, "000000", now), None);
+    }
+
+    #[test]
+    fn generated_secrets_can_be_used() {
+        let secret = generate_secret().unwrap();
+        let code = code_at(&secret, 1).unwrap();
+
+        assert_eq!(secret.len(), 32);
+        assert_eq!(code.len(), 6);
+
+        let uri = otpauth_uri("Chamber", "some user", &secret);
+        assert!(uri.starts_with("otpauth://totp/Chamber:some%20user?"));
+        assert!(uri.contains(&format!("secret={secret}")));
+    }
+
+    #[test]
+    fn recovery_codes_are_unique_and_hashed_case_insensitively() {
+        let codes = generate_recovery_codes().unwrap();
+
+        let mut deduped = codes.clone();
+        deduped.sort();
+        deduped.dedup();
+
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert_eq!(deduped.len(), RECOVERY_CODE_COUNT);
+        assert_eq!(
+            hash_recovery_code(&codes[0]),
+            hash_recovery_code(&format!(" {} ", codes[0].to_uppercase()))
+        );
+        assert_ne!(hash_recovery_code(&codes[0]), hash_recovery_code(&codes[1]));
+    }
+}
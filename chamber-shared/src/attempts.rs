@@ -0,0 +1,24 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A failed login or unseal, kept for admins to review.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FailedAttempt {
+    /// Either `login` or `unseal`.
+    pub kind: String,
+    pub username: Option<String>,
+    pub ip: Option<String>,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The most failed attempts that a single request can return.
+pub const MAX_FAILED_ATTEMPTS: i64 = 1000;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FailedAttemptsParams {
+    /// Defaults to 100, and is clamped to between 1 and [`MAX_FAILED_ATTEMPTS`].
+    pub limit: Option<i64>,
+}
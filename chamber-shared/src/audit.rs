@@ -0,0 +1,31 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A change to a secret that's kept for namespace admins to review.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AuditEntry {
+    /// Only `promote` for now.
+    pub action: String,
+    pub username: String,
+    pub key: String,
+    /// The environment that the value came from, for promotions.
+    pub from_environment: Option<String>,
+    /// The environment that was changed.
+    pub environment: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The most audit entries that a single request can return.
+pub const MAX_AUDIT_ENTRIES: i64 = 1000;
+
+/// The query string of `GET /v1/audit`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct AuditLogQuery {
+    /// The most entries to return, newest first. Defaults to 100, and is clamped to between 1
+    /// and [`MAX_AUDIT_ENTRIES`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+}
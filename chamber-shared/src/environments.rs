@@ -0,0 +1,26 @@
+use crate::check_name;
+use serde::{Deserialize, Serialize};
+
+/// The environment that requests use when they don't name one. Everything from before
+/// environments were added lives here.
+pub static DEFAULT_ENVIRONMENT: &str = "default";
+/// Picks the environment, such as `staging` or `prod`, that a secrets request reads from and
+/// writes to.
+pub static ENVIRONMENT_HEADER: &str = "x-chamber-environment";
+
+/// Environment names follow the same rules as namespace names, since they're sent in a header
+/// and typed on the command line too.
+pub fn check_environment_name(name: &str) -> Result<(), String> {
+    check_name("environment", name)
+}
+
+/// Copies a secret's value from one environment to another.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PromoteSecretParams {
+    pub key: String,
+    /// The environment to copy the value from, such as `staging`.
+    pub from: String,
+    /// The environment to copy the value to, such as `prod`.
+    pub to: String,
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The header that carries the ID of a request, which is also included in error bodies.
+pub static REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// A machine-readable error code. These are stable, so clients can match on them
+/// rather than on the message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    Unauthorised,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    Conflict,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    UnprocessableEntity,
+    Locked,
+    TooManyRequests,
+    TwoFactorRequired,
+    TwoFactorEnrollmentRequired,
+    Internal,
+    /// A code from a newer server that this client doesn't know about yet.
+    #[serde(other)]
+    Unknown,
+}
+
+impl ErrorCode {
+    /// The HTTP status that the server responds with for this code.
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::BadRequest => 400,
+            Self::Unauthorised | Self::TwoFactorRequired => 401,
+            Self::Forbidden | Self::TwoFactorEnrollmentRequired => 403,
+            Self::NotFound => 404,
+            Self::MethodNotAllowed => 405,
+            Self::Conflict => 409,
+            Self::PayloadTooLarge => 413,
+            Self::UnsupportedMediaType => 415,
+            Self::UnprocessableEntity => 422,
+            Self::Locked => 423,
+            Self::TooManyRequests => 429,
+            Self::Internal | Self::Unknown => 500,
+        }
+    }
+
+    /// The code for a status, for errors that don't have a more specific one.
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            400 => Self::BadRequest,
+            401 => Self::Unauthorised,
+            403 => Self::Forbidden,
+            404 => Self::NotFound,
+            405 => Self::MethodNotAllowed,
+            409 => Self::Conflict,
+            413 => Self::PayloadTooLarge,
+            415 => Self::UnsupportedMediaType,
+            422 => Self::UnprocessableEntity,
+            423 => Self::Locked,
+            429 => Self::TooManyRequests,
+            _ => Self::Internal,
+        }
+    }
+}
+
+/// The body of every error response.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ErrorBody {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Matches the `x-request-id` header, and the server's logs for the request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl ErrorBody {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            request_id: None,
+        }
+    }
+}
+
+impl fmt::Display for ErrorBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+
+        if let Some(id) = &self.request_id {
+            write!(f, " (request ID: {id})")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ErrorBody {}
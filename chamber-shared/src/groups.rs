@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Group {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Users that belong to this group directly.
+    #[serde(default)]
+    pub members: Vec<String>,
+    /// Groups nested inside this one. Their members inherit this group's grants.
+    #[serde(default)]
+    pub subgroups: Vec<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub access_level: i32,
+}
+
+/// Names a group, for the legacy routes that take it in the body.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GroupParams {
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateGroupParams {
+    pub name: String,
+    pub description: Option<String>,
+    pub roles: Option<Vec<String>>,
+    pub access_level: Option<i32>,
+}
+
+/// Changes to a group. Anything left out is kept.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GroupChanges {
+    pub description: Option<String>,
+    pub roles: Option<Vec<String>>,
+    pub access_level: Option<i32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GroupMembersParams {
+    pub name: String,
+    pub users: Option<Vec<String>>,
+    pub groups: Option<Vec<String>>,
+}
+
+/// Users and groups to add to or remove from a group.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GroupMembers {
+    pub users: Option<Vec<String>>,
+    pub groups: Option<Vec<String>>,
+}
@@ -1,6 +1,41 @@
 use serde::{Deserialize, Serialize};
 
+pub mod attempts;
+pub mod audit;
+pub mod environments;
+pub mod errors;
+pub mod groups;
+pub mod namespaces;
+pub mod policy;
+pub mod roles;
+pub mod secrets;
+pub mod ssh;
+pub mod users;
+
+/// Checks a name that ends up in a header and on the command line, such as a namespace's or an
+/// environment's. `kind` is what's being named, and is used in the error.
+pub fn check_name(kind: &str, name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > 63 {
+        return Err(format!(
+            "A {kind} name has to be between 1 and 63 characters long"
+        ));
+    }
+
+    if !name
+        .chars()
+        .all(|x| x.is_ascii_lowercase() || x.is_ascii_digit() || x == '-')
+        || name.starts_with('-')
+    {
+        return Err(format!(
+            "{name} isn't a valid {kind} name, use lowercase letters, digits and dashes"
+        ));
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct AuthBody {
    pub access_token: String,
    pub token_type: String,
@@ -14,9 +49,3 @@ impl AuthBody {
         }
     }
 }
-
-#[derive(Serialize, Debug, Deserialize)]
-pub struct SecretPublic {
-    pub key: String,
-    pub value: String
-}
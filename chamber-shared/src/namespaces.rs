@@ -0,0 +1,26 @@
+use crate::check_name;
+use serde::{Deserialize, Serialize};
+
+/// The namespace that requests use when they don't name one. Everything from before
+/// namespaces were added lives here.
+pub static DEFAULT_NAMESPACE: &str = "default";
+/// Picks the namespace that a root key request acts on. Tokens are always tied to the
+/// namespace that their user logged in to.
+pub static NAMESPACE_HEADER: &str = "x-chamber-namespace";
+
+/// A tenant of the instance. Secrets, users, roles, groups and policies all belong to one,
+/// and nothing in one namespace can see into another.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Namespace {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// Namespace names end up in headers and on the command line, so they're kept to lowercase
+/// letters, digits and dashes.
+pub fn check_namespace_name(name: &str) -> Result<(), String> {
+    check_name("namespace", name)
+}
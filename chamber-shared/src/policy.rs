@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// An action that a policy rule can grant on the secrets it matches.
+/// `Deny` is special: a matching rule with it blocks every other capability.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Capability {
+    Read,
+    List,
+    Create,
+    Update,
+    Delete,
+    Deny,
+}
+
+impl Capability {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::List => "list",
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Delete => "delete",
+            Self::Deny => "deny",
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Capability {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(Self::Read),
+            "list" => Ok(Self::List),
+            "create" => Ok(Self::Create),
+            "update" => Ok(Self::Update),
+            "delete" => Ok(Self::Delete),
+            "deny" => Ok(Self::Deny),
+            _ => Err(format!("Unknown capability: {s}")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PolicyRule {
+    /// Glob matched against secret keys: `*` matches any run of characters and `?` matches one.
+    pub path: String,
+    /// If not empty, a secret needs at least one of these tags for the rule to match.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// If set, the rule only matches secrets at or below this access level.
+    #[serde(default)]
+    pub max_access_level: Option<i32>,
+    /// If true, the rule only matches secrets whose role whitelist is empty or shares a role with the user.
+    #[serde(default)]
+    pub check_whitelist: bool,
+    /// If not empty, the rule only matches secrets in one of these environments.
+    #[serde(default)]
+    pub environments: Vec<String>,
+    pub capabilities: Vec<Capability>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Policy {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub rules: Vec<PolicyRule>,
+    /// Users that this policy is attached to.
+    #[serde(default)]
+    pub users: Vec<String>,
+    /// Roles that this policy is attached to.
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+/// The outcome of evaluating a capability against a secret.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Decision {
+    pub allowed: bool,
+    /// Name of the policy whose rule produced the decision. `None` means nothing matched.
+    pub policy: Option<String>,
+    pub rule: Option<PolicyRule>,
+}
+
+/// Why a user can read a secret.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AccessGrant {
+    pub username: String,
+    /// Groups that the user inherits access levels and roles from.
+    pub groups: Vec<String>,
+    /// Roles of the user that the granting rule depended on.
+    pub roles: Vec<String>,
+    pub decision: Decision,
+}
+
+/// Every user and role that can read a given secret.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SecretAccess {
+    pub key: String,
+    pub users: Vec<AccessGrant>,
+    pub roles: Vec<String>,
+}
+
+/// A secret that a user can reach, and what they can do with it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReachableSecret {
+    pub key: String,
+    pub capabilities: Vec<Capability>,
+    /// The decision for reading the secret.
+    pub decision: Decision,
+}
+
+/// Every secret that a given user can reach.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserAccess {
+    pub username: String,
+    pub groups: Vec<String>,
+    pub secrets: Vec<ReachableSecret>,
+}
+
+/// Names a policy, for the legacy routes that take it in the body.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PolicyParams {
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AttachPolicyParams {
+    pub name: String,
+    pub users: Option<Vec<String>>,
+    pub roles: Option<Vec<String>>,
+}
+
+/// Users and roles to attach a policy to or detach it from.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PolicyAttachments {
+    pub users: Option<Vec<String>>,
+    pub roles: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SecretAccessParams {
+    pub key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserAccessParams {
+    pub username: String,
+}
+
+/// A capability to check for a user on a secret, without changing anything.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SimulateParams {
+    pub username: String,
+    pub key: String,
+    /// Defaults to `read`.
+    pub capability: Option<Capability>,
+}
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Role {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// A role along with everything that references it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RoleUsage {
+    pub name: String,
+    pub description: String,
+    /// Users that have been granted this role.
+    pub users: Vec<String>,
+    /// Groups that grant this role to their members.
+    pub groups: Vec<String>,
+    /// Secrets with this role in their whitelist.
+    pub secrets: Vec<String>,
+}
+
+/// Names a role, for the legacy routes that take it in the body.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RoleParams {
+    pub name: String,
+}
@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// The header that carries the cursor for the next page of a secret listing. It's left out
+/// on the last page.
+pub static NEXT_CURSOR_HEADER: &str = "x-next-cursor";
+
+/// The most secrets that a single page can hold.
+pub const MAX_PAGE_SIZE: i64 = 1000;
+
+/// A secret without its value.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct SecretInfo {
+    pub key: String,
+    pub tags: Vec<String>,
+    pub access_level: i32,
+    pub role_whitelist: Vec<String>,
+}
+
+/// Checks that a key is a `/`-separated path, such as `payments/prod/db/password`, without
+/// any empty segments.
+pub fn check_key_path(key: &str) -> Result<(), String> {
+    if key.is_empty() {
+        return Err("Keys can't be empty".to_string());
+    }
+
+    if key.split('/').any(str::is_empty) {
+        return Err(format!(
+            "{key} has an empty path segment: keys can't start or end with / or contain //"
+        ));
+    }
+
+    Ok(())
+}
+
+/// A decrypted secret.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SecretPublic {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateSecretParams {
+    pub key: String,
+    pub value: String,
+    pub tags: Option<Vec<String>>,
+    pub access_level: Option<i32>,
+    pub role_whitelist: Option<Vec<String>>,
+}
+
+/// Names a secret, or a tag for the legacy by-tag route.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SecretKey {
+    pub key: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ListSecretsArgs {
+    pub tag_filter: Option<String>,
+}
+
+/// The query string of `GET /v1/secrets`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct ListSecretsQuery {
+    /// A single tag. This is kept for older clients, and is added to `tags`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// A comma-separated list of tags.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<String>,
+    /// Whether a secret needs `all` of the tags or `any` of them. Defaults to `all`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag_match: Option<TagMatch>,
+    /// A key prefix, such as `payments/`, or a glob such as `payments/*/stripe`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// Defaults to `key`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<SecretSort>,
+    /// Defaults to `asc`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<SortOrder>,
+    /// The `x-next-cursor` header of the previous page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    /// The most secrets to return, up to 1000. Every match is returned if this is left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+}
+
+/// How a listing's tags are combined.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum TagMatch {
+    /// Secrets that have every tag.
+    #[default]
+    All,
+    /// Secrets that have at least one of the tags.
+    Any,
+}
+
+impl FromStr for TagMatch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Self::All),
+            "any" => Ok(Self::Any),
+            _ => Err(format!("Unknown tag match: {s}")),
+        }
+    }
+}
+
+/// What a listing is sorted by. Secrets with the same creation time are sorted by key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum SecretSort {
+    #[default]
+    Key,
+    Created,
+}
+
+impl FromStr for SecretSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "key" => Ok(Self::Key),
+            "created" => Ok(Self::Created),
+            _ => Err(format!("Unknown sort: {s}")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Which secrets a listing returns, and in what order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SecretFilter {
+    pub tags: Vec<String>,
+    pub tag_match: TagMatch,
+    /// A key prefix, or a glob if it contains `*` or `?`.
+    pub key: Option<String>,
+    pub sort: SecretSort,
+    pub order: SortOrder,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+impl SecretFilter {
+    /// Only secrets with the given tag, which is what the legacy routes filter on.
+    pub fn tagged(tag: Option<String>) -> Self {
+        Self {
+            tags: tag.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<ListSecretsQuery> for SecretFilter {
+    fn from(query: ListSecretsQuery) -> Self {
+        let mut tags: Vec<String> = query.tag.into_iter().collect();
+
+        if let Some(list) = query.tags {
+            tags.extend(
+                list.split(',')
+                    .map(|x| x.trim())
+                    .filter(|x| !x.is_empty())
+                    .map(String::from),
+            );
+        }
+
+        Self {
+            tags,
+            tag_match: query.tag_match.unwrap_or_default(),
+            key: query.key.filter(|x| !x.is_empty()),
+            sort: query.sort.unwrap_or_default(),
+            order: query.order.unwrap_or_default(),
+            cursor: query.cursor,
+            limit: query.limit.map(|x| x.clamp(1, MAX_PAGE_SIZE)),
+        }
+    }
+}
+
+/// A page of a secret listing.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SecretPage {
+    pub secrets: Vec<SecretInfo>,
+    /// Where the next page starts, if there is one.
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateSecret {
+    pub key: String,
+    /// The secret's new tags, which replace the old ones.
+    pub update_data: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SecretTags {
+    pub tags: Vec<String>,
+}
+
+/// The query string of `GET /v1/folders`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct FolderQuery {
+    /// A folder, such as `payments/prod`. The top level is listed if this is left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+}
+
+/// What's directly inside a folder.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FolderListing {
+    /// The folder, without a trailing `/`. This is empty for the top level.
+    pub prefix: String,
+    /// The paths of the folders inside it, such as `payments/prod`.
+    pub folders: Vec<String>,
+    /// The secrets inside it that aren't in one of its folders.
+    pub secrets: Vec<SecretInfo>,
+}
+
+/// Moves or renames a secret, or a folder along with everything in it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MoveSecretsParams {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MovedSecret {
+    pub from: String,
+    pub to: String,
+}
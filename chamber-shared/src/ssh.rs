@@ -0,0 +1,99 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The SSHSIG namespace for login challenges, so that signatures made for other purposes
+/// (like signing git commits) can't be replayed as a login.
+pub static SSH_LOGIN_NAMESPACE: &str = "chamber-login";
+/// The SSHSIG namespace for unseal challenges.
+pub static SSH_UNSEAL_NAMESPACE: &str = "chamber-unseal";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SshKey {
+    /// The SHA-256 fingerprint, as shown by `ssh-keygen -l`.
+    pub fingerprint: String,
+    /// The public key in OpenSSH format, without the comment.
+    pub public_key: String,
+    pub comment: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AddSshKeyParams {
+    /// A public key in OpenSSH format, like the contents of `~/.ssh/id_ed25519.pub`.
+    pub public_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RemoveSshKeyParams {
+    pub fingerprint: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SshChallengeParams {
+    /// The Chamber namespace of the user, not to be confused with the SSHSIG namespace.
+    /// Defaults to the `default` namespace.
+    pub namespace: Option<String>,
+    pub username: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SshChallenge {
+    /// Sign this with the key, using `namespace`.
+    pub nonce: String,
+    pub namespace: String,
+    pub expires_in_secs: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SshLoginParams {
+    /// Has to match the namespace that the challenge was issued for.
+    pub namespace: Option<String>,
+    pub username: String,
+    pub nonce: String,
+    /// An armored SSHSIG signature of the nonce, as made by `ssh-keygen -Y sign`.
+    pub signature: String,
+    /// A TOTP or recovery code, needed once the user has enabled two-factor authentication.
+    pub code: Option<String>,
+}
+
+/// A public key belonging to an operator who is allowed to unseal the instance.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UnsealKey {
+    pub name: String,
+    pub key: SshKey,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AddUnsealKeyParams {
+    /// Who the key belongs to, so that it can be told apart from the others.
+    pub name: String,
+    /// A public key in OpenSSH format, like the contents of `~/.ssh/id_ed25519.pub`.
+    pub public_key: String,
+    /// A challenge signed by one of the registered unseal keys, needed once there are any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval: Option<UnsealParams>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RemoveUnsealKeyParams {
+    pub fingerprint: String,
+    /// A challenge signed by one of the registered unseal keys, which can be the one being removed.
+    pub approval: UnsealParams,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UnsealParams {
+    pub nonce: String,
+    /// An armored SSHSIG signature of the nonce, made with a registered unseal key.
+    pub signature: String,
+}
@@ -0,0 +1,175 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// What the logged-in user can see about themselves.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserProfile {
+    pub username: String,
+    pub namespace: String,
+    /// Whether the user can manage the users, roles, groups and policies of their namespace.
+    pub namespace_admin: bool,
+    pub access_level: i32,
+    pub roles: Vec<String>,
+    /// Groups that the user belongs to, directly or through nesting.
+    pub groups: Vec<String>,
+    /// The access level after merging in the user's groups.
+    pub effective_access_level: i32,
+    /// The roles after merging in the user's groups.
+    pub effective_roles: Vec<String>,
+    pub two_factor_enabled: bool,
+    /// Whether the user's access level means that they have to use two-factor authentication.
+    pub two_factor_required: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ChangePasswordParams {
+    pub old_password: String,
+    pub new_password: String,
+}
+
+/// A user as shown to admins. Never includes the password hash.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserSummary {
+    pub username: String,
+    pub access_level: i32,
+    pub roles: Vec<String>,
+    pub namespace_admin: bool,
+    pub disabled: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub two_factor_enabled: bool,
+}
+
+/// Creates the first admin of a fresh instance.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct InitParams {
+    /// Defaults to `root`.
+    pub username: Option<String>,
+    /// If left out, a password is generated and returned.
+    pub password: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct InitResponse {
+    pub username: String,
+    /// Only set if the password was generated.
+    pub password: Option<String>,
+}
+
+/// Returned when starting TOTP enrollment. The secret only becomes active once a code is confirmed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TwoFactorEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+/// Single-use codes for logging in without an authenticator. Only ever shown once.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TwoFactorSettings {
+    /// Users with an access level above this have to use two-factor authentication.
+    /// `None` means that it's optional for everyone.
+    pub required_above: Option<i32>,
+}
+
+/// Names a user, for the legacy routes that take it in the body.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserParams {
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateUserParams {
+    pub username: String,
+    pub password: String,
+    pub access_level: Option<i32>,
+    pub roles: Option<Vec<String>>,
+    /// Lets the user manage their namespace without the root key.
+    pub namespace_admin: Option<bool>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateUserParams {
+    pub username: String,
+    pub access_level: Option<i32>,
+    pub roles: Option<Vec<String>>,
+    pub namespace_admin: Option<bool>,
+}
+
+/// Changes to a user. Anything left out is kept.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserChanges {
+    pub access_level: Option<i32>,
+    pub roles: Option<Vec<String>>,
+    pub namespace_admin: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserExpiry {
+    /// Leaving this out or setting it to null removes the expiry.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserExpiryParams {
+    pub name: String,
+    /// Leaving this out or setting it to null removes the expiry.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserLoginParams {
+    /// Defaults to the `default` namespace.
+    pub namespace: Option<String>,
+    pub username: String,
+    pub password: String,
+    /// A TOTP or recovery code, needed once the user has enabled two-factor authentication.
+    pub code: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CertLoginParams {
+    /// The namespace of the certificate's user. This comes from the certificate, so if it's
+    /// given it has to match.
+    pub namespace: Option<String>,
+    pub code: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TwoFactorPasswordParams {
+    pub password: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TwoFactorCodeParams {
+    pub code: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DisableTwoFactorParams {
+    pub password: String,
+    pub code: String,
+}